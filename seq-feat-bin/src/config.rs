@@ -0,0 +1,1124 @@
+//! Feature set configuration files
+//!
+//! A config lists the registered features to compute, with their parameters
+//! and optionally their column names, so a feature set can be changed
+//! without recompiling. It can be TOML, YAML or JSON, picked by the file
+//! extension, for example
+//!
+//! ```toml
+//! name = "prefilter"
+//!
+//! [[features]]
+//! feature = "gc_content"
+//!
+//! [[features]]
+//! feature = "tsallis"
+//! k_max = 6
+//! q = 3.0
+//!
+//! [[features]]
+//! feature = "fourier"
+//! name = "fourier_z_curve"
+//! mapping = "z_curve"
+//! ```
+//!
+//! Parameters left out take their defaults, and the config written to the
+//! output metadata has them all filled in. Configs of protein features set
+//! `protein = true`.
+use crate::{Args, MAX_KMER_TABLE_K, MAX_REDUCED_K};
+use anyhow::{anyhow, Context, Result};
+use seq_feat::feature::{
+    Aac, Alphabet, Anf, Cksnap, CodonPosition, Column, Ctd, Dpc, Fcgr, Feature, Fickett, Fourier,
+    Fractal, GcContent, KmerGraphStats, KnownStructure, Length, Mfe, NullScores, Nussinov,
+    OrfPeptide, Partition, Period3Profile, Period3Snr, PropertyAutocorrelation, Pseknc,
+    ReducedKmers, Registry, Shannon, SpectralFingerprint, StructureTriplets, Tsallis,
+    UnpairedProbability, Value, WaveletStats, ZCurve,
+};
+use seq_feat::stats::mapping::NumericMapping;
+use seq_feat::stats::pseknc::{PropertyTable, PseKncParams};
+use seq_feat::structure::nussinov::NussinovParams;
+use seq_feat::structure::params::EnergyParams;
+use serde::de::{DeserializeOwned, Error as _};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+fn period3_step() -> usize {
+    Period3Profile::default().step
+}
+
+fn kmer_min() -> u64 {
+    1
+}
+
+fn kmer_max() -> u64 {
+    4
+}
+
+fn tsallis_q() -> f64 {
+    2.0
+}
+
+fn mapping() -> String {
+    "eiip".to_owned()
+}
+
+fn gap_max() -> usize {
+    3
+}
+
+fn min_loop() -> usize {
+    3
+}
+
+fn wobble() -> bool {
+    true
+}
+
+fn dangles() -> bool {
+    true
+}
+
+fn anf_length() -> usize {
+    100
+}
+
+fn pseknc_k() -> usize {
+    PseKncParams::default().k
+}
+
+fn pseknc_lambda() -> usize {
+    PseKncParams::default().lambda
+}
+
+fn pseknc_weight() -> f64 {
+    PseKncParams::default().weight
+}
+
+fn pseknc_kind() -> String {
+    PseKncParams::default().kind.name().to_owned()
+}
+
+fn autocorrelation_kind() -> String {
+    "dac".to_owned()
+}
+
+fn lag() -> usize {
+    3
+}
+
+fn psd_bins() -> usize {
+    32
+}
+
+fn psd_mapping() -> String {
+    "binary".to_owned()
+}
+
+fn window() -> String {
+    "hann".to_owned()
+}
+
+fn wavelet() -> String {
+    "haar".to_owned()
+}
+
+fn levels() -> usize {
+    4
+}
+
+fn zcurve_params() -> usize {
+    9
+}
+
+fn fcgr_k() -> usize {
+    4
+}
+
+fn fractal_signal() -> String {
+    "dna_walk".to_owned()
+}
+
+fn higuchi_kmax() -> usize {
+    8
+}
+
+fn graph_k() -> usize {
+    3
+}
+
+fn shuffles() -> usize {
+    100
+}
+
+fn shuffle_k() -> usize {
+    2
+}
+
+fn seed() -> u64 {
+    1
+}
+
+fn reduced_alphabet() -> String {
+    "triad7".to_owned()
+}
+
+fn reduced_k() -> usize {
+    3
+}
+
+/// A built-in feature and its parameters, tagged with its registered name
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "feature", rename_all = "snake_case", deny_unknown_fields)]
+pub enum FeatureKind {
+    GcContent,
+    Fickett,
+    Period3Snr,
+    Period3Profile {
+        window: usize,
+        #[serde(default = "period3_step")]
+        step: usize,
+    },
+    Shannon {
+        #[serde(default = "kmer_min")]
+        k_min: u64,
+        #[serde(default = "kmer_max")]
+        k_max: u64,
+    },
+    Tsallis {
+        #[serde(default = "kmer_min")]
+        k_min: u64,
+        #[serde(default = "kmer_max")]
+        k_max: u64,
+        #[serde(default = "tsallis_q")]
+        q: f64,
+    },
+    CodonPosition,
+    OrfCodonPosition,
+    OrfPeptide,
+    Fourier {
+        #[serde(default = "mapping")]
+        mapping: String,
+    },
+    Cksnap {
+        #[serde(default = "gap_max")]
+        gap_max: usize,
+    },
+    /// Structure features take a ViennaRNA parameter file, or use the
+    /// embedded parameters
+    Mfe {
+        #[serde(default)]
+        energy_params: Option<String>,
+        #[serde(default = "dangles")]
+        dangles: bool,
+    },
+    Partition {
+        #[serde(default)]
+        energy_params: Option<String>,
+        #[serde(default = "dangles")]
+        dangles: bool,
+    },
+    UnpairedProbability {
+        #[serde(default)]
+        energy_params: Option<String>,
+        #[serde(default = "dangles")]
+        dangles: bool,
+    },
+    Nussinov {
+        #[serde(default = "min_loop")]
+        min_loop: usize,
+        #[serde(default = "wobble")]
+        wobble: bool,
+        #[serde(default)]
+        max_span: Option<usize>,
+    },
+    /// Features of the structure given with each sequence
+    Structure,
+    StructureTriplets,
+    /// Z-score and p-value of the first column of another feature against
+    /// shuffles, with `lower` or `upper` values counting as extreme
+    NullScore {
+        of: Box<FeatureKind>,
+        tail: String,
+        #[serde(default = "shuffles")]
+        shuffles: usize,
+        #[serde(default = "shuffle_k")]
+        shuffle_k: usize,
+        #[serde(default = "seed")]
+        seed: u64,
+    },
+    Anf {
+        #[serde(default = "anf_length")]
+        length: usize,
+    },
+    /// Property features take a CSV of dinucleotide properties, or use the
+    /// built-in RNA table
+    Pseknc {
+        #[serde(default = "pseknc_k")]
+        k: usize,
+        #[serde(default = "pseknc_lambda")]
+        lambda: usize,
+        #[serde(default = "pseknc_weight")]
+        weight: f64,
+        #[serde(default = "pseknc_kind")]
+        kind: String,
+        #[serde(default)]
+        property_table: Option<String>,
+    },
+    Autocorrelation {
+        #[serde(default = "autocorrelation_kind")]
+        kind: String,
+        #[serde(default = "lag")]
+        lag: usize,
+        #[serde(default)]
+        property_table: Option<String>,
+    },
+    SpectralFingerprint {
+        #[serde(default = "psd_bins")]
+        bins: usize,
+        #[serde(default = "psd_mapping")]
+        mapping: String,
+        #[serde(default = "window")]
+        window: String,
+    },
+    Wavelet {
+        #[serde(default = "wavelet")]
+        wavelet: String,
+        #[serde(default = "mapping")]
+        mapping: String,
+        #[serde(default = "levels")]
+        levels: usize,
+    },
+    Zcurve {
+        #[serde(default = "zcurve_params")]
+        params: usize,
+    },
+    OrfZcurve {
+        #[serde(default = "zcurve_params")]
+        params: usize,
+    },
+    Fcgr {
+        #[serde(default = "fcgr_k")]
+        k: usize,
+    },
+    Fractal {
+        #[serde(default = "fractal_signal")]
+        signal: String,
+        #[serde(default = "higuchi_kmax")]
+        higuchi_kmax: usize,
+    },
+    KmerGraph {
+        #[serde(default = "graph_k")]
+        k: usize,
+    },
+    /// Protein features, for configs with `protein = true`
+    Length,
+    Aac,
+    Dpc,
+    ReducedKmers {
+        #[serde(default = "reduced_alphabet")]
+        alphabet: String,
+        #[serde(default = "reduced_k")]
+        k: usize,
+    },
+    Ctd,
+}
+
+/// Load energy parameters from a file, or the embedded ones
+fn energy_params(path: &Option<String>) -> Result<EnergyParams> {
+    match path {
+        Some(path) => {
+            let file = fs::File::open(path).with_context(|| format!("Opening {}", path))?;
+            EnergyParams::from_vienna_par(io::BufReader::new(file))
+        }
+        None => Ok(EnergyParams::turner2004()),
+    }
+}
+
+/// Load dinucleotide properties from a file, or the built-in RNA table
+fn property_table(path: &Option<String>) -> Result<PropertyTable> {
+    match path {
+        Some(path) => {
+            let file = fs::File::open(path).with_context(|| format!("Opening {}", path))?;
+            PropertyTable::from_csv(io::BufReader::new(file))
+        }
+        None => Ok(PropertyTable::rna()),
+    }
+}
+
+/// Check the k of a feature with a table of all kmers, up to `max`
+fn kmer_table_k(feature: &str, k: usize, max: u64) -> Result<usize> {
+    if k == 0 || k as u64 > max {
+        return Err(anyhow!(
+            "Feature {} needs k from 1 to {}, not {}",
+            feature,
+            max,
+            k
+        ));
+    }
+    Ok(k)
+}
+
+/// Check the range of kmer lengths of an entropy feature
+fn kmer_range(feature: &str, k_min: u64, k_max: u64) -> Result<()> {
+    if k_min == 0 || k_min > k_max {
+        return Err(anyhow!(
+            "Feature {} needs 1 <= k_min <= k_max, not k_min {} and k_max {}",
+            feature,
+            k_min,
+            k_max
+        ));
+    }
+    Ok(())
+}
+
+/// Files read while building features, so features sharing one read it once
+#[derive(Default)]
+pub struct Loaded {
+    energy_params: HashMap<Option<String>, EnergyParams>,
+    property_tables: HashMap<Option<String>, Arc<PropertyTable>>,
+}
+
+impl Loaded {
+    fn energy_params(&mut self, path: &Option<String>, dangles: bool) -> Result<EnergyParams> {
+        if !self.energy_params.contains_key(path) {
+            self.energy_params
+                .insert(path.clone(), energy_params(path)?);
+        }
+        let mut params = self.energy_params[path].clone();
+        params.dangles = dangles;
+        Ok(params)
+    }
+
+    fn property_table(&mut self, path: &Option<String>) -> Result<Arc<PropertyTable>> {
+        if !self.property_tables.contains_key(path) {
+            self.property_tables
+                .insert(path.clone(), Arc::new(property_table(path)?));
+        }
+        Ok(self.property_tables[path].clone())
+    }
+}
+
+impl FeatureKind {
+    /// The feature with its default parameters, from its name
+    pub fn from_name(name: &str) -> Result<Self> {
+        serde_json::from_value(serde_json::json!({ "feature": name }))
+            .map_err(|_| anyhow!("Unknown feature {}", name))
+    }
+
+    /// The feature with parameters the command line gives for all features
+    /// of its kind filled in
+    fn with_args(self, args: &Args) -> Self {
+        match self {
+            FeatureKind::Mfe { .. } => FeatureKind::Mfe {
+                energy_params: args.energy_params.clone(),
+                dangles: !args.no_dangles,
+            },
+            FeatureKind::Partition { .. } => FeatureKind::Partition {
+                energy_params: args.energy_params.clone(),
+                dangles: !args.no_dangles,
+            },
+            FeatureKind::UnpairedProbability { .. } => FeatureKind::UnpairedProbability {
+                energy_params: args.energy_params.clone(),
+                dangles: !args.no_dangles,
+            },
+            FeatureKind::Nussinov { .. } => FeatureKind::Nussinov {
+                min_loop: args.nussinov_min_loop,
+                wobble: !args.nussinov_no_wobble,
+                max_span: args.nussinov_max_span,
+            },
+            FeatureKind::Pseknc {
+                k,
+                lambda,
+                weight,
+                kind,
+                ..
+            } => FeatureKind::Pseknc {
+                k,
+                lambda,
+                weight,
+                kind,
+                property_table: args.property_table.clone(),
+            },
+            FeatureKind::Autocorrelation { kind, lag, .. } => FeatureKind::Autocorrelation {
+                kind,
+                lag,
+                property_table: args.property_table.clone(),
+            },
+            kind => kind,
+        }
+    }
+
+    /// Build the feature, reading any files it needs through `loaded`
+    pub fn build(&self, loaded: &mut Loaded) -> Result<Box<dyn Feature>> {
+        Ok(match self {
+            FeatureKind::GcContent => Box::new(GcContent),
+            FeatureKind::Fickett => Box::new(Fickett),
+            FeatureKind::Period3Snr => Box::new(Period3Snr),
+            FeatureKind::Period3Profile { window, step } => Box::new(Period3Profile {
+                window: *window,
+                step: *step,
+            }),
+            FeatureKind::Shannon { k_min, k_max } => {
+                kmer_range("shannon", *k_min, *k_max)?;
+                Box::new(Shannon {
+                    kmer_min: *k_min,
+                    kmer_max: *k_max,
+                })
+            }
+            FeatureKind::Tsallis { k_min, k_max, q } => {
+                kmer_range("tsallis", *k_min, *k_max)?;
+                // The Tsallis entropy tends to the Shannon entropy as q goes
+                // to 1, but the formula divides by zero there
+                if *q == 1.0 {
+                    return Err(anyhow!(
+                        "Feature tsallis can't have q = 1, use shannon instead"
+                    ));
+                }
+                Box::new(Tsallis {
+                    kmer_min: *k_min,
+                    kmer_max: *k_max,
+                    q: *q,
+                })
+            }
+            FeatureKind::CodonPosition => Box::new(CodonPosition { orf: false }),
+            FeatureKind::OrfCodonPosition => Box::new(CodonPosition { orf: true }),
+            FeatureKind::OrfPeptide => Box::new(OrfPeptide),
+            FeatureKind::Fourier { mapping } => Box::new(Fourier {
+                mapping: mapping.parse()?,
+            }),
+            FeatureKind::Cksnap { gap_max } => Box::new(Cksnap { gap_max: *gap_max }),
+            FeatureKind::Mfe {
+                energy_params,
+                dangles,
+            } => Box::new(Mfe {
+                params: loaded.energy_params(energy_params, *dangles)?,
+            }),
+            FeatureKind::Partition {
+                energy_params,
+                dangles,
+            } => Box::new(Partition {
+                params: loaded.energy_params(energy_params, *dangles)?,
+            }),
+            FeatureKind::UnpairedProbability {
+                energy_params,
+                dangles,
+            } => Box::new(UnpairedProbability {
+                params: loaded.energy_params(energy_params, *dangles)?,
+            }),
+            FeatureKind::Nussinov {
+                min_loop,
+                wobble,
+                max_span,
+            } => Box::new(Nussinov {
+                params: NussinovParams {
+                    min_loop: *min_loop,
+                    wobble: *wobble,
+                    max_span: *max_span,
+                },
+            }),
+            FeatureKind::Structure => Box::new(KnownStructure),
+            FeatureKind::StructureTriplets => Box::new(StructureTriplets),
+            FeatureKind::NullScore {
+                of,
+                tail,
+                shuffles,
+                shuffle_k,
+                seed,
+            } => Box::new(NullScores::new(
+                of.build(loaded)?,
+                tail.parse()?,
+                *shuffles,
+                *shuffle_k,
+                *seed,
+            )?),
+            FeatureKind::Anf { length } => Box::new(Anf { length: *length }),
+            FeatureKind::Pseknc {
+                k,
+                lambda,
+                weight,
+                kind,
+                property_table,
+            } => Box::new(Pseknc {
+                params: PseKncParams {
+                    k: *k,
+                    lambda: *lambda,
+                    weight: *weight,
+                    kind: kind.parse()?,
+                },
+                table: loaded.property_table(property_table)?,
+            }),
+            FeatureKind::Autocorrelation {
+                kind,
+                lag,
+                property_table,
+            } => Box::new(PropertyAutocorrelation {
+                kind: kind.parse()?,
+                lag: *lag,
+                table: loaded.property_table(property_table)?,
+            }),
+            FeatureKind::SpectralFingerprint {
+                bins,
+                mapping,
+                window,
+            } => Box::new(SpectralFingerprint {
+                mapping: mapping.parse()?,
+                window: window.parse()?,
+                bins: *bins,
+            }),
+            FeatureKind::Wavelet {
+                wavelet,
+                mapping,
+                levels,
+            } => Box::new(WaveletStats {
+                wavelet: wavelet.parse()?,
+                mapping: mapping.parse()?,
+                levels: *levels,
+            }),
+            FeatureKind::Zcurve { params } => Box::new(ZCurve {
+                params: params.to_string().parse()?,
+                orf: false,
+            }),
+            FeatureKind::OrfZcurve { params } => Box::new(ZCurve {
+                params: params.to_string().parse()?,
+                orf: true,
+            }),
+            FeatureKind::Fcgr { k } => Box::new(Fcgr {
+                k: kmer_table_k("fcgr", *k, MAX_KMER_TABLE_K)?,
+            }),
+            FeatureKind::Fractal {
+                signal,
+                higuchi_kmax,
+            } => Box::new(Fractal {
+                signal: signal.parse()?,
+                higuchi_kmax: *higuchi_kmax,
+            }),
+            FeatureKind::KmerGraph { k } => Box::new(KmerGraphStats {
+                k: kmer_table_k("kmer_graph", *k, MAX_KMER_TABLE_K)?,
+            }),
+            FeatureKind::Length => Box::new(Length),
+            FeatureKind::Aac => Box::new(Aac),
+            FeatureKind::Dpc => Box::new(Dpc),
+            FeatureKind::ReducedKmers { alphabet, k } => Box::new(ReducedKmers {
+                alphabet: alphabet.parse()?,
+                k: kmer_table_k("reduced_kmers", *k, MAX_REDUCED_K)?,
+            }),
+            FeatureKind::Ctd => Box::new(Ctd),
+        })
+    }
+}
+
+/// One feature in a config
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FeatureEntry {
+    #[serde(flatten)]
+    pub kind: FeatureKind,
+    /// Name to register the feature under, so the same feature can be listed
+    /// twice with different parameters
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Column names to use instead of the feature's own, one per column
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub columns: Option<Vec<String>>,
+}
+
+/// Remove and deserialize a key from a table, if it's there
+fn take<T: DeserializeOwned, E: serde::de::Error>(
+    table: &mut serde_json::Map<String, serde_json::Value>,
+    key: &str,
+) -> Result<Option<T>, E> {
+    table
+        .remove(key)
+        .map(serde_json::from_value)
+        .transpose()
+        .map_err(E::custom)
+}
+
+impl<'de> Deserialize<'de> for FeatureEntry {
+    // Serde can't flatten into an enum that denies unknown fields, so the
+    // shared keys come out first and the rest has to be a FeatureKind
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut table = serde_json::Map::deserialize(deserializer)?;
+        let name = take(&mut table, "name")?;
+        let columns = take(&mut table, "columns")?;
+        let kind = serde_json::from_value(table.into()).map_err(D::Error::custom)?;
+        Ok(FeatureEntry {
+            kind,
+            name,
+            columns,
+        })
+    }
+}
+
+/// A feature with the name and column names given in a config
+struct Configured {
+    name: String,
+    columns: Vec<Column>,
+    feature: Box<dyn Feature>,
+}
+
+impl Feature for Configured {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        self.columns.clone()
+    }
+
+    fn alphabet(&self) -> Alphabet {
+        self.feature.alphabet()
+    }
+
+    fn compute(&self, clean_seq: &str) -> Result<Vec<Value>> {
+        self.feature.compute(clean_seq)
+    }
+
+    fn compute_with_structure(
+        &self,
+        clean_seq: &str,
+        structure: Option<&str>,
+    ) -> Result<Vec<Value>> {
+        self.feature.compute_with_structure(clean_seq, structure)
+    }
+}
+
+/// A set of features to compute
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FeatureConfig {
+    /// Name of the feature set, recorded in the output
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Whether the input is protein sequences rather than nucleotides
+    #[serde(default)]
+    pub protein: bool,
+    pub features: Vec<FeatureEntry>,
+}
+
+impl FeatureConfig {
+    /// Read a config, in TOML, YAML or JSON by the file extension
+    pub fn from_path(path: &str) -> Result<Self> {
+        let text = fs::read_to_string(path).with_context(|| format!("Reading {}", path))?;
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|x| x.to_str())
+            .unwrap_or_default();
+        Self::parse(&text, extension).with_context(|| format!("Reading {}", path))
+    }
+
+    /// Parse a config in the format of a file extension, `toml`, `yaml`,
+    /// `yml` or `json`
+    fn parse(text: &str, extension: &str) -> Result<Self> {
+        let config = match extension {
+            "toml" => toml::from_str(text)?,
+            "yaml" | "yml" => serde_yaml::from_str(text)?,
+            "json" => serde_json::from_str(text)?,
+            _ => return Err(anyhow!("Unknown config format {}", extension)),
+        };
+        Ok(config)
+    }
+
+    /// The config equivalent to the command line's feature options
+    ///
+    /// Features are in the order of --features and then of the options
+    /// below, and features listed several times by one option are named
+    /// after their parameter, like `fourier_eiip`. Structure features take
+    /// their parameters from the other options.
+    pub fn from_args(args: &Args) -> Result<Self> {
+        let mut features = Vec::new();
+        let mut add = |kind: FeatureKind, name: Option<String>| {
+            features.push(FeatureEntry {
+                kind: kind.with_args(args),
+                name,
+                columns: None,
+            })
+        };
+        if args.protein {
+            add(FeatureKind::Length, None);
+            add(FeatureKind::Shannon { k_min: 1, k_max: 4 }, None);
+            add(
+                FeatureKind::Tsallis {
+                    k_min: 1,
+                    k_max: 4,
+                    q: tsallis_q(),
+                },
+                None,
+            );
+            add(FeatureKind::Aac, None);
+            if args.dpc {
+                add(FeatureKind::Dpc, None);
+            }
+            for alphabet in &args.reduced_alphabet {
+                let kind = FeatureKind::ReducedKmers {
+                    alphabet: alphabet.name().to_owned(),
+                    k: args.reduced_k,
+                };
+                add(kind, Some(format!("reduced_kmers_{}", alphabet.name())));
+            }
+            add(FeatureKind::Ctd, None);
+            return Ok(FeatureConfig {
+                name: None,
+                protein: true,
+                features,
+            });
+        }
+
+        for name in &args.features {
+            add(FeatureKind::from_name(name)?, None);
+        }
+        if args.orf_peptide {
+            add(FeatureKind::OrfPeptide, None);
+        }
+        if let Some(gap_max) = args.cksnap_gap {
+            add(FeatureKind::Cksnap { gap_max }, None);
+        }
+        for mapping in &args.fourier {
+            let kind = FeatureKind::Fourier {
+                mapping: mapping.name(),
+            };
+            add(kind, Some(format!("fourier_{}", mapping.name())));
+        }
+        if let Some(wavelet) = args.wavelet {
+            let kind = FeatureKind::Wavelet {
+                wavelet: wavelet.name().to_owned(),
+                mapping: args.wavelet_mapping.name(),
+                levels: args.wavelet_levels,
+            };
+            add(kind, None);
+        }
+        for signal in &args.fractal {
+            let kind = FeatureKind::Fractal {
+                signal: signal.name().to_owned(),
+                higuchi_kmax: args.higuchi_kmax,
+            };
+            add(kind, Some(format!("fractal_{}", signal.name())));
+        }
+        for &k in &args.graph_k {
+            add(
+                FeatureKind::KmerGraph { k },
+                Some(format!("kmer_graph_{}", k)),
+            );
+        }
+        for params in &args.zcurve {
+            let params = params.dimension();
+            let (kind, name) = if args.zcurve_orf {
+                (FeatureKind::OrfZcurve { params }, "orf_zcurve")
+            } else {
+                (FeatureKind::Zcurve { params }, "zcurve")
+            };
+            add(kind, Some(format!("{}_{}", name, params)));
+        }
+        for kind in &args.autocorrelation {
+            let name = format!("autocorrelation_{}", kind.name());
+            let kind = FeatureKind::Autocorrelation {
+                kind: kind.name().to_owned(),
+                lag: args.autocorrelation_lag,
+                property_table: None,
+            };
+            add(kind, Some(name));
+        }
+        if args.mfe {
+            add(FeatureKind::from_name("mfe")?, None);
+        }
+        if args.partition {
+            add(FeatureKind::from_name("partition")?, None);
+        }
+        if args.unpaired_track {
+            add(FeatureKind::from_name("unpaired_probability")?, None);
+        }
+        if args.structure_features {
+            add(FeatureKind::Structure, None);
+            add(FeatureKind::StructureTriplets, None);
+        }
+        if args.nussinov {
+            add(FeatureKind::from_name("nussinov")?, None);
+        }
+        for feature in &args.null_features {
+            let kind = FeatureKind::NullScore {
+                of: Box::new(feature.kind().with_args(args)),
+                tail: feature.tail().name().to_owned(),
+                shuffles: args.shuffles,
+                shuffle_k: args.shuffle_k,
+                seed: args.shuffle_seed,
+            };
+            add(kind, None);
+        }
+        if let Some(window) = args.period3_window {
+            let kind = FeatureKind::Period3Profile {
+                window,
+                step: args.period3_step,
+            };
+            add(kind, None);
+        }
+        if let Some(bins) = args.psd_bins {
+            let kind = FeatureKind::SpectralFingerprint {
+                bins,
+                mapping: args.psd_mapping.name(),
+                window: args.psd_window.name().to_owned(),
+            };
+            add(kind, None);
+        }
+        if let Some(k) = args.fcgr_k {
+            add(FeatureKind::Fcgr { k }, None);
+        }
+        if let Some(length) = args.anf_length {
+            add(FeatureKind::Anf { length }, None);
+        }
+        Ok(FeatureConfig {
+            name: None,
+            protein: false,
+            features,
+        })
+    }
+
+    /// Build the features, in config order
+    ///
+    /// Errors if a parameter is invalid, a feature doesn't apply to the
+    /// sequences, two features or columns have the same name, or a feature
+    /// is given the wrong number of column names.
+    pub fn registry(&self) -> Result<Registry> {
+        let sequences = if self.protein {
+            Alphabet::Protein
+        } else {
+            Alphabet::Nucleotide
+        };
+        let mut loaded = Loaded::default();
+        let mut column_names = HashSet::from(["id".to_owned()]);
+        let mut registry = Registry::new();
+        for entry in &self.features {
+            let feature = entry.kind.build(&mut loaded)?;
+            let name = entry.name.as_deref().unwrap_or(feature.name()).to_owned();
+            if registry.get(&name).is_some() {
+                return Err(anyhow!(
+                    "Feature {} is listed twice, give one a different name",
+                    name
+                ));
+            }
+            if !feature.alphabet().accepts(sequences) {
+                return Err(anyhow!(
+                    "Feature {} needs {} sequences",
+                    name,
+                    feature.alphabet().name()
+                ));
+            }
+            let mut columns = feature.columns();
+            if let Some(names) = &entry.columns {
+                if names.len() != columns.len() {
+                    return Err(anyhow!(
+                        "Feature {} has {} columns but {} names were given",
+                        name,
+                        columns.len(),
+                        names.len()
+                    ));
+                }
+                for (column, new_name) in columns.iter_mut().zip(names) {
+                    column.name = new_name.clone();
+                }
+            }
+            for column in &columns {
+                if !column_names.insert(column.name.clone()) {
+                    return Err(anyhow!(
+                        "Feature {} writes column {}, which is already written",
+                        name,
+                        column.name
+                    ));
+                }
+            }
+            registry.register(Configured {
+                name,
+                columns,
+                feature,
+            });
+        }
+        Ok(registry)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::{FeatureConfig, FeatureEntry, FeatureKind};
+    use crate::Args;
+    use clap::Parser;
+
+    fn entry(kind: FeatureKind) -> FeatureEntry {
+        FeatureEntry {
+            kind,
+            name: None,
+            columns: None,
+        }
+    }
+
+    fn registry_error(kind: FeatureKind) -> String {
+        let config = FeatureConfig {
+            name: None,
+            protein: false,
+            features: vec![entry(kind)],
+        };
+        config.registry().err().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let config = FeatureConfig {
+            name: Some("prefilter".to_owned()),
+            protein: false,
+            features: vec![
+                entry(FeatureKind::GcContent),
+                FeatureEntry {
+                    kind: FeatureKind::Tsallis {
+                        k_min: 2,
+                        k_max: 3,
+                        q: 3.0,
+                    },
+                    name: Some("tsallis_q3".to_owned()),
+                    columns: Some(vec!["t2".to_owned(), "t3".to_owned()]),
+                },
+                entry(FeatureKind::Mfe {
+                    energy_params: None,
+                    dangles: false,
+                }),
+                entry(FeatureKind::NullScore {
+                    of: Box::new(FeatureKind::Fickett),
+                    tail: "upper".to_owned(),
+                    shuffles: 10,
+                    shuffle_k: 1,
+                    seed: 7,
+                }),
+            ],
+        };
+        let toml = toml::to_string(&config).unwrap();
+        assert_eq!(FeatureConfig::parse(&toml, "toml").unwrap(), config);
+        let yaml = serde_yaml::to_string(&config).unwrap();
+        assert_eq!(FeatureConfig::parse(&yaml, "yaml").unwrap(), config);
+        let json = serde_json::to_string(&config).unwrap();
+        assert_eq!(FeatureConfig::parse(&json, "json").unwrap(), config);
+        assert_eq!(config.registry().unwrap().names()[1], "tsallis_q3");
+    }
+
+    #[test]
+    fn test_defaults() {
+        let toml = "[[features]]\nfeature = \"period3_profile\"\nwindow = 60\n";
+        let config = FeatureConfig::parse(toml, "toml").unwrap();
+        let kind = FeatureKind::Period3Profile {
+            window: 60,
+            step: 1,
+        };
+        assert_eq!(config.features, [entry(kind)]);
+        assert!(!config.protein);
+    }
+
+    #[test]
+    fn test_unknown_fields() {
+        let toml = "[[features]]\nfeature = \"shannon\"\nkmax = 6\n";
+        let error = FeatureConfig::parse(toml, "toml").err().unwrap();
+        assert!(
+            error.to_string().contains("unknown field `kmax`"),
+            "{}",
+            error
+        );
+
+        let yaml = "features:\n  - feature: gc_content\nfeatres: []\n";
+        let error = FeatureConfig::parse(yaml, "yaml").err().unwrap();
+        assert!(
+            error.to_string().contains("unknown field `featres`"),
+            "{}",
+            error
+        );
+
+        let json = r#"{"features": [{"feature": "gc_contents"}]}"#;
+        let error = FeatureConfig::parse(json, "json").err().unwrap();
+        assert!(
+            error.to_string().contains("unknown variant `gc_contents`"),
+            "{}",
+            error
+        );
+
+        assert!(FeatureConfig::parse("", "ini").is_err());
+    }
+
+    #[test]
+    fn test_invalid_parameters() {
+        assert_eq!(
+            registry_error(FeatureKind::Tsallis {
+                k_min: 1,
+                k_max: 4,
+                q: 1.0
+            }),
+            "Feature tsallis can't have q = 1, use shannon instead"
+        );
+        assert_eq!(
+            registry_error(FeatureKind::Shannon { k_min: 0, k_max: 4 }),
+            "Feature shannon needs 1 <= k_min <= k_max, not k_min 0 and k_max 4"
+        );
+        assert_eq!(
+            registry_error(FeatureKind::Tsallis {
+                k_min: 5,
+                k_max: 4,
+                q: 2.0
+            }),
+            "Feature tsallis needs 1 <= k_min <= k_max, not k_min 5 and k_max 4"
+        );
+        assert_eq!(
+            registry_error(FeatureKind::Fcgr { k: 13 }),
+            "Feature fcgr needs k from 1 to 12, not 13"
+        );
+        let reduced = FeatureKind::ReducedKmers {
+            alphabet: "triad7".to_owned(),
+            k: 25,
+        };
+        let config = FeatureConfig {
+            name: None,
+            protein: true,
+            features: vec![entry(reduced)],
+        };
+        assert_eq!(
+            config.registry().err().unwrap().to_string(),
+            "Feature reduced_kmers needs k from 1 to 5, not 25"
+        );
+        assert!(parse_args(&["--protein", "--reduced-k", "25"]).is_err());
+        assert_eq!(
+            registry_error(FeatureKind::Aac),
+            "Feature aac needs protein sequences"
+        );
+    }
+
+    #[test]
+    fn test_duplicate_columns() {
+        let config = FeatureConfig {
+            name: None,
+            protein: false,
+            features: vec![
+                entry(FeatureKind::GcContent),
+                FeatureEntry {
+                    kind: FeatureKind::Fickett,
+                    name: None,
+                    columns: Some(vec!["gc_cont".to_owned()]),
+                },
+            ],
+        };
+        assert_eq!(
+            config.registry().err().unwrap().to_string(),
+            "Feature fickett writes column gc_cont, which is already written"
+        );
+    }
+
+    fn parse_args(flags: &[&str]) -> Result<Args, clap::Error> {
+        let args = ["feat_extract", "in.fa", "out.parquet"];
+        Args::try_parse_from(args.iter().chain(flags))
+    }
+
+    #[test]
+    fn test_config_conflicts() {
+        assert!(parse_args(&["--config", "features.toml"]).is_ok());
+        assert!(parse_args(&["--config", "features.toml", "--mfe"]).is_err());
+        assert!(parse_args(&["--config", "features.toml", "--period3-step", "3"]).is_err());
+        assert!(parse_args(&["--config", "features.toml", "--features", "mfe"]).is_err());
+        assert!(parse_args(&["--config", "features.toml", "--structures", "s.tsv"]).is_ok());
+        // Feature options don't conflict with each other
+        assert!(parse_args(&["--mfe", "--partition", "--nussinov"]).is_ok());
+    }
+}
@@ -1,18 +1,83 @@
 // use anyhow::Ok;
-use anyhow::Result;
+mod config;
+
+use anyhow::{anyhow, Context, Result};
 use bio::io::fasta;
 use bio::io::fasta::Record;
-use clap::Parser;
-use core::f64::NAN;
+use clap::builder::RangedU64ValueParser;
+use clap::{ArgGroup, Parser, ValueEnum};
+use config::{FeatureConfig, FeatureKind};
+use polars::export::arrow::datatypes::PhysicalType as ArrowPhysicalType;
+use polars::export::arrow::io::parquet::write::{
+    transverse, CompressionOptions, Encoding, FileWriter, KeyValue, RowGroupIterator, Version,
+    WriteOptions,
+};
 use polars::prelude::*;
-use seq_feat::{stats, utils};
+use seq_feat::feature::{ColumnType, Period3Profile, Registry, Value};
+use seq_feat::protein::composition::ReducedAlphabet;
+use seq_feat::stats::autocorrelation::Autocorrelation;
+use seq_feat::stats::fractal::FractalSignal;
+use seq_feat::stats::mapping::Mapping;
+use seq_feat::stats::null::Tail;
+use seq_feat::stats::spectral::Window;
+use seq_feat::stats::wavelet::Wavelet;
+use seq_feat::stats::zcurve::ZCurveParams;
+use seq_feat::{stats, structure, utils};
+use std::collections::HashMap;
 use std::fs;
 use std::io;
+use std::io::BufRead;
+use std::path::Path;
 use std::sync::Mutex;
 use std::thread;
 use std::time;
 
-#[derive(Parser, Debug)]
+/// Largest k for features with a table of all 4^k kmers, which takes
+/// 128 MiB of memory at 12
+const MAX_KMER_TABLE_K: u64 = 12;
+
+/// Largest k for reduced alphabet kmers, which have a column each, 16807
+/// of them for the seven letter alphabet at 5
+const MAX_REDUCED_K: u64 = 5;
+
+/// Image formats for writing FCGR images
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum ImageFormat {
+    Pgm,
+    Png,
+}
+
+/// Features that can be scored against shuffled sequences
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum NullFeature {
+    Mfe,
+    Nussinov,
+    Fickett,
+    Shannon,
+}
+
+impl NullFeature {
+    /// The feature scored, whose first column is the one scored
+    fn kind(&self) -> FeatureKind {
+        match self {
+            NullFeature::Mfe => FeatureKind::from_name("mfe").unwrap(),
+            NullFeature::Nussinov => FeatureKind::from_name("nussinov").unwrap(),
+            NullFeature::Fickett => FeatureKind::Fickett,
+            NullFeature::Shannon => FeatureKind::Shannon { k_min: 3, k_max: 3 },
+        }
+    }
+
+    /// Whether low or high values of the feature are the unusual ones
+    fn tail(&self) -> Tail {
+        match self {
+            NullFeature::Mfe | NullFeature::Shannon => Tail::Lower,
+            NullFeature::Nussinov | NullFeature::Fickett => Tail::Upper,
+        }
+    }
+}
+
+#[derive(Parser, Debug, Clone)]
+#[command(group(ArgGroup::new("feature_flags").multiple(true)))]
 struct Args {
     /// Where to read the fasta file from
     input: String,
@@ -23,73 +88,458 @@ struct Args {
 
     /// Number of threads to use to process chunks, default 1
     num_threads: Option<usize>,
+
+    /// Registered features to compute with their default parameters, by
+    /// name: gc_content, fickett, period3_snr, period3_profile, shannon,
+    /// tsallis, codon_position, orf_codon_position, orf_peptide, fourier,
+    /// cksnap, mfe, partition, unpaired_probability, nussinov, structure,
+    /// structure_triplets, anf, pseknc, autocorrelation,
+    /// spectral_fingerprint, wavelet, zcurve, orf_zcurve, fcgr, fractal or
+    /// kmer_graph. The structure features use --energy-params, --no-dangles and
+    /// the nussinov options, and the property features --property-table
+    #[arg(
+        long,
+        conflicts_with = "protein",
+        value_delimiter = ',',
+        default_values = [
+            "gc_content",
+            "fickett",
+            "period3_snr",
+            "shannon",
+            "tsallis",
+            "codon_position",
+            "orf_codon_position",
+        ]
+    )]
+    features: Vec<String>,
+
+    /// TOML, YAML or JSON file listing the features to compute with their
+    /// parameters and column names, instead of --features and the other
+    /// feature options
+    #[arg(long, conflicts_with_all = ["features", "protein", "feature_flags"])]
+    config: Option<String>,
+
+    /// Write the ANF encoding as a list column, padded or truncated to this length
+    #[arg(long, group = "feature_flags")]
+    anf_length: Option<usize>,
+
+    /// Compute k-spaced nucleotide pair composition for gaps up to this
+    #[arg(long, group = "feature_flags")]
+    cksnap_gap: Option<usize>,
+
+    /// Dinucleotide property autocorrelation descriptors to compute, any of
+    /// dac, dcc, dacc, moreau_broto, moran or geary
+    #[arg(long, value_delimiter = ',', group = "feature_flags")]
+    autocorrelation: Vec<Autocorrelation>,
+
+    /// Maximum lag for the autocorrelation descriptors
+    #[arg(long, default_value_t = 3, group = "feature_flags")]
+    autocorrelation_lag: usize,
+
+    /// CSV of dinucleotide properties to use instead of the built-in RNA table
+    #[arg(long, group = "feature_flags")]
+    property_table: Option<String>,
+
+    /// Numeric mappings to compute Fourier spectrum statistics for, e.g.
+    /// binary, eiip, z_curve
+    #[arg(long, value_delimiter = ',', group = "feature_flags")]
+    fourier: Vec<Mapping>,
+
+    /// Write a period-3 coding profile as a list column, using windows of this size
+    #[arg(long, group = "feature_flags")]
+    period3_window: Option<usize>,
+
+    /// Step between period-3 profile windows
+    #[arg(long, default_value_t = Period3Profile::default().step, group = "feature_flags")]
+    period3_step: usize,
+
+    /// Write a spectral fingerprint list column with this many frequency bands
+    #[arg(long, group = "feature_flags")]
+    psd_bins: Option<usize>,
+
+    /// Numeric mapping for the spectral fingerprint
+    #[arg(long, default_value = "binary", group = "feature_flags")]
+    psd_mapping: Mapping,
+
+    /// Window function for the spectral fingerprint, one of rectangular, hann,
+    /// hamming or blackman
+    #[arg(long, default_value = "hann", group = "feature_flags")]
+    psd_window: Window,
+
+    /// Compute discrete wavelet transform features with this wavelet, one of
+    /// haar, db2 or db4
+    #[arg(long, group = "feature_flags")]
+    wavelet: Option<Wavelet>,
+
+    /// Numeric mapping for the wavelet features
+    #[arg(long, default_value = "eiip", group = "feature_flags")]
+    wavelet_mapping: Mapping,
+
+    /// Number of wavelet decomposition levels
+    #[arg(long, default_value_t = 4, group = "feature_flags")]
+    wavelet_levels: usize,
+
+    /// Z-curve parameter sets to compute, any of 9, 12, 36 or 48
+    #[arg(long, value_delimiter = ',', group = "feature_flags")]
+    zcurve: Vec<ZCurveParams>,
+
+    /// Compute the Z-curve parameters on the longest ORF instead of the whole
+    /// sequence
+    #[arg(long, group = "feature_flags")]
+    zcurve_orf: bool,
+
+    /// Translate the longest ORF and write physicochemical features of the
+    /// peptide, prefixed orf_pep_
+    #[arg(long, group = "feature_flags")]
+    orf_peptide: bool,
+
+    /// Write the frequency chaos game representation as a list column, at
+    /// resolution 2^k by 2^k for this k, from 1 to 12
+    #[arg(
+        long,
+        value_parser = RangedU64ValueParser::<usize>::new().range(1..=MAX_KMER_TABLE_K),
+        group = "feature_flags",
+    )]
+    fcgr_k: Option<usize>,
+
+    /// Also write an FCGR image for every sequence into this directory
+    #[arg(long, requires = "fcgr_k")]
+    fcgr_dir: Option<String>,
+
+    /// Format of the FCGR images
+    #[arg(long, value_enum, default_value_t = ImageFormat::Png)]
+    fcgr_format: ImageFormat,
+
+    /// Signals to compute fractal dimension and Hurst exponent features for,
+    /// any of dna_walk, real or integer
+    #[arg(long, value_delimiter = ',', group = "feature_flags")]
+    fractal: Vec<FractalSignal>,
+
+    /// Largest interval for the Higuchi fractal dimension
+    #[arg(long, default_value_t = 8, group = "feature_flags")]
+    higuchi_kmax: usize,
+
+    /// Kmer sizes to compute kmer graph topology features for, from 1 to 12
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_parser = RangedU64ValueParser::<usize>::new().range(1..=MAX_KMER_TABLE_K),
+        group = "feature_flags",
+    )]
+    graph_k: Vec<usize>,
+
+    /// Predict the minimum free energy structure, and write its energy
+    /// features and dot-bracket string
+    #[arg(long, group = "feature_flags")]
+    mfe: bool,
+
+    /// ViennaRNA 2.x parameter file to use instead of the embedded Turner
+    /// 2004 subset
+    #[arg(long, group = "feature_flags")]
+    energy_params: Option<String>,
+
+    /// Leave out the dangling end and terminal mismatch energies of exterior
+    /// and multiloop stems, as RNAfold -d0 rather than its default -d2
+    #[arg(long, group = "feature_flags")]
+    no_dangles: bool,
+
+    /// Write the fraction of bases paired in the structure with the most
+    /// base pairs (Nussinov), a fast stand-in for MFE folding
+    #[arg(long, group = "feature_flags")]
+    nussinov: bool,
+
+    /// Fewest unpaired bases in a hairpin loop for Nussinov folding
+    #[arg(long, default_value_t = 3, group = "feature_flags")]
+    nussinov_min_loop: usize,
+
+    /// Only allow Watson-Crick pairs in Nussinov folding, not G-U
+    #[arg(long, group = "feature_flags")]
+    nussinov_no_wobble: bool,
+
+    /// Longest base pair span for Nussinov folding, which keeps memory
+    /// linear in the sequence length
+    #[arg(long, group = "feature_flags")]
+    nussinov_max_span: Option<usize>,
+
+    /// Compute the partition function, and write ensemble free energy, MFE
+    /// structure frequency and ensemble diversity
+    #[arg(long, group = "feature_flags")]
+    partition: bool,
+
+    /// Also write the probability that each base is unpaired as a list
+    /// column
+    #[arg(long, requires = "partition", group = "feature_flags")]
+    unpaired_track: bool,
+
+    /// Features to score against shuffled sequences, writing a z-score and
+    /// empirical p-value for each, any of mfe, nussinov, fickett or shannon
+    #[arg(long, value_delimiter = ',', group = "feature_flags")]
+    null_features: Vec<NullFeature>,
+
+    /// Number of shuffled sequences for each null distribution
+    #[arg(long, default_value_t = 100, group = "feature_flags")]
+    shuffles: usize,
+
+    /// Length of kmer to keep the counts of when shuffling, 2 for the
+    /// dinucleotide shuffle
+    #[arg(long, default_value_t = 2, group = "feature_flags")]
+    shuffle_k: usize,
+
+    /// Seed for the shuffles, so the scores are reproducible
+    #[arg(long, default_value_t = 1, group = "feature_flags")]
+    shuffle_seed: u64,
+
+    /// Write features of known dot-bracket or WUSS structures, given after
+    /// each sequence in 3-line FASTA (optionally followed by the energy, as
+    /// RNAfold writes) or in --structures
+    #[arg(long, group = "feature_flags")]
+    structure_features: bool,
+
+    /// Tab separated file of sequence IDs and dot-bracket structures, used
+    /// instead of structures in the input
+    #[arg(long)]
+    structures: Option<String>,
+
+    /// Treat the input as protein sequences, writing amino acid composition,
+    /// CTD descriptors and entropies instead of the nucleotide features
+    #[arg(long)]
+    protein: bool,
+
+    /// Also write the 400 dipeptide frequencies of protein sequences
+    #[arg(long, requires = "protein", group = "feature_flags")]
+    dpc: bool,
+
+    /// Reduced amino acid alphabets to write kmer frequencies for, any of
+    /// triad7, dayhoff6 or hp2
+    #[arg(
+        long,
+        value_delimiter = ',',
+        requires = "protein",
+        group = "feature_flags"
+    )]
+    reduced_alphabet: Vec<ReducedAlphabet>,
+
+    /// Length of the reduced alphabet kmers, from 1 to 5
+    #[arg(
+        long,
+        default_value_t = 3,
+        value_parser = RangedU64ValueParser::<usize>::new().range(1..=MAX_REDUCED_K),
+        group = "feature_flags",
+    )]
+    reduced_k: usize,
 }
 
-fn chunk_get_features(records: Vec<Record>) -> Result<DataFrame> {
-    let len = records.len();
-    let mut ids = Vec::with_capacity(len);
-    ids.resize(len, "".to_owned());
-    let mut gc_cont = vec![NAN; len];
-    let mut fickett = vec![NAN; len];
-    let mut shannon_1 = vec![NAN; len];
-    let mut shannon_2 = vec![NAN; len];
-    let mut shannon_3 = vec![NAN; len];
-    let mut shannon_4 = vec![NAN; len];
-    let mut tsallis_1 = vec![NAN; len];
-    let mut tsallis_2 = vec![NAN; len];
-    let mut tsallis_3 = vec![NAN; len];
-    let mut tsallis_4 = vec![NAN; len];
+/// Read a tab separated file of IDs and dot-bracket structures
+fn read_structures(path: &str) -> Result<HashMap<String, String>> {
+    let mut structures = HashMap::new();
+    for line in io::BufReader::new(fs::File::open(path)?).lines() {
+        let line = line?;
+        if let Some((id, structure)) = line.split_once('\t') {
+            structures.insert(id.to_owned(), structure.trim().to_owned());
+        }
+    }
+    Ok(structures)
+}
 
-    let mut idx: usize = 0;
+/// Write one FCGR image, named after the sequence ID
+fn write_fcgr_image(
+    dir: &str,
+    id: &str,
+    grid: &[u64],
+    k: usize,
+    format: ImageFormat,
+) -> Result<()> {
+    // IDs can contain anything, so keep file names to a safe subset
+    let name: String = id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "-_.".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let extension = match format {
+        ImageFormat::Pgm => "pgm",
+        ImageFormat::Png => "png",
+    };
+    let path = Path::new(dir).join(format!("{}.{}", name, extension));
+    let mut writer = io::BufWriter::new(fs::File::create(path)?);
 
-    for record in records {
+    match format {
+        ImageFormat::Pgm => stats::cgr::write_pgm(&mut writer, grid, k)?,
+        ImageFormat::Png => {
+            let side = 1u32 << k;
+            let mut encoder = png::Encoder::new(writer, side, side);
+            encoder.set_color(png::ColorType::Grayscale);
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder
+                .write_header()?
+                .write_image_data(&stats::cgr::fcgr_pixels(grid))?;
+        }
+    }
+    Ok(())
+}
+
+/// Write a DataFrame as parquet, with extra key-value metadata in the footer
+///
+/// Polars' ParquetWriter has no way to add metadata, so this writes through
+/// arrow2 directly with the same settings.
+fn write_parquet<W: io::Write>(
+    writer: W,
+    df: &mut DataFrame,
+    metadata: Vec<(String, String)>,
+) -> Result<u64> {
+    df.rechunk();
+    let schema = df.schema().to_arrow();
+    let options = WriteOptions {
+        write_statistics: false,
+        compression: CompressionOptions::Zstd(None),
+        version: Version::V2,
+        data_pagesize_limit: None,
+    };
+    let encodings = schema
+        .fields
+        .iter()
+        .map(|field| {
+            transverse(&field.data_type, |data_type| {
+                match data_type.to_physical_type() {
+                    ArrowPhysicalType::Dictionary(_) => Encoding::RleDictionary,
+                    _ => Encoding::Plain,
+                }
+            })
+        })
+        .collect();
+    let chunks = df.iter_chunks().filter(|chunk| !chunk.is_empty()).map(Ok);
+    let row_groups = RowGroupIterator::try_new(chunks, &schema, options, encodings)?;
+
+    let mut file = FileWriter::try_new(writer, schema, options)?;
+    for group in row_groups {
+        file.write(group?)?;
+    }
+    let metadata = metadata
+        .into_iter()
+        .map(|(key, value)| KeyValue {
+            key,
+            value: Some(value),
+        })
+        .collect();
+    Ok(file.end(Some(metadata))?)
+}
+
+fn chunk_get_features(
+    records: Vec<Record>,
+    args: &Args,
+    registry: &Registry,
+    structures: &HashMap<String, String>,
+    protein: bool,
+) -> Result<DataFrame> {
+    let len = records.len();
+    let mut ids = vec!["".to_owned(); len];
+    let mut valid = vec![false; len];
+    let features: Vec<_> = registry.iter().collect();
+    let feature_widths: Vec<usize> = features.iter().map(|x| x.columns().len()).collect();
+    let feature_columns: Vec<_> = features.iter().flat_map(|x| x.columns()).collect();
+    let mut feature_values: Vec<Vec<Option<Value>>> = vec![vec![None; len]; feature_columns.len()];
+
+    for (idx, record) in records.iter().enumerate() {
         let id = record.id().to_owned();
-        let seq = std::str::from_utf8(record.seq())?;
-        let seq_clean = match utils::seq::clean(seq) {
-            Ok(clean_seq) => clean_seq,
-            Err(_cs) => {
-                idx += 1;
-                continue;
-            } // Skip processing this one -
+        let record_seq = std::str::from_utf8(record.seq())?;
+        let (seq_clean, record_structure) = if protein {
+            (utils::seq::clean_protein(record_seq), None)
+        } else {
+            // 3-line records have the structure joined on after the sequence
+            let split = structure::features::split_structure(record_seq)
+                .with_context(|| format!("Reading the structure of {}", id))?;
+            match split {
+                Some((seq, structure)) => (utils::seq::clean(seq), Some(structure)),
+                None => (utils::seq::clean(record_seq), None),
+            }
+        };
+        let seq_clean = match seq_clean {
+            // Chunks are padded with empty records, which have no features
+            Ok(clean_seq) if !clean_seq.is_empty() => clean_seq,
+            _ => continue,
+        };
+        let known_structure = match &args.structures {
+            Some(_) => structures.get(&id).map(|x| &x[..]),
+            None => record_structure,
         };
 
-        let gc = stats::counts::gc_content(&seq_clean);
-        let fickett_seq = stats::fickett::score(&seq_clean);
-        let se = stats::entropy::shannon(&seq_clean, 4);
-        let te = stats::entropy::tsallis(&seq_clean, 4);
-
+        // Features that don't apply to this sequence are left missing
+        let mut columns = feature_values.iter_mut();
+        for (feature, width) in features.iter().zip(&feature_widths) {
+            let feature_columns: Vec<_> = columns.by_ref().take(*width).collect();
+            if let Ok(values) = feature.compute_with_structure(&seq_clean, known_structure) {
+                for (column, value) in feature_columns.into_iter().zip(values) {
+                    column[idx] = Some(value);
+                }
+            }
+        }
+        if let (Some(dir), Some(k)) = (&args.fcgr_dir, args.fcgr_k) {
+            let grid = stats::cgr::fcgr(&seq_clean, k);
+            write_fcgr_image(dir, &id, &grid, k, args.fcgr_format)?;
+        }
         ids[idx] = id;
-        gc_cont[idx] = gc;
-        fickett[idx] = fickett_seq;
-        shannon_1[idx] = se[0];
-        shannon_2[idx] = se[1];
-        shannon_3[idx] = se[2];
-        shannon_4[idx] = se[3];
-        tsallis_1[idx] = te[0];
-        tsallis_2[idx] = te[1];
-        tsallis_3[idx] = te[2];
-        tsallis_4[idx] = te[3];
-        idx += 1;
+        valid[idx] = true;
     }
 
-    let prefiltered_features = df!("id" => ids,
-        "gc_cont"   =>  gc_cont  ,
-        "fickett"   =>  fickett  ,
-        "shannon_1" =>  shannon_1,
-        "shannon_2" =>  shannon_2,
-        "shannon_3" =>  shannon_3,
-        "shannon_4" =>  shannon_4,
-        "tsallis_1" =>  tsallis_1,
-        "tsallis_2" =>  tsallis_2,
-        "tsallis_3" =>  tsallis_3,
-        "tsallis_4" =>  tsallis_4,
-    )?;
-    let mask = prefiltered_features.column("gc_cont")?.is_not_nan()?;
+    // Building the frame in one go errors on duplicate column names, where
+    // adding columns one at a time would replace them
+    let mut series = vec![Series::new("id", ids)];
+    for (column, values) in feature_columns.iter().zip(feature_values) {
+        series.push(match column.kind {
+            ColumnType::Float => {
+                let values: Vec<f64> = values
+                    .into_iter()
+                    .map(|value| match value {
+                        Some(Value::Float(x)) => x,
+                        _ => f64::NAN,
+                    })
+                    .collect();
+                Series::new(&column.name, values)
+            }
+            ColumnType::FloatList => {
+                let values: Vec<Series> = values
+                    .into_iter()
+                    .map(|value| match value {
+                        Some(Value::FloatList(x)) => Series::new("", x),
+                        _ => Series::new("", Vec::<f64>::new()),
+                    })
+                    .collect();
+                Series::new(&column.name, values)
+            }
+            ColumnType::Text => {
+                let values: Vec<Option<String>> = values
+                    .into_iter()
+                    .map(|value| match value {
+                        Some(Value::Text(x)) => Some(x),
+                        _ => None,
+                    })
+                    .collect();
+                Series::new(&column.name, values)
+            }
+        });
+    }
+    let prefiltered_features = DataFrame::new(series)?;
+    let mask: BooleanChunked = valid.into_iter().collect();
     Ok(prefiltered_features.filter(&mask)?) // should filter sequences with invalid sequences
 }
 
 fn main() -> Result<()> {
     let cli = Args::parse();
+    let feature_config = match &cli.config {
+        Some(path) => FeatureConfig::from_path(path)?,
+        None => FeatureConfig::from_args(&cli)?,
+    };
+    // Build the features before any threads start, so a bad config fails
+    // straight away
+    let registry = Arc::new(feature_config.registry()?);
+    let structures = Arc::new(match &cli.structures {
+        Some(path) => read_structures(path)?,
+        None => HashMap::new(),
+    });
 
     let max_ids: usize = cli.num_ids.unwrap_or(usize::MAX);
     let num_threads = cli.num_threads.unwrap_or(1);
@@ -98,10 +548,14 @@ fn main() -> Result<()> {
     let input: Box<dyn io::BufRead> = if cli.input == "-" {
         Box::new(io::BufReader::new(io::stdin()))
     } else {
-        Box::new(io::BufReader::new(fs::File::open(cli.input).unwrap()))
+        Box::new(io::BufReader::new(fs::File::open(&cli.input).unwrap()))
     };
 
-    let output = ParquetWriter::new(fs::File::create(cli.output)?);
+    if let Some(dir) = &cli.fcgr_dir {
+        fs::create_dir_all(dir)?;
+    }
+
+    let output = fs::File::create(&cli.output)?;
     let mut reader = fasta::Reader::new(input).records();
 
     let feature_df = Arc::new(Mutex::new(DataFrame::default()));
@@ -129,8 +583,19 @@ fn main() -> Result<()> {
         }
         if thread_count < num_threads {
             let accum_feats = Arc::clone(&feature_df);
+            let thread_args = cli.clone();
+            let thread_registry = Arc::clone(&registry);
+            let thread_structures = Arc::clone(&structures);
+            let protein = feature_config.protein;
             let handle = thread::spawn(move || {
-                let feature_result = chunk_get_features(record_chunk).ok().unwrap();
+                let feature_result = chunk_get_features(
+                    record_chunk,
+                    &thread_args,
+                    &thread_registry,
+                    &thread_structures,
+                    protein,
+                )
+                .unwrap();
                 let mut features = accum_feats.lock().unwrap();
                 features.vstack_mut(&feature_result).unwrap();
             });
@@ -153,7 +618,28 @@ fn main() -> Result<()> {
         }
     }
 
-    feature_df.lock().unwrap().rechunk();
-    output.finish(&mut feature_df.lock().unwrap())?;
+    // Wait for the last chunks, so an error in any of them fails the run
+    for handle in handles {
+        handle
+            .join()
+            .map_err(|_| anyhow!("Feature extraction failed"))?;
+    }
+
+    let args: Vec<String> = std::env::args().collect();
+    let metadata = vec![
+        (
+            "feat_extract_version".to_owned(),
+            env!("CARGO_PKG_VERSION").to_owned(),
+        ),
+        (
+            "feat_extract_args".to_owned(),
+            serde_json::to_string(&args)?,
+        ),
+        (
+            "feat_extract_config".to_owned(),
+            serde_json::to_string(&feature_config)?,
+        ),
+    ];
+    write_parquet(output, &mut feature_df.lock().unwrap(), metadata)?;
     Ok(())
 }
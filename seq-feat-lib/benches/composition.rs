@@ -0,0 +1,48 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use seq_feat::stats::{composition::Composition, counts, fickett};
+
+/// A pseudo-random sequence, long enough that the counting dominates
+fn test_sequence(len: usize) -> String {
+    let mut state: u32 = 42;
+    (0..len)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            ['A', 'C', 'G', 'U'][(state >> 16) as usize % 4]
+        })
+        .collect()
+}
+
+/// The old approach, one `str::matches` scan per base and per phase
+fn rescan_counts(seq: &str) -> [[f64; 4]; 3] {
+    let mut phased = [[0.0; 4]; 3];
+    for (phase, counts) in phased.iter_mut().enumerate() {
+        for (base, count) in ['A', 'C', 'G', 'U'].iter().zip(counts.iter_mut()) {
+            *count = seq
+                .chars()
+                .skip(phase)
+                .step_by(3)
+                .map(|x| (x == *base) as i64 as f64)
+                .sum();
+        }
+    }
+    phased
+}
+
+fn bench_composition(c: &mut Criterion) {
+    let seq = test_sequence(10_000);
+
+    let mut group = c.benchmark_group("phased_counts");
+    group.bench_function("rescan", |b| b.iter(|| rescan_counts(black_box(&seq))));
+    group.bench_function("single_pass", |b| {
+        b.iter(|| Composition::new(black_box(&seq)))
+    });
+    group.finish();
+
+    c.bench_function("gc_content", |b| {
+        b.iter(|| counts::gc_content(black_box(&seq)))
+    });
+    c.bench_function("fickett", |b| b.iter(|| fickett::score(black_box(&seq))));
+}
+
+criterion_group!(benches, bench_composition);
+criterion_main!(benches);
@@ -1 +1,2 @@
 pub mod orf;
+pub mod peptide;
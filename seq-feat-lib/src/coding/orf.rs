@@ -1,5 +1,6 @@
 use crate::utils::*;
 use regex::Regex;
+use std::ops::Range;
 use wasm_bindgen::prelude::*;
 
 lazy_static! {
@@ -9,6 +10,9 @@ lazy_static! {
     pub static ref TGA_STOP: Regex = Regex::new(r"UGA").unwrap();
 }
 
+/// Stop codons, as they appear in a cleaned sequence
+pub const STOP_CODONS: [&[u8]; 3] = [b"UAA", b"UAG", b"UGA"];
+
 #[wasm_bindgen(js_name=orf_length)]
 pub fn length(seq: &str) -> Option<usize> {
     let clean_seq = seq::clean(seq).ok().unwrap();
@@ -39,6 +43,37 @@ pub fn length(seq: &str) -> Option<usize> {
     Some(end_idx.unwrap() - start_idx.unwrap())
 }
 
+/// Find the longest open reading frame in a cleaned sequence
+///
+/// Each of the three forward frames is scanned for an AUG followed by an in
+/// frame stop codon. The range returned covers the longest of these from the
+/// start of the AUG to the end of the stop codon, so it is always a whole
+/// number of codons. Reading frames that run off the end of the sequence
+/// without a stop are not counted.
+pub fn longest(clean_seq: &str) -> Option<Range<usize>> {
+    let bytes = clean_seq.as_bytes();
+    let mut longest_orf: Option<Range<usize>> = None;
+
+    for frame in 0..3 {
+        let mut start_idx: Option<usize> = None;
+        for (codon_idx, codon) in bytes[frame.min(bytes.len())..].chunks_exact(3).enumerate() {
+            let idx = frame + 3 * codon_idx;
+            if start_idx.is_none() && codon == b"AUG" {
+                start_idx = Some(idx);
+            } else if STOP_CODONS.contains(&codon) {
+                if let Some(start) = start_idx.take() {
+                    let end = idx + 3;
+                    if end - start > longest_orf.as_ref().map_or(0, |x| x.len()) {
+                        longest_orf = Some(start..end);
+                    }
+                }
+            }
+        }
+    }
+
+    longest_orf
+}
+
 #[cfg(test)]
 mod test {
     use crate::coding::orf;
@@ -62,4 +97,14 @@ mod test {
         assert_eq!(orf::length("AUGAGUGAUCAGCAGUUGGACUAUGCCUUAGACCUAAUGAGGCACCUACCUCCACAGCAAAUUGAGAAAAAGCUCAGCAACCUGAUUGACCUGAUCCCUCAUCUAUGUGAAGAUCUCUUGCCUUCUGUUAAUCAGAUAAUGAAAAUUGCCAGAGACAAGGAAGUGGGAAAGGAUUACCUUUUGUGUGACUGCAACAGAGAU")
         , Some(37));
     }
+
+    #[test]
+    fn test_longest_orf() {
+        assert_eq!(orf::longest("AAAAAAAA"), None);
+        // Start with no stop in frame
+        assert_eq!(orf::longest("AUGAAAAUAA"), None);
+        // Two ORFs in different frames, the second is longer
+        assert_eq!(orf::longest("AUGUAACAUGAAACCCUGAAA"), Some(7..19));
+        assert_eq!(orf::longest("GGAUGCCCUAGG"), Some(2..11));
+    }
 }
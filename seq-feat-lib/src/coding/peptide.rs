@@ -0,0 +1,468 @@
+//! Translation and physicochemical features of peptides
+//!
+//! These are the protein level features of coding potential tools like CPC2
+//! and RNAsamba, calculated on the peptide an ORF encodes. The scales and
+//! pK values follow Biopython's `ProtParam`, which CPC2 uses, so the values
+//! should be comparable: Kyte-Doolittle hydropathy for GRAVY, Guruprasad's
+//! dipeptide weights for the instability index, Ikai's aliphatic index and
+//! Lobry's aromaticity. The isoelectric point is found with the Bjellqvist
+//! pK values, as Biopython and ExPASy do, or with EMBOSS's.
+use anyhow::{anyhow, Result};
+use std::str::FromStr;
+use wasm_bindgen::prelude::*;
+
+/// Features reported for each peptide, in output order
+pub const PEPTIDE_FEATURE_NAMES: [&str; 9] = [
+    "length",
+    "pi",
+    "pi_emboss",
+    "molecular_weight",
+    "gravy",
+    "instability_index",
+    "aliphatic_index",
+    "aromaticity",
+    "charge_ph7",
+];
+
+/// The standard amino acids, in the order of the tables below
+pub const AMINO_ACIDS: [char; 20] = [
+    'A', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'K', 'L', 'M', 'N', 'P', 'Q', 'R', 'S', 'T', 'V', 'W',
+    'Y',
+];
+
+/// The standard genetic code, for codons in UCAG order
+const GENETIC_CODE: &[u8; 64] = b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG";
+
+/// Average mass of each free amino acid in daltons
+const MASSES: [f64; 20] = [
+    89.0932, 121.1582, 133.1027, 147.1293, 165.1891, 75.0666, 155.1546, 131.1729, 146.1876,
+    131.1729, 149.2113, 132.1179, 115.1305, 146.1445, 174.201, 105.0926, 119.1192, 117.1463,
+    204.2252, 181.1885,
+];
+
+/// Average mass of water, lost from each peptide bond
+const WATER: f64 = 18.01528;
+
+/// Kyte-Doolittle hydropathy of each amino acid
+const HYDROPATHY: [f64; 20] = [
+    1.8, 2.5, -3.5, -3.5, 2.8, -0.4, -3.2, 4.5, -3.9, 3.8, 1.9, -3.5, -1.6, -3.5, -4.5, -0.8, -0.7,
+    4.2, -0.9, -1.3,
+];
+
+/// Guruprasad et al. (1990) dipeptide instability weights, indexed by the
+/// first then the second amino acid
+const INSTABILITY: [[f64; 20]; 20] = [
+    // A
+    [
+        1.0, 44.94, -7.49, 1.0, 1.0, 1.0, -7.49, 1.0, 1.0, 1.0, 1.0, 1.0, 20.26, 1.0, 1.0, 1.0,
+        1.0, 1.0, 1.0, 1.0,
+    ],
+    // C
+    [
+        1.0, 1.0, 20.26, 1.0, 1.0, 1.0, 33.6, 1.0, 1.0, 20.26, 33.6, 1.0, 20.26, -6.54, 1.0, 1.0,
+        33.6, -6.54, 24.68, 1.0,
+    ],
+    // D
+    [
+        1.0, 1.0, 1.0, 1.0, -6.54, 1.0, 1.0, 1.0, -7.49, 1.0, 1.0, 1.0, 1.0, 1.0, -6.54, 20.26,
+        -14.03, 1.0, 1.0, 1.0,
+    ],
+    // E
+    [
+        1.0, 44.94, 20.26, 33.6, 1.0, 1.0, -6.54, 20.26, 1.0, 1.0, 1.0, 1.0, 20.26, 20.26, 1.0,
+        20.26, 1.0, 1.0, -14.03, 1.0,
+    ],
+    // F
+    [
+        1.0, 1.0, 13.34, 1.0, 1.0, 1.0, 1.0, 1.0, -14.03, 1.0, 1.0, 1.0, 20.26, 1.0, 1.0, 1.0, 1.0,
+        1.0, 1.0, 33.601,
+    ],
+    // G
+    [
+        -7.49, 1.0, 1.0, -6.54, 1.0, 13.34, 1.0, -7.49, -7.49, 1.0, 1.0, -7.49, 1.0, 1.0, 1.0, 1.0,
+        -7.49, 1.0, 13.34, -7.49,
+    ],
+    // H
+    [
+        1.0, 1.0, 1.0, 1.0, -9.37, -9.37, 1.0, 44.94, 24.68, 1.0, 1.0, 24.68, -1.88, 1.0, 1.0, 1.0,
+        -6.54, 1.0, -1.88, 44.94,
+    ],
+    // I
+    [
+        1.0, 1.0, 1.0, 44.94, 1.0, 1.0, 13.34, 1.0, -7.49, 20.26, 1.0, 1.0, -1.88, 1.0, 1.0, 1.0,
+        1.0, -7.49, 1.0, 1.0,
+    ],
+    // K
+    [
+        1.0, 1.0, 1.0, 1.0, 1.0, -7.49, 1.0, -7.49, 1.0, -7.49, 33.6, 1.0, -6.54, 24.64, 33.6, 1.0,
+        1.0, -7.49, 1.0, 1.0,
+    ],
+    // L
+    [
+        1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, -7.49, 1.0, 1.0, 1.0, 20.26, 33.6, 20.26, 1.0, 1.0,
+        1.0, 24.68, 1.0,
+    ],
+    // M
+    [
+        13.34, 1.0, 1.0, 1.0, 1.0, 1.0, 58.28, 1.0, 1.0, 1.0, -1.88, 1.0, 44.94, -6.54, -6.54,
+        44.94, -1.88, 1.0, 1.0, 24.68,
+    ],
+    // N
+    [
+        1.0, -1.88, 1.0, 1.0, -14.03, -14.03, 1.0, 44.94, 24.68, 1.0, 1.0, 1.0, -1.88, -6.54, 1.0,
+        1.0, -7.49, 1.0, -9.37, 1.0,
+    ],
+    // P
+    [
+        20.26, -6.54, -6.54, 18.38, 20.26, 1.0, 1.0, 1.0, 1.0, 1.0, -6.54, 1.0, 20.26, 20.26,
+        -6.54, 20.26, 1.0, 20.26, -1.88, 1.0,
+    ],
+    // Q
+    [
+        1.0, -6.54, 20.26, 20.26, -6.54, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 20.26, 20.26, 1.0,
+        44.94, 1.0, -6.54, 1.0, -6.54,
+    ],
+    // R
+    [
+        1.0, 1.0, 1.0, 1.0, 1.0, -7.49, 20.26, 1.0, 1.0, 1.0, 1.0, 13.34, 20.26, 20.26, 58.28,
+        44.94, 1.0, 1.0, 58.28, -6.54,
+    ],
+    // S
+    [
+        1.0, 33.6, 1.0, 20.26, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 44.94, 20.26, 20.26, 20.26,
+        1.0, 1.0, 1.0, 1.0,
+    ],
+    // T
+    [
+        1.0, 1.0, 1.0, 20.26, 13.34, -7.49, 1.0, 1.0, 1.0, 1.0, 1.0, -14.03, 1.0, -6.54, 1.0, 1.0,
+        1.0, 1.0, -14.03, 1.0,
+    ],
+    // V
+    [
+        1.0, 1.0, -14.03, 1.0, 1.0, -7.49, 1.0, 1.0, -1.88, 1.0, 1.0, 1.0, 20.26, 1.0, 1.0, 1.0,
+        -7.49, 1.0, 1.0, -6.54,
+    ],
+    // W
+    [
+        -14.03, 1.0, 1.0, 1.0, 1.0, -9.37, 24.68, 1.0, 1.0, 13.34, 24.68, 13.34, 1.0, 1.0, 1.0,
+        1.0, -14.03, -7.49, 1.0, 1.0,
+    ],
+    // Y
+    [
+        24.68, 1.0, 24.68, -6.54, 1.0, -7.49, 13.34, 1.0, 1.0, 1.0, 44.94, 1.0, 13.34, 1.0, -15.91,
+        1.0, -7.49, 1.0, -9.37, 13.34,
+    ],
+];
+
+/// Index of an amino acid in `AMINO_ACIDS`
+pub fn residue_index(residue: u8) -> Option<usize> {
+    AMINO_ACIDS.iter().position(|x| *x as u8 == residue)
+}
+
+/// Index of a nucleotide in the UCAG order of the genetic code
+fn code_index(base: u8) -> Option<usize> {
+    match base {
+        b'U' | b'T' => Some(0),
+        b'C' => Some(1),
+        b'A' => Some(2),
+        b'G' => Some(3),
+        _ => None,
+    }
+}
+
+/// Translate a sequence with the standard genetic code
+///
+/// Every whole codon is translated from the first base, with `*` for stop
+/// codons and `X` for codons with anything other than a nucleotide. A
+/// partial codon at the end is dropped.
+#[wasm_bindgen(js_name=translate)]
+pub fn translate(clean_seq: &str) -> String {
+    clean_seq
+        .as_bytes()
+        .chunks_exact(3)
+        .map(|codon| {
+            match (
+                code_index(codon[0]),
+                code_index(codon[1]),
+                code_index(codon[2]),
+            ) {
+                (Some(first), Some(second), Some(third)) => {
+                    GENETIC_CODE[16 * first + 4 * second + third] as char
+                }
+                _ => 'X',
+            }
+        })
+        .collect()
+}
+
+/// Sets of pK values for calculating charges
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PkSet {
+    /// Bjellqvist et al. (1993), with terminal pKs depending on the terminal
+    /// residues, as used by Biopython and ExPASy
+    Bjellqvist,
+    /// The pK values in EMBOSS `iep`
+    Emboss,
+}
+
+impl FromStr for PkSet {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "bjellqvist" => Ok(PkSet::Bjellqvist),
+            "emboss" => Ok(PkSet::Emboss),
+            _ => Err(anyhow!("Unknown pK set {}", s)),
+        }
+    }
+}
+
+impl PkSet {
+    /// pK of the N terminus, given the first residue
+    fn n_terminal(&self, first: u8) -> f64 {
+        match self {
+            PkSet::Bjellqvist => match first {
+                b'A' => 7.59,
+                b'M' => 7.0,
+                b'S' => 6.93,
+                b'P' => 8.36,
+                b'T' => 6.82,
+                b'V' => 7.44,
+                b'E' => 7.7,
+                _ => 7.5,
+            },
+            PkSet::Emboss => 8.6,
+        }
+    }
+
+    /// pK of the C terminus, given the last residue
+    fn c_terminal(&self, last: u8) -> f64 {
+        match self {
+            PkSet::Bjellqvist => match last {
+                b'D' => 4.55,
+                b'E' => 4.75,
+                _ => 3.55,
+            },
+            PkSet::Emboss => 3.6,
+        }
+    }
+
+    /// pKs of the positively charged side chains K, R and H
+    fn positive(&self) -> [(u8, f64); 3] {
+        match self {
+            PkSet::Bjellqvist => [(b'K', 10.0), (b'R', 12.0), (b'H', 5.98)],
+            PkSet::Emboss => [(b'K', 10.8), (b'R', 12.5), (b'H', 6.5)],
+        }
+    }
+
+    /// pKs of the negatively charged side chains D, E, C and Y
+    fn negative(&self) -> [(u8, f64); 4] {
+        match self {
+            PkSet::Bjellqvist => [(b'D', 4.05), (b'E', 4.45), (b'C', 9.0), (b'Y', 10.0)],
+            PkSet::Emboss => [(b'D', 3.9), (b'E', 4.1), (b'C', 8.5), (b'Y', 10.1)],
+        }
+    }
+}
+
+/// Count each of the standard amino acids in a peptide
+fn residue_counts(peptide: &str) -> [usize; 20] {
+    let mut counts = [0; 20];
+    for residue in peptide.bytes().filter_map(residue_index) {
+        counts[residue] += 1;
+    }
+    counts
+}
+
+/// Net charge of a peptide at a given pH
+///
+/// NaN for an empty peptide.
+pub fn charge(peptide: &str, ph: f64, pk: PkSet) -> f64 {
+    let bytes = peptide.as_bytes();
+    let (first, last) = match (bytes.first(), bytes.last()) {
+        (Some(first), Some(last)) => (*first, *last),
+        _ => return f64::NAN,
+    };
+    let count = |residue: u8| bytes.iter().filter(|x| **x == residue).count() as f64;
+    let positive = |pk: f64| 1.0 / (10f64.powf(ph - pk) + 1.0);
+    let negative = |pk: f64| 1.0 / (10f64.powf(pk - ph) + 1.0);
+
+    let mut charge = positive(pk.n_terminal(first)) - negative(pk.c_terminal(last));
+    for (residue, value) in pk.positive() {
+        charge += count(residue) * positive(value);
+    }
+    for (residue, value) in pk.negative() {
+        charge -= count(residue) * negative(value);
+    }
+    charge
+}
+
+/// The pH at which a peptide has no net charge
+///
+/// Found by bisection, as charge only falls with pH. NaN for an empty
+/// peptide.
+pub fn isoelectric_point(peptide: &str, pk: PkSet) -> f64 {
+    if peptide.is_empty() {
+        return f64::NAN;
+    }
+    let (mut low, mut high) = (0.0, 14.0);
+    while high - low > 1e-6 {
+        let mid = (low + high) / 2.0;
+        if charge(peptide, mid, pk) > 0.0 {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    (low + high) / 2.0
+}
+
+/// Average molecular weight of a peptide in daltons
+pub fn molecular_weight(peptide: &str) -> f64 {
+    let counts = residue_counts(peptide);
+    let residues: usize = counts.iter().sum();
+    let mass: f64 = counts.iter().zip(MASSES).map(|(x, y)| *x as f64 * y).sum();
+    mass - residues.saturating_sub(1) as f64 * WATER
+}
+
+/// Grand average of hydropathy, the mean Kyte-Doolittle score
+pub fn gravy(peptide: &str) -> f64 {
+    let counts = residue_counts(peptide);
+    let residues: usize = counts.iter().sum();
+    let total: f64 = counts
+        .iter()
+        .zip(HYDROPATHY)
+        .map(|(x, y)| *x as f64 * y)
+        .sum();
+    total / residues as f64
+}
+
+/// Instability index, where over 40 suggests the protein is unstable
+pub fn instability_index(peptide: &str) -> f64 {
+    let residues: Vec<usize> = peptide.bytes().filter_map(residue_index).collect();
+    let total: f64 = residues
+        .windows(2)
+        .map(|pair| INSTABILITY[pair[0]][pair[1]])
+        .sum();
+    10.0 * total / residues.len() as f64
+}
+
+/// Aliphatic index, the relative volume of aliphatic side chains
+pub fn aliphatic_index(peptide: &str) -> f64 {
+    let counts = residue_counts(peptide);
+    let residues: usize = counts.iter().sum();
+    let mole_percent =
+        |residue: u8| 100.0 * counts[residue_index(residue).unwrap()] as f64 / residues as f64;
+    mole_percent(b'A') + 2.9 * mole_percent(b'V') + 3.9 * (mole_percent(b'I') + mole_percent(b'L'))
+}
+
+/// Fraction of residues that are aromatic, F, W or Y
+pub fn aromaticity(peptide: &str) -> f64 {
+    let counts = residue_counts(peptide);
+    let residues: usize = counts.iter().sum();
+    let aromatic: usize = [b'F', b'W', b'Y']
+        .iter()
+        .map(|x| counts[residue_index(*x).unwrap()])
+        .sum();
+    aromatic as f64 / residues as f64
+}
+
+/// Calculate the features in `PEPTIDE_FEATURE_NAMES` for a peptide
+///
+/// A trailing stop `*` is ignored, and everything but length is NaN for an
+/// empty peptide.
+#[wasm_bindgen(js_name=peptide_features)]
+pub fn peptide_features(peptide: &str) -> Vec<f64> {
+    let peptide = peptide.strip_suffix('*').unwrap_or(peptide);
+    vec![
+        peptide.len() as f64,
+        isoelectric_point(peptide, PkSet::Bjellqvist),
+        isoelectric_point(peptide, PkSet::Emboss),
+        if peptide.is_empty() {
+            f64::NAN
+        } else {
+            molecular_weight(peptide)
+        },
+        gravy(peptide),
+        instability_index(peptide),
+        aliphatic_index(peptide),
+        aromaticity(peptide),
+        charge(peptide, 7.0, PkSet::Bjellqvist),
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use crate::coding::peptide::{self, PkSet};
+
+    fn close(x: f64, y: f64, tolerance: f64) -> bool {
+        (x - y).abs() < tolerance
+    }
+
+    #[test]
+    fn test_translate() {
+        assert_eq!(peptide::translate("AUGGCCUUUUAAGG"), "MAF*");
+        assert_eq!(peptide::translate("AUGNNNUGG"), "MXW");
+        assert_eq!(peptide::translate(""), "");
+        // Every codon translates
+        let all: String = ["U", "C", "A", "G"]
+            .iter()
+            .flat_map(|x| ["U", "C", "A", "G"].map(move |y| format!("{}{}", x, y)))
+            .flat_map(|xy| ["U", "C", "A", "G"].map(move |z| format!("{}{}", xy, z)))
+            .collect();
+        assert_eq!(peptide::translate(&all).matches('*').count(), 3);
+    }
+
+    #[test]
+    fn test_composition_scores() {
+        // Two glycines joined by one peptide bond
+        assert!(close(
+            peptide::molecular_weight("GG"),
+            2.0 * 75.0666 - 18.01528,
+            1e-9
+        ));
+        assert!(close(peptide::gravy("AIR"), (1.8 + 4.5 - 4.5) / 3.0, 1e-12));
+        assert!(close(peptide::aromaticity("FWYA"), 0.75, 1e-12));
+        // 25% each of A, V, I and L
+        assert!(close(
+            peptide::aliphatic_index("AVIL"),
+            25.0 + 2.9 * 25.0 + 3.9 * 50.0,
+            1e-9
+        ));
+        // AC then CH
+        assert!(close(
+            peptide::instability_index("ACH"),
+            10.0 * (44.94 + 33.6) / 3.0,
+            1e-9
+        ));
+    }
+
+    #[test]
+    fn test_charge() {
+        // Lysine rich peptides are basic and aspartate rich ones acidic
+        assert!(peptide::charge("KKKK", 7.0, PkSet::Bjellqvist) > 3.0);
+        assert!(peptide::charge("DDDD", 7.0, PkSet::Bjellqvist) < -3.0);
+        assert!(peptide::isoelectric_point("KKKK", PkSet::Emboss) > 10.0);
+        assert!(peptide::isoelectric_point("DDDD", PkSet::Emboss) < 4.0);
+
+        // Glycine alone is balanced halfway between its terminal pKs
+        let pi = peptide::isoelectric_point("G", PkSet::Emboss);
+        assert!(close(pi, (8.6 + 3.6) / 2.0, 1e-4));
+        assert!(close(peptide::charge("G", pi, PkSet::Emboss), 0.0, 1e-5));
+        let pi = peptide::isoelectric_point("G", PkSet::Bjellqvist);
+        assert!(close(pi, (7.5 + 3.55) / 2.0, 1e-4));
+    }
+
+    #[test]
+    fn test_features() {
+        let features = peptide::peptide_features("MAFKKDE*");
+        assert_eq!(features.len(), peptide::PEPTIDE_FEATURE_NAMES.len());
+        assert_eq!(features[0], 7.0);
+        assert!(features.iter().all(|x| x.is_finite()));
+
+        let features = peptide::peptide_features("");
+        assert_eq!(features[0], 0.0);
+        assert!(features[1..].iter().all(|x| x.is_nan()));
+        assert!("emboss".parse::<PkSet>().is_ok());
+        assert!("other".parse::<PkSet>().is_err());
+    }
+}
@@ -0,0 +1,1293 @@
+//! A common interface to the feature extractors
+//!
+//! Each extractor is a free function with whatever signature suits it, so
+//! anything computing several has to know about each one. The `Feature`
+//! trait wraps them up with their output columns, and a `Registry` holds a
+//! set of them by name, so callers can enumerate and compute features
+//! without knowing what they are.
+//!
+//! Downstream crates can implement `Feature` for their own extractors and
+//! register them alongside the built-in ones:
+//!
+//! ```
+//! use anyhow::Result;
+//! use seq_feat::feature::{Column, Feature, Registry, Value};
+//!
+//! struct Purines;
+//!
+//! impl Feature for Purines {
+//!     fn name(&self) -> &str {
+//!         "purines"
+//!     }
+//!
+//!     fn columns(&self) -> Vec<Column> {
+//!         vec![Column::float("purine_fraction")]
+//!     }
+//!
+//!     fn compute(&self, clean_seq: &str) -> Result<Vec<Value>> {
+//!         let purines = clean_seq.bytes().filter(|x| b"AG".contains(x)).count();
+//!         Ok(vec![Value::Float(purines as f64 / clean_seq.len() as f64)])
+//!     }
+//! }
+//!
+//! let mut registry = Registry::builtin();
+//! registry.register(Purines);
+//! let values = registry.get("purines").unwrap().compute("GAUA").unwrap();
+//! assert_eq!(values, [Value::Float(0.75)]);
+//! ```
+use crate::coding::peptide::PEPTIDE_FEATURE_NAMES;
+use crate::protein::composition::ReducedAlphabet;
+use crate::stats::autocorrelation::Autocorrelation;
+use crate::stats::counts::CODON_POSITION_NAMES;
+use crate::stats::fractal::FractalSignal;
+use crate::stats::mapping::{Mapping, NumericMapping};
+use crate::stats::null::Tail;
+use crate::stats::pseknc::{PropertyTable, PseKncParams};
+use crate::stats::spectral::Window;
+use crate::stats::wavelet::Wavelet;
+use crate::stats::zcurve::ZCurveParams;
+use crate::structure::features::STRUCTURE_FEATURE_NAMES;
+use crate::structure::mfe::MFE_FEATURE_NAMES;
+use crate::structure::nussinov::NussinovParams;
+use crate::structure::params::EnergyParams;
+use crate::structure::partition::ENSEMBLE_FEATURE_NAMES;
+use crate::{coding, protein, stats, structure};
+use anyhow::{anyhow, Result};
+use std::sync::Arc;
+use wasm_bindgen::prelude::*;
+
+lazy_static! {
+    static ref BUILTIN: Registry = Registry::builtin();
+}
+
+/// The type of values in an output column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    /// One number per sequence
+    Float,
+    /// A list of numbers per sequence, like a profile along it
+    FloatList,
+    /// A string per sequence, like a dot-bracket structure
+    Text,
+}
+
+/// An output column of a feature
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Column {
+    pub name: String,
+    pub kind: ColumnType,
+}
+
+impl Column {
+    pub fn float(name: &str) -> Self {
+        Column {
+            name: name.to_owned(),
+            kind: ColumnType::Float,
+        }
+    }
+
+    pub fn float_list(name: &str) -> Self {
+        Column {
+            name: name.to_owned(),
+            kind: ColumnType::FloatList,
+        }
+    }
+
+    pub fn text(name: &str) -> Self {
+        Column {
+            name: name.to_owned(),
+            kind: ColumnType::Text,
+        }
+    }
+}
+
+/// Float columns named after each of a list of names
+fn floats<S: AsRef<str>>(names: &[S]) -> Vec<Column> {
+    names.iter().map(|x| Column::float(x.as_ref())).collect()
+}
+
+/// A value in one column for one sequence
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Float(f64),
+    FloatList(Vec<f64>),
+    Text(String),
+}
+
+impl Value {
+    /// The numbers in the value, one for a float, any number for a list and
+    /// none for text
+    pub fn to_vec(&self) -> Vec<f64> {
+        match self {
+            Value::Float(x) => vec![*x],
+            Value::FloatList(x) => x.clone(),
+            Value::Text(_) => Vec::new(),
+        }
+    }
+}
+
+/// Values of an extractor's output as float values
+fn float_values(values: Vec<f64>) -> Vec<Value> {
+    values.into_iter().map(Value::Float).collect()
+}
+
+/// The kind of sequence a feature is computed on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alphabet {
+    /// Nucleotide sequences, cleaned by `utils::seq::clean`
+    Nucleotide,
+    /// Protein sequences, cleaned by `utils::seq::clean_protein`
+    Protein,
+    /// Either
+    Any,
+}
+
+impl Alphabet {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Alphabet::Nucleotide => "nucleotide",
+            Alphabet::Protein => "protein",
+            Alphabet::Any => "any",
+        }
+    }
+
+    /// Whether a feature on this alphabet can be computed on `sequences`
+    pub fn accepts(&self, sequences: Alphabet) -> bool {
+        *self == Alphabet::Any || *self == sequences
+    }
+}
+
+/// Something computed from a sequence into one or more columns
+pub trait Feature: Send + Sync {
+    /// Name used to select the feature, like `shannon`
+    fn name(&self) -> &str;
+
+    /// The output columns, in the order `compute` returns their values
+    fn columns(&self) -> Vec<Column>;
+
+    /// The kind of sequence the feature is computed on, nucleotides unless
+    /// overridden
+    fn alphabet(&self) -> Alphabet {
+        Alphabet::Nucleotide
+    }
+
+    /// Compute the feature for a sequence cleaned for its `alphabet`
+    ///
+    /// Returns one value per column. Errors if the feature doesn't apply to
+    /// the sequence, for example ORF features on a sequence without an ORF,
+    /// in which case callers should leave the columns missing.
+    fn compute(&self, clean_seq: &str) -> Result<Vec<Value>>;
+
+    /// Compute the feature for a sequence with a known dot-bracket or WUSS
+    /// structure, if it has one
+    ///
+    /// Features of the given structure override this, and the rest ignore
+    /// the structure and `compute`.
+    fn compute_with_structure(
+        &self,
+        clean_seq: &str,
+        _structure: Option<&str>,
+    ) -> Result<Vec<Value>> {
+        self.compute(clean_seq)
+    }
+}
+
+/// GC content, in column `gc_cont`
+pub struct GcContent;
+
+impl Feature for GcContent {
+    fn name(&self) -> &str {
+        "gc_content"
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        vec![Column::float("gc_cont")]
+    }
+
+    fn compute(&self, clean_seq: &str) -> Result<Vec<Value>> {
+        Ok(vec![Value::Float(stats::counts::gc_content(clean_seq))])
+    }
+}
+
+/// Fickett TESTCODE score
+pub struct Fickett;
+
+impl Feature for Fickett {
+    fn name(&self) -> &str {
+        "fickett"
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        vec![Column::float("fickett")]
+    }
+
+    fn compute(&self, clean_seq: &str) -> Result<Vec<Value>> {
+        Ok(vec![Value::Float(stats::fickett::score(clean_seq))])
+    }
+}
+
+/// Signal to noise ratio of the period-3 Fourier peak
+pub struct Period3Snr;
+
+impl Feature for Period3Snr {
+    fn name(&self) -> &str {
+        "period3_snr"
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        vec![Column::float("period3_snr")]
+    }
+
+    fn compute(&self, clean_seq: &str) -> Result<Vec<Value>> {
+        Ok(vec![Value::Float(stats::fourier::period3_snr(clean_seq))])
+    }
+}
+
+/// Shannon entropy of kmers from `kmer_min` to `kmer_max`, in `shannon_1`
+/// and on
+pub struct Shannon {
+    pub kmer_min: u64,
+    pub kmer_max: u64,
+}
+
+impl Feature for Shannon {
+    fn name(&self) -> &str {
+        "shannon"
+    }
+
+    fn alphabet(&self) -> Alphabet {
+        Alphabet::Any
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        (self.kmer_min..=self.kmer_max)
+            .map(|k| Column::float(&format!("shannon_{}", k)))
+            .collect()
+    }
+
+    fn compute(&self, clean_seq: &str) -> Result<Vec<Value>> {
+        let entropies = stats::entropy::shannon(clean_seq, self.kmer_max);
+        let skip = self.kmer_min.saturating_sub(1) as usize;
+        Ok(entropies.into_iter().skip(skip).map(Value::Float).collect())
+    }
+}
+
+/// Tsallis entropy with index `q` of kmers from `kmer_min` to `kmer_max`, in
+/// `tsallis_1` and on
+pub struct Tsallis {
+    pub kmer_min: u64,
+    pub kmer_max: u64,
+    pub q: f64,
+}
+
+impl Feature for Tsallis {
+    fn name(&self) -> &str {
+        "tsallis"
+    }
+
+    fn alphabet(&self) -> Alphabet {
+        Alphabet::Any
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        (self.kmer_min..=self.kmer_max)
+            .map(|k| Column::float(&format!("tsallis_{}", k)))
+            .collect()
+    }
+
+    fn compute(&self, clean_seq: &str) -> Result<Vec<Value>> {
+        let entropies = stats::entropy::tsallis_q(clean_seq, self.kmer_max, self.q);
+        let skip = self.kmer_min.saturating_sub(1) as usize;
+        Ok(entropies.into_iter().skip(skip).map(Value::Float).collect())
+    }
+}
+
+/// Period-3 signal to noise ratio along the sequence, as a list
+pub struct Period3Profile {
+    pub window: usize,
+    pub step: usize,
+}
+
+impl Default for Period3Profile {
+    fn default() -> Self {
+        Period3Profile {
+            window: 120,
+            step: 1,
+        }
+    }
+}
+
+impl Feature for Period3Profile {
+    fn name(&self) -> &str {
+        "period3_profile"
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        vec![Column::float_list("period3_profile")]
+    }
+
+    fn compute(&self, clean_seq: &str) -> Result<Vec<Value>> {
+        let profile = stats::fourier::period3_profile(clean_seq, self.window, self.step);
+        Ok(vec![Value::FloatList(profile)])
+    }
+}
+
+/// Statistics of the Fourier power spectrum under a numeric mapping
+pub struct Fourier {
+    pub mapping: Mapping,
+}
+
+impl Feature for Fourier {
+    fn name(&self) -> &str {
+        "fourier"
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        floats(&stats::fourier::spectrum_stat_names(&self.mapping))
+    }
+
+    fn compute(&self, clean_seq: &str) -> Result<Vec<Value>> {
+        let spectrum_stats = stats::fourier::spectrum_stats(clean_seq, &self.mapping)
+            .ok_or_else(|| anyhow!("Sequence too short for a spectrum"))?;
+        Ok(spectrum_stats
+            .to_vec()
+            .into_iter()
+            .map(Value::Float)
+            .collect())
+    }
+}
+
+/// Composition of nucleotide pairs with gaps up to `gap_max`
+pub struct Cksnap {
+    pub gap_max: usize,
+}
+
+impl Feature for Cksnap {
+    fn name(&self) -> &str {
+        "cksnap"
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        floats(&stats::entropy::cksnap_names(self.gap_max))
+    }
+
+    fn compute(&self, clean_seq: &str) -> Result<Vec<Value>> {
+        let composition = stats::entropy::cksnap(clean_seq, self.gap_max);
+        Ok(composition.into_iter().map(Value::Float).collect())
+    }
+}
+
+/// Base composition at each codon position, of the whole sequence or of its
+/// longest ORF with the columns prefixed `orf_`
+pub struct CodonPosition {
+    pub orf: bool,
+}
+
+impl Feature for CodonPosition {
+    fn name(&self) -> &str {
+        if self.orf {
+            "orf_codon_position"
+        } else {
+            "codon_position"
+        }
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        if self.orf {
+            let names: Vec<String> = CODON_POSITION_NAMES
+                .iter()
+                .map(|x| format!("orf_{}", x))
+                .collect();
+            floats(&names)
+        } else {
+            floats(&CODON_POSITION_NAMES)
+        }
+    }
+
+    fn compute(&self, clean_seq: &str) -> Result<Vec<Value>> {
+        let seq = if self.orf {
+            let orf = coding::orf::longest(clean_seq).ok_or_else(|| anyhow!("No ORF found"))?;
+            &clean_seq[orf]
+        } else {
+            clean_seq
+        };
+        let content = stats::counts::codon_position_content(seq);
+        Ok(content.into_iter().map(Value::Float).collect())
+    }
+}
+
+/// Physicochemical properties of the peptide of the longest ORF
+pub struct OrfPeptide;
+
+impl Feature for OrfPeptide {
+    fn name(&self) -> &str {
+        "orf_peptide"
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        let names: Vec<String> = PEPTIDE_FEATURE_NAMES
+            .iter()
+            .map(|x| format!("orf_pep_{}", x))
+            .collect();
+        floats(&names)
+    }
+
+    fn compute(&self, clean_seq: &str) -> Result<Vec<Value>> {
+        let orf = coding::orf::longest(clean_seq).ok_or_else(|| anyhow!("No ORF found"))?;
+        let peptide = coding::peptide::translate(&clean_seq[orf]);
+        let values = coding::peptide::peptide_features(&peptide);
+        Ok(values.into_iter().map(Value::Float).collect())
+    }
+}
+
+/// Minimum free energy features, then the structure in `mfe_structure`
+pub struct Mfe {
+    pub params: EnergyParams,
+}
+
+impl Feature for Mfe {
+    fn name(&self) -> &str {
+        "mfe"
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        let mut columns = floats(&MFE_FEATURE_NAMES);
+        columns.push(Column::text("mfe_structure"));
+        columns
+    }
+
+    fn compute(&self, clean_seq: &str) -> Result<Vec<Value>> {
+        let fold = structure::mfe::fold(clean_seq, &self.params);
+        let mut values = float_values(structure::mfe::mfe_features(clean_seq, &fold));
+        values.push(Value::Text(fold.structure));
+        Ok(values)
+    }
+}
+
+/// Partition function ensemble features
+pub struct Partition {
+    pub params: EnergyParams,
+}
+
+impl Feature for Partition {
+    fn name(&self) -> &str {
+        "partition"
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        floats(&ENSEMBLE_FEATURE_NAMES)
+    }
+
+    fn compute(&self, clean_seq: &str) -> Result<Vec<Value>> {
+        let ensemble = structure::partition::ensemble(clean_seq, &self.params);
+        let values = structure::partition::ensemble_features(&ensemble);
+        Ok(values.into_iter().map(Value::Float).collect())
+    }
+}
+
+/// Probability that each base is unpaired, as a list
+pub struct UnpairedProbability {
+    pub params: EnergyParams,
+}
+
+impl Feature for UnpairedProbability {
+    fn name(&self) -> &str {
+        "unpaired_probability"
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        vec![Column::float_list("unpaired_probability")]
+    }
+
+    fn compute(&self, clean_seq: &str) -> Result<Vec<Value>> {
+        let ensemble = structure::partition::ensemble(clean_seq, &self.params);
+        Ok(vec![Value::FloatList(ensemble.unpaired())])
+    }
+}
+
+/// Fraction of bases paired by base pair maximisation
+pub struct Nussinov {
+    pub params: NussinovParams,
+}
+
+impl Feature for Nussinov {
+    fn name(&self) -> &str {
+        "nussinov"
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        vec![Column::float("nussinov_paired_fraction")]
+    }
+
+    fn compute(&self, clean_seq: &str) -> Result<Vec<Value>> {
+        let pairs = structure::nussinov::max_pairs(clean_seq, &self.params);
+        let fraction = 2.0 * pairs.len() as f64 / clean_seq.len() as f64;
+        Ok(vec![Value::Float(fraction)])
+    }
+}
+
+/// Accumulated nucleotide frequencies, padded or truncated to `length`, as
+/// a list
+pub struct Anf {
+    pub length: usize,
+}
+
+impl Feature for Anf {
+    fn name(&self) -> &str {
+        "anf"
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        vec![Column::float_list("anf")]
+    }
+
+    fn compute(&self, clean_seq: &str) -> Result<Vec<Value>> {
+        let encoding = stats::anf::anf(clean_seq);
+        let values = stats::anf::fixed_length(encoding, self.length);
+        Ok(vec![Value::FloatList(values)])
+    }
+}
+
+/// Pseudo k-tuple nucleotide composition
+pub struct Pseknc {
+    pub params: PseKncParams,
+    pub table: Arc<PropertyTable>,
+}
+
+impl Feature for Pseknc {
+    fn name(&self) -> &str {
+        "pseknc"
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        floats(&stats::pseknc::pseknc_names(&self.table, &self.params))
+    }
+
+    fn compute(&self, clean_seq: &str) -> Result<Vec<Value>> {
+        let values = stats::pseknc::pseknc(clean_seq, &self.table, &self.params)?;
+        Ok(float_values(values))
+    }
+}
+
+/// One of the dinucleotide property autocorrelation descriptors, for lags
+/// up to `lag`
+pub struct PropertyAutocorrelation {
+    pub kind: Autocorrelation,
+    pub lag: usize,
+    pub table: Arc<PropertyTable>,
+}
+
+impl Feature for PropertyAutocorrelation {
+    fn name(&self) -> &str {
+        "autocorrelation"
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        let names = stats::autocorrelation::autocorrelation_names(&self.table, self.lag, self.kind);
+        floats(&names)
+    }
+
+    fn compute(&self, clean_seq: &str) -> Result<Vec<Value>> {
+        let values =
+            stats::autocorrelation::autocorrelation(clean_seq, &self.table, self.lag, self.kind)?;
+        Ok(float_values(values))
+    }
+}
+
+/// Windowed power spectrum in `bins` frequency bands, as a list named
+/// after the mapping
+pub struct SpectralFingerprint {
+    pub mapping: Mapping,
+    pub window: Window,
+    pub bins: usize,
+}
+
+impl Feature for SpectralFingerprint {
+    fn name(&self) -> &str {
+        "spectral_fingerprint"
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        vec![Column::float_list(&format!("psd_{}", self.mapping.name()))]
+    }
+
+    fn compute(&self, clean_seq: &str) -> Result<Vec<Value>> {
+        let fingerprint = stats::spectral::spectral_fingerprint(
+            clean_seq,
+            &self.mapping,
+            self.window,
+            self.bins,
+        )?;
+        Ok(vec![Value::FloatList(fingerprint)])
+    }
+}
+
+/// Statistics of each level of a discrete wavelet transform
+pub struct WaveletStats {
+    pub wavelet: Wavelet,
+    pub mapping: Mapping,
+    pub levels: usize,
+}
+
+impl Feature for WaveletStats {
+    fn name(&self) -> &str {
+        "wavelet"
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        let names = stats::wavelet::wavelet_feature_names(&self.mapping, self.wavelet, self.levels);
+        floats(&names)
+    }
+
+    fn compute(&self, clean_seq: &str) -> Result<Vec<Value>> {
+        let values =
+            stats::wavelet::wavelet_features(clean_seq, &self.mapping, self.wavelet, self.levels);
+        Ok(float_values(values))
+    }
+}
+
+/// A set of Z-curve parameters, of the whole sequence or of its longest ORF
+/// with the columns prefixed `orf_`
+pub struct ZCurve {
+    pub params: ZCurveParams,
+    pub orf: bool,
+}
+
+impl Feature for ZCurve {
+    fn name(&self) -> &str {
+        if self.orf {
+            "orf_zcurve"
+        } else {
+            "zcurve"
+        }
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        let names = stats::zcurve::z_curve_param_names(self.params);
+        if self.orf {
+            let names: Vec<String> = names.iter().map(|x| format!("orf_{}", x)).collect();
+            floats(&names)
+        } else {
+            floats(&names)
+        }
+    }
+
+    fn compute(&self, clean_seq: &str) -> Result<Vec<Value>> {
+        let seq = if self.orf {
+            let orf = coding::orf::longest(clean_seq).ok_or_else(|| anyhow!("No ORF found"))?;
+            &clean_seq[orf]
+        } else {
+            clean_seq
+        };
+        Ok(float_values(stats::zcurve::z_curve_params(
+            seq,
+            self.params,
+        )))
+    }
+}
+
+/// Frequency chaos game representation at resolution `2^k` by `2^k`, as a
+/// list
+pub struct Fcgr {
+    pub k: usize,
+}
+
+impl Feature for Fcgr {
+    fn name(&self) -> &str {
+        "fcgr"
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        vec![Column::float_list(&format!("fcgr_k{}", self.k))]
+    }
+
+    fn compute(&self, clean_seq: &str) -> Result<Vec<Value>> {
+        let frequencies = stats::cgr::fcgr_frequencies(clean_seq, self.k);
+        Ok(vec![Value::FloatList(frequencies)])
+    }
+}
+
+/// Fractal dimensions and Hurst exponent of a numeric signal
+pub struct Fractal {
+    pub signal: FractalSignal,
+    pub higuchi_kmax: usize,
+}
+
+impl Feature for Fractal {
+    fn name(&self) -> &str {
+        "fractal"
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        floats(&stats::fractal::fractal_feature_names(self.signal))
+    }
+
+    fn compute(&self, clean_seq: &str) -> Result<Vec<Value>> {
+        let values = stats::fractal::fractal_features(clean_seq, self.signal, self.higuchi_kmax);
+        Ok(float_values(values))
+    }
+}
+
+/// Topology of the de Bruijn style graph of kmers of length `k`
+pub struct KmerGraphStats {
+    pub k: usize,
+}
+
+impl Feature for KmerGraphStats {
+    fn name(&self) -> &str {
+        "kmer_graph"
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        floats(&stats::graph::graph_feature_names(self.k))
+    }
+
+    fn compute(&self, clean_seq: &str) -> Result<Vec<Value>> {
+        Ok(float_values(stats::graph::graph_features(
+            clean_seq, self.k,
+        )))
+    }
+}
+
+/// Features of a known structure given with the sequence
+pub struct KnownStructure;
+
+impl Feature for KnownStructure {
+    fn name(&self) -> &str {
+        "structure"
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        floats(&STRUCTURE_FEATURE_NAMES)
+    }
+
+    fn compute(&self, _clean_seq: &str) -> Result<Vec<Value>> {
+        Err(anyhow!("No structure given"))
+    }
+
+    fn compute_with_structure(
+        &self,
+        _clean_seq: &str,
+        structure: Option<&str>,
+    ) -> Result<Vec<Value>> {
+        let structure = structure.ok_or_else(|| anyhow!("No structure given"))?;
+        let values = structure::features::structure_features(structure)?;
+        Ok(float_values(values))
+    }
+}
+
+/// Composition of structure triplets of a known structure given with the
+/// sequence
+pub struct StructureTriplets;
+
+impl Feature for StructureTriplets {
+    fn name(&self) -> &str {
+        "structure_triplets"
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        floats(&structure::features::triplet_names())
+    }
+
+    fn compute(&self, _clean_seq: &str) -> Result<Vec<Value>> {
+        Err(anyhow!("No structure given"))
+    }
+
+    fn compute_with_structure(
+        &self,
+        clean_seq: &str,
+        structure: Option<&str>,
+    ) -> Result<Vec<Value>> {
+        let structure = structure.ok_or_else(|| anyhow!("No structure given"))?;
+        let values = structure::features::triplet_composition(clean_seq, structure)?;
+        Ok(float_values(values))
+    }
+}
+
+/// Z-score and empirical p-value of another feature's first column against
+/// kmer preserving shuffles of the sequence
+pub struct NullScores {
+    name: String,
+    column: String,
+    pub feature: Box<dyn Feature>,
+    pub tail: Tail,
+    pub shuffles: usize,
+    /// Length of kmer whose counts each shuffle keeps
+    pub k: usize,
+    pub seed: u64,
+}
+
+impl NullScores {
+    /// Score `feature`, naming the result after it like `mfe_null`
+    ///
+    /// Errors if the feature's first column isn't a number.
+    pub fn new(
+        feature: Box<dyn Feature>,
+        tail: Tail,
+        shuffles: usize,
+        k: usize,
+        seed: u64,
+    ) -> Result<Self> {
+        let column = match feature.columns().first() {
+            Some(column) if column.kind == ColumnType::Float => column.name.clone(),
+            _ => {
+                return Err(anyhow!(
+                    "Feature {} has no number to score against shuffles",
+                    feature.name()
+                ))
+            }
+        };
+        Ok(NullScores {
+            name: format!("{}_null", feature.name()),
+            column,
+            feature,
+            tail,
+            shuffles,
+            k,
+            seed,
+        })
+    }
+}
+
+impl Feature for NullScores {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn alphabet(&self) -> Alphabet {
+        self.feature.alphabet()
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        floats(&stats::null::null_score_names(&self.column))
+    }
+
+    fn compute(&self, clean_seq: &str) -> Result<Vec<Value>> {
+        // Shuffles the feature doesn't apply to count as NaN
+        let first = |values: Result<Vec<Value>>| match values.as_deref() {
+            Ok([Value::Float(x), ..]) => *x,
+            _ => f64::NAN,
+        };
+        let value = first(Ok(self.feature.compute(clean_seq)?));
+        let score = stats::null::null_score_of(
+            value,
+            clean_seq,
+            |seq| first(self.feature.compute(seq)),
+            self.tail,
+            self.shuffles,
+            self.k,
+            self.seed,
+        );
+        Ok(float_values(score.stats()))
+    }
+}
+
+/// Sequence length, in column `length`
+pub struct Length;
+
+impl Feature for Length {
+    fn name(&self) -> &str {
+        "length"
+    }
+
+    fn alphabet(&self) -> Alphabet {
+        Alphabet::Any
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        vec![Column::float("length")]
+    }
+
+    fn compute(&self, clean_seq: &str) -> Result<Vec<Value>> {
+        Ok(vec![Value::Float(clean_seq.len() as f64)])
+    }
+}
+
+/// Amino acid composition of a protein
+pub struct Aac;
+
+impl Feature for Aac {
+    fn name(&self) -> &str {
+        "aac"
+    }
+
+    fn alphabet(&self) -> Alphabet {
+        Alphabet::Protein
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        floats(&protein::composition::aac_names())
+    }
+
+    fn compute(&self, clean_seq: &str) -> Result<Vec<Value>> {
+        Ok(float_values(protein::composition::aac(clean_seq)))
+    }
+}
+
+/// Dipeptide composition of a protein
+pub struct Dpc;
+
+impl Feature for Dpc {
+    fn name(&self) -> &str {
+        "dpc"
+    }
+
+    fn alphabet(&self) -> Alphabet {
+        Alphabet::Protein
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        floats(&protein::composition::dpc_names())
+    }
+
+    fn compute(&self, clean_seq: &str) -> Result<Vec<Value>> {
+        Ok(float_values(protein::composition::dpc(clean_seq)))
+    }
+}
+
+/// Frequencies of kmers of length `k` over a reduced amino acid alphabet
+pub struct ReducedKmers {
+    pub alphabet: ReducedAlphabet,
+    pub k: usize,
+}
+
+impl Feature for ReducedKmers {
+    fn name(&self) -> &str {
+        "reduced_kmers"
+    }
+
+    fn alphabet(&self) -> Alphabet {
+        Alphabet::Protein
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        floats(&protein::composition::reduced_kmer_names(
+            self.alphabet,
+            self.k,
+        ))
+    }
+
+    fn compute(&self, clean_seq: &str) -> Result<Vec<Value>> {
+        let values =
+            protein::composition::reduced_kmer_frequencies(clean_seq, self.alphabet, self.k);
+        Ok(float_values(values))
+    }
+}
+
+/// Composition, transition and distribution descriptors of a protein
+pub struct Ctd;
+
+impl Feature for Ctd {
+    fn name(&self) -> &str {
+        "ctd"
+    }
+
+    fn alphabet(&self) -> Alphabet {
+        Alphabet::Protein
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        floats(&protein::ctd::ctd_names())
+    }
+
+    fn compute(&self, clean_seq: &str) -> Result<Vec<Value>> {
+        Ok(float_values(protein::ctd::ctd(clean_seq)))
+    }
+}
+
+/// A set of features, looked up by name
+#[derive(Default)]
+pub struct Registry {
+    features: Vec<Box<dyn Feature>>,
+}
+
+impl Registry {
+    /// An empty registry
+    pub fn new() -> Self {
+        Registry::default()
+    }
+
+    /// A registry of the built-in features with their default parameters
+    pub fn builtin() -> Self {
+        let params = EnergyParams::turner2004();
+        let mut registry = Registry::new();
+        registry.register(GcContent);
+        registry.register(Fickett);
+        registry.register(Period3Snr);
+        registry.register(Period3Profile::default());
+        registry.register(Shannon {
+            kmer_min: 1,
+            kmer_max: 4,
+        });
+        registry.register(Tsallis {
+            kmer_min: 1,
+            kmer_max: 4,
+            q: 2.0,
+        });
+        registry.register(CodonPosition { orf: false });
+        registry.register(CodonPosition { orf: true });
+        registry.register(OrfPeptide);
+        registry.register(Fourier {
+            mapping: Mapping::Eiip,
+        });
+        registry.register(Cksnap { gap_max: 3 });
+        registry.register(Mfe {
+            params: params.clone(),
+        });
+        registry.register(Partition {
+            params: params.clone(),
+        });
+        registry.register(UnpairedProbability {
+            params: params.clone(),
+        });
+        registry.register(Nussinov {
+            params: NussinovParams::default(),
+        });
+        registry.register(KnownStructure);
+        registry.register(StructureTriplets);
+        let null = NullScores::new(Box::new(Mfe { params }), Tail::Lower, 100, 2, 1)
+            .expect("MFE has a number to score");
+        registry.register(null);
+        let table = Arc::new(PropertyTable::rna());
+        registry.register(Anf { length: 100 });
+        registry.register(Pseknc {
+            params: PseKncParams::default(),
+            table: table.clone(),
+        });
+        registry.register(PropertyAutocorrelation {
+            kind: Autocorrelation::Dac,
+            lag: 3,
+            table,
+        });
+        registry.register(SpectralFingerprint {
+            mapping: Mapping::Binary,
+            window: Window::Hann,
+            bins: 32,
+        });
+        registry.register(WaveletStats {
+            wavelet: Wavelet::default(),
+            mapping: Mapping::Eiip,
+            levels: 4,
+        });
+        registry.register(ZCurve {
+            params: ZCurveParams::Mono9,
+            orf: false,
+        });
+        registry.register(ZCurve {
+            params: ZCurveParams::Mono9,
+            orf: true,
+        });
+        registry.register(Fcgr { k: 4 });
+        registry.register(Fractal {
+            signal: FractalSignal::DnaWalk,
+            higuchi_kmax: 8,
+        });
+        registry.register(KmerGraphStats { k: 3 });
+        registry.register(Length);
+        registry.register(Aac);
+        registry.register(Dpc);
+        registry.register(ReducedKmers {
+            alphabet: ReducedAlphabet::Triad7,
+            k: 3,
+        });
+        registry.register(Ctd);
+        registry
+    }
+
+    /// Add a feature
+    ///
+    /// A feature with the same name as one already registered replaces it in
+    /// place, so a built-in feature can be given different parameters.
+    pub fn register<F: Feature + 'static>(&mut self, feature: F) {
+        match self
+            .features
+            .iter()
+            .position(|x| x.name() == feature.name())
+        {
+            Some(idx) => self.features[idx] = Box::new(feature),
+            None => self.features.push(Box::new(feature)),
+        }
+    }
+
+    /// Look up a feature by name
+    pub fn get(&self, name: &str) -> Option<&dyn Feature> {
+        self.features
+            .iter()
+            .find(|x| x.name() == name)
+            .map(|x| x.as_ref())
+    }
+
+    /// Look up several features by name, in the order given
+    ///
+    /// Errors on the first name that isn't registered.
+    pub fn select<S: AsRef<str>>(&self, names: &[S]) -> Result<Vec<&dyn Feature>> {
+        names
+            .iter()
+            .map(|name| {
+                self.get(name.as_ref())
+                    .ok_or_else(|| anyhow!("Unknown feature {}", name.as_ref()))
+            })
+            .collect()
+    }
+
+    /// All the features, in the order they were registered
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Feature> {
+        self.features.iter().map(|x| x.as_ref())
+    }
+
+    /// Names of all the features, in the order they were registered
+    pub fn names(&self) -> Vec<&str> {
+        self.iter().map(|x| x.name()).collect()
+    }
+}
+
+/// Names of the built-in features
+#[wasm_bindgen(js_name=feature_names)]
+pub fn feature_names() -> Vec<String> {
+    BUILTIN.names().into_iter().map(String::from).collect()
+}
+
+/// Output column names of a built-in feature, or none for an unknown name
+#[wasm_bindgen(js_name=feature_columns)]
+pub fn feature_columns(name: &str) -> Vec<String> {
+    BUILTIN
+        .get(name)
+        .map(|x| x.columns().into_iter().map(|column| column.name).collect())
+        .unwrap_or_default()
+}
+
+/// Compute a built-in feature, with any list values flattened in place
+///
+/// Returns nothing for an unknown name or a feature that doesn't apply to
+/// the sequence.
+#[wasm_bindgen(js_name=compute_feature)]
+pub fn compute_feature(name: &str, clean_seq: &str) -> Vec<f64> {
+    BUILTIN
+        .get(name)
+        .and_then(|x| x.compute(clean_seq).ok())
+        .map(|values| values.iter().flat_map(Value::to_vec).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::feature::{
+        self, Alphabet, ColumnType, Feature, GcContent, NullScores, Registry, Shannon, Value,
+    };
+    use crate::stats::entropy;
+    use crate::stats::null::Tail;
+
+    #[test]
+    fn test_builtin_columns() {
+        // Every feature returns a value of the right type for each column
+        let registry = Registry::builtin();
+        let seq = "AUGGCUACGUAGCCAUUAGCGGAUCCAUGCAAAUGCGCGUAUAUCCGAUGA";
+        let structure = "((((....))))...((((((....))))))....................";
+        let peptide = "MASVLQKRSTWYPGHECDFNI";
+        for feature in registry.iter() {
+            let columns = feature.columns();
+            let values = match feature.alphabet() {
+                Alphabet::Protein => feature.compute(peptide),
+                _ => feature.compute_with_structure(seq, Some(structure)),
+            }
+            .unwrap();
+            assert_eq!(columns.len(), values.len(), "{}", feature.name());
+            for (column, value) in columns.iter().zip(&values) {
+                match (column.kind, value) {
+                    (ColumnType::Float, Value::Float(_)) => {}
+                    (ColumnType::FloatList, Value::FloatList(_)) => {}
+                    (ColumnType::Text, Value::Text(_)) => {}
+                    _ => panic!("{} has the wrong type", column.name),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_register() {
+        let mut registry = Registry::builtin();
+        let count = registry.names().len();
+        registry.register(Shannon {
+            kmer_min: 2,
+            kmer_max: 3,
+        });
+        assert_eq!(registry.names().len(), count);
+        let shannon = registry.get("shannon").unwrap();
+        assert_eq!(shannon.columns()[0].name, "shannon_2");
+        let values = shannon.compute("AUGC").unwrap();
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[1], Value::Float(entropy::shannon("AUGC", 3)[2]));
+
+        let selected = registry.select(&["tsallis", "gc_content"]).unwrap();
+        assert_eq!(selected[1].columns()[0].name, "gc_cont");
+        assert_eq!(
+            registry
+                .select(&["gc_content", "nope"])
+                .err()
+                .unwrap()
+                .to_string(),
+            "Unknown feature nope"
+        );
+    }
+
+    #[test]
+    fn test_missing_orf() {
+        let registry = Registry::builtin();
+        assert!(registry
+            .get("orf_peptide")
+            .unwrap()
+            .compute("CCCC")
+            .is_err());
+        assert!(feature::compute_feature("orf_codon_position", "CCCC").is_empty());
+        assert_eq!(feature::compute_feature("gc_content", "GCAU"), [0.5]);
+        assert_eq!(feature::feature_columns("shannon")[3], "shannon_4");
+        assert!(feature::feature_names().contains(&"nussinov".to_owned()));
+    }
+
+    #[test]
+    fn test_structure_features() {
+        let registry = Registry::builtin();
+        let structure = registry.get("structure").unwrap();
+        assert_eq!(
+            structure.compute("GGGAAACCC").err().unwrap().to_string(),
+            "No structure given"
+        );
+        let values = structure
+            .compute_with_structure("GGGAAACCC", Some("(((...)))"))
+            .unwrap();
+        assert_eq!(values.len(), structure.columns().len());
+        // Features of the sequence alone ignore any structure
+        let gc = registry.get("gc_content").unwrap();
+        assert_eq!(
+            gc.compute_with_structure("GCAU", Some("....")).unwrap(),
+            gc.compute("GCAU").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_null_scores() {
+        let null = NullScores::new(Box::new(GcContent), Tail::Upper, 10, 1, 1).unwrap();
+        assert_eq!(null.name(), "gc_content_null");
+        assert_eq!(null.columns()[0].name, "gc_cont_z_score");
+        // Shuffles keeping mononucleotide counts keep the GC content, so
+        // every shuffle is as extreme
+        let values = null.compute("AUGGCUACGUAGCC").unwrap();
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[1], Value::Float(1.0));
+        let shannon = Box::new(Shannon {
+            kmer_min: 1,
+            kmer_max: 2,
+        });
+        let null = NullScores::new(shannon, Tail::Upper, 10, 1, 1).unwrap();
+        assert_eq!(null.alphabet(), Alphabet::Any);
+
+        let fcgr = Box::new(feature::Fcgr { k: 2 });
+        assert_eq!(
+            NullScores::new(fcgr, Tail::Upper, 10, 1, 1)
+                .err()
+                .unwrap()
+                .to_string(),
+            "Feature fcgr has no number to score against shuffles"
+        );
+    }
+}
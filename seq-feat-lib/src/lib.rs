@@ -13,5 +13,8 @@
 extern crate lazy_static;
 
 pub mod coding;
+pub mod feature;
+pub mod protein;
 pub mod stats;
+pub mod structure;
 pub mod utils;
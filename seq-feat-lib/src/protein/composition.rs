@@ -0,0 +1,217 @@
+//! Composition descriptors of protein sequences
+//!
+//! The protein counterparts of the kmer composition features: amino acid
+//! composition (AAC), dipeptide composition (DPC) and kmers over reduced
+//! alphabets, which group amino acids with similar properties so that longer
+//! kmers stay a manageable size.
+use crate::coding::peptide::{residue_index, AMINO_ACIDS};
+use anyhow::{anyhow, Result};
+use std::str::FromStr;
+use wasm_bindgen::prelude::*;
+
+/// Fraction of a peptide made up of each amino acid, in `AMINO_ACIDS` order
+///
+/// An empty peptide gives all zeros.
+#[wasm_bindgen(js_name=aac)]
+pub fn aac(peptide: &str) -> Vec<f64> {
+    let mut counts = [0u64; 20];
+    for residue in peptide.bytes().filter_map(residue_index) {
+        counts[residue] += 1;
+    }
+    frequencies(&counts, peptide.len())
+}
+
+/// Divide counts by a total, or all zeros if there's nothing to count
+fn frequencies(counts: &[u64], total: usize) -> Vec<f64> {
+    let total = total.max(1) as f64;
+    counts.iter().map(|x| *x as f64 / total).collect()
+}
+
+/// Get the column names for `aac`, like `aac_W`
+pub fn aac_names() -> Vec<String> {
+    AMINO_ACIDS.iter().map(|x| format!("aac_{}", x)).collect()
+}
+
+/// Frequencies of the 400 overlapping dipeptides
+///
+/// Indexed `20 * first + second` in `AMINO_ACIDS` order, and divided by the
+/// number of dipeptides in the sequence. A peptide without any dipeptides,
+/// of one residue or none, gives all zeros.
+#[wasm_bindgen(js_name=dpc)]
+pub fn dpc(peptide: &str) -> Vec<f64> {
+    let residues: Vec<Option<usize>> = peptide.bytes().map(residue_index).collect();
+    let mut counts = vec![0u64; 400];
+    for pair in residues.windows(2) {
+        if let [Some(first), Some(second)] = pair {
+            counts[20 * first + second] += 1;
+        }
+    }
+    frequencies(&counts, peptide.len().saturating_sub(1))
+}
+
+/// Get the column names for `dpc`, like `dpc_KR`
+pub fn dpc_names() -> Vec<String> {
+    AMINO_ACIDS
+        .iter()
+        .flat_map(|x| AMINO_ACIDS.iter().map(move |y| format!("dpc_{}{}", x, y)))
+        .collect()
+}
+
+/// Groupings of the amino acids into smaller alphabets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReducedAlphabet {
+    /// The seven classes of the conjoint triad (Shen et al. 2007), by
+    /// dipole and side chain volume
+    Triad7,
+    /// Dayhoff's six classes of mutually substitutable amino acids
+    Dayhoff6,
+    /// Hydrophobic and polar, as in the HP lattice model
+    Hp2,
+}
+
+impl FromStr for ReducedAlphabet {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "triad7" => Ok(ReducedAlphabet::Triad7),
+            "dayhoff6" => Ok(ReducedAlphabet::Dayhoff6),
+            "hp2" => Ok(ReducedAlphabet::Hp2),
+            _ => Err(anyhow!("Unknown reduced alphabet {}", s)),
+        }
+    }
+}
+
+impl ReducedAlphabet {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ReducedAlphabet::Triad7 => "triad7",
+            ReducedAlphabet::Dayhoff6 => "dayhoff6",
+            ReducedAlphabet::Hp2 => "hp2",
+        }
+    }
+
+    /// The amino acids in each group
+    pub fn groups(&self) -> &'static [&'static str] {
+        match self {
+            ReducedAlphabet::Triad7 => &["AGV", "ILFP", "YMTS", "HNQW", "RK", "DE", "C"],
+            ReducedAlphabet::Dayhoff6 => &["AGPST", "C", "DENQ", "FWY", "HKR", "ILMV"],
+            ReducedAlphabet::Hp2 => &["ACFILMVW", "DEGHKNPQRSTY"],
+        }
+    }
+
+    /// Which group an amino acid is in
+    pub fn group(&self, residue: u8) -> Option<usize> {
+        self.groups()
+            .iter()
+            .position(|x| x.as_bytes().contains(&residue))
+    }
+}
+
+/// Frequencies of overlapping kmers in a reduced alphabet
+///
+/// Kmers are indexed with the first group most significant, as for
+/// nucleotide kmers, and divided by the number of kmers in the sequence.
+/// Kmers including anything outside the alphabet aren't counted, and a
+/// peptide shorter than k gives all zeros.
+pub fn reduced_kmer_frequencies(peptide: &str, alphabet: ReducedAlphabet, k: usize) -> Vec<f64> {
+    let size = alphabet.groups().len();
+    let groups: Vec<Option<usize>> = peptide.bytes().map(|x| alphabet.group(x)).collect();
+    let mut counts = vec![0u64; size.pow(k as u32)];
+    if k > 0 {
+        for kmer in groups.windows(k) {
+            let index = kmer
+                .iter()
+                .try_fold(0, |acc, x| x.map(|group| acc * size + group));
+            if let Some(index) = index {
+                counts[index] += 1;
+            }
+        }
+    }
+    frequencies(&counts, (peptide.len() + 1).saturating_sub(k))
+}
+
+/// Get the column names for `reduced_kmer_frequencies`, like
+/// `triad7_k3_152` with the groups numbered from 1
+pub fn reduced_kmer_names(alphabet: ReducedAlphabet, k: usize) -> Vec<String> {
+    let size = alphabet.groups().len();
+    (0..size.pow(k as u32))
+        .map(|index| {
+            let kmer: String = (0..k)
+                .rev()
+                .map(|position| {
+                    let group = index / size.pow(position as u32) % size;
+                    char::from_digit(group as u32 + 1, 10).unwrap()
+                })
+                .collect();
+            format!("{}_k{}_{}", alphabet.name(), k, kmer)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::protein::composition::{self, ReducedAlphabet};
+
+    #[test]
+    fn test_aac() {
+        let values = composition::aac("AACW");
+        assert_eq!(values.len(), 20);
+        assert_eq!(values[0], 0.5);
+        assert_eq!(values[1], 0.25);
+        assert_eq!(values[18], 0.25);
+        assert_eq!(composition::aac_names()[18], "aac_W");
+    }
+
+    #[test]
+    fn test_dpc() {
+        let values = composition::dpc("AACA");
+        assert_eq!(values.len(), 400);
+        // AA, AC and CA
+        assert_eq!(values[0], 1.0 / 3.0);
+        assert_eq!(values[1], 1.0 / 3.0);
+        assert_eq!(values[20], 1.0 / 3.0);
+        assert_eq!(composition::dpc_names()[20], "dpc_CA");
+    }
+
+    #[test]
+    fn test_too_short() {
+        assert_eq!(composition::aac(""), [0.0; 20]);
+        assert_eq!(composition::dpc("A"), [0.0; 400]);
+        assert_eq!(composition::dpc(""), [0.0; 400]);
+        assert_eq!(
+            composition::reduced_kmer_frequencies("AV", ReducedAlphabet::Hp2, 3),
+            [0.0; 8]
+        );
+    }
+
+    #[test]
+    fn test_reduced_kmers() {
+        // Every amino acid is in exactly one group of each alphabet
+        for alphabet in [
+            ReducedAlphabet::Triad7,
+            ReducedAlphabet::Dayhoff6,
+            ReducedAlphabet::Hp2,
+        ] {
+            let letters: usize = alphabet.groups().iter().map(|x| x.len()).sum();
+            assert_eq!(letters, 20);
+            assert!(b"ACDEFGHIKLMNPQRSTVWY"
+                .iter()
+                .all(|x| alphabet.group(*x).is_some()));
+        }
+
+        // A and V are hydrophobic, K polar: AV, VK, KA
+        let values = composition::reduced_kmer_frequencies("AVKA", ReducedAlphabet::Hp2, 2);
+        assert_eq!(values, [1.0 / 3.0, 1.0 / 3.0, 1.0 / 3.0, 0.0]);
+        let names = composition::reduced_kmer_names(ReducedAlphabet::Hp2, 2);
+        assert_eq!(names, ["hp2_k2_11", "hp2_k2_12", "hp2_k2_21", "hp2_k2_22"]);
+
+        let names = composition::reduced_kmer_names(ReducedAlphabet::Triad7, 3);
+        assert_eq!(names.len(), 343);
+        assert_eq!(names[4 * 7 * 7 + 1], "triad7_k3_512");
+        assert_eq!(
+            "dayhoff6".parse::<ReducedAlphabet>().unwrap(),
+            ReducedAlphabet::Dayhoff6
+        );
+    }
+}
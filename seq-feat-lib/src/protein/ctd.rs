@@ -0,0 +1,172 @@
+//! Composition, transition and distribution (CTD) descriptors
+//!
+//! For each of seven physicochemical properties (Dubchak et al. 1995) the
+//! amino acids are split into three groups, and the sequence is described by
+//! how much of it falls in each group (composition), how often neighbouring
+//! residues change between each pair of groups (transition), and where along
+//! the sequence the first, 25%, 50%, 75% and last residues of each group fall
+//! (distribution). The groups and the distribution positions follow propy.
+
+/// The properties and their three groups, from low to high
+pub const CTD_PROPERTIES: [(&str, [&str; 3]); 7] = [
+    ("hydrophobicity", ["RKEDQN", "GASTPHY", "CLVIMFW"]),
+    ("vdw_volume", ["GASTPDC", "NVEQIL", "MHKFRYW"]),
+    ("polarity", ["LIFWCMVY", "PAGST", "HQRKNED"]),
+    ("polarizability", ["GASDT", "CPNVEQIL", "KMHFRYW"]),
+    ("charge", ["KR", "ANCQGHILMFPSTWYV", "DE"]),
+    ("secondary_structure", ["EALMQKRH", "VIYCWFT", "GNPSD"]),
+    ("solvent_accessibility", ["ALFCGIVW", "RKQEND", "MPSTHY"]),
+];
+
+/// Fractions of each group's residues at which the distribution is taken
+const DISTRIBUTION: [(f64, &str); 5] = [
+    (0.0, "first"),
+    (0.25, "25"),
+    (0.5, "50"),
+    (0.75, "75"),
+    (1.0, "100"),
+];
+
+/// Calculate the CTD descriptors of a peptide
+///
+/// For each property in `CTD_PROPERTIES` order there are 21 values: the
+/// fraction of residues in each group, the fraction of neighbouring pairs
+/// changing between groups 1 and 2, 1 and 3, and 2 and 3 in either
+/// direction, then for each group the positions in `DISTRIBUTION` as
+/// fractions of the sequence length, or zero if the group is absent.
+/// Compositions of an empty peptide, and transitions of a peptide with no
+/// neighbouring pairs, are zero as well.
+pub fn ctd(peptide: &str) -> Vec<f64> {
+    let length = peptide.len().max(1) as f64;
+    let mut values = Vec::with_capacity(21 * CTD_PROPERTIES.len());
+    for (_, groups) in CTD_PROPERTIES {
+        let classes: Vec<Option<usize>> = peptide
+            .bytes()
+            .map(|x| {
+                groups
+                    .iter()
+                    .position(|group| group.as_bytes().contains(&x))
+            })
+            .collect();
+
+        for group in 0..3 {
+            let count = classes.iter().filter(|x| **x == Some(group)).count();
+            values.push(count as f64 / length);
+        }
+
+        let mut transitions = [0u64; 3];
+        for pair in classes.windows(2) {
+            match pair {
+                [Some(0), Some(1)] | [Some(1), Some(0)] => transitions[0] += 1,
+                [Some(0), Some(2)] | [Some(2), Some(0)] => transitions[1] += 1,
+                [Some(1), Some(2)] | [Some(2), Some(1)] => transitions[2] += 1,
+                _ => {}
+            }
+        }
+        let pairs = peptide.len().saturating_sub(1).max(1) as f64;
+        values.extend(transitions.iter().map(|x| *x as f64 / pairs));
+
+        for group in 0..3 {
+            let positions: Vec<usize> = classes
+                .iter()
+                .enumerate()
+                .filter(|(_, x)| **x == Some(group))
+                .map(|(idx, _)| idx + 1)
+                .collect();
+            for (fraction, _) in DISTRIBUTION {
+                if positions.is_empty() {
+                    values.push(0.0);
+                } else {
+                    // The nth residue of the group, counting the first as
+                    // the 0% residue
+                    let nth = ((fraction * positions.len() as f64).floor() as usize).max(1);
+                    values.push(positions[nth - 1] as f64 / length);
+                }
+            }
+        }
+    }
+    values
+}
+
+/// Get the column names for `ctd`, like `ctd_charge_c1`, `ctd_charge_t12`
+/// and `ctd_charge_d3_25`
+pub fn ctd_names() -> Vec<String> {
+    let mut names = Vec::with_capacity(21 * CTD_PROPERTIES.len());
+    for (property, _) in CTD_PROPERTIES {
+        for group in 1..=3 {
+            names.push(format!("ctd_{}_c{}", property, group));
+        }
+        for pair in ["12", "13", "23"] {
+            names.push(format!("ctd_{}_t{}", property, pair));
+        }
+        for group in 1..=3 {
+            for (_, position) in DISTRIBUTION {
+                names.push(format!("ctd_{}_d{}_{}", property, group, position));
+            }
+        }
+    }
+    names
+}
+
+#[cfg(test)]
+mod test {
+    use crate::protein::ctd;
+
+    #[test]
+    fn test_groups() {
+        // Each property puts every amino acid in exactly one group
+        for (property, groups) in ctd::CTD_PROPERTIES {
+            let mut letters: Vec<u8> = groups.iter().flat_map(|x| x.bytes()).collect();
+            letters.sort_unstable();
+            assert_eq!(letters, b"ACDEFGHIKLMNPQRSTVWY", "{}", property);
+        }
+    }
+
+    #[test]
+    fn test_charge() {
+        // Charge groups: K positive, A neutral, D negative
+        let values = ctd::ctd("KAAKDAAA");
+        let names = ctd::ctd_names();
+        assert_eq!(values.len(), 147);
+        assert_eq!(names.len(), 147);
+
+        let charge = 4 * 21;
+        assert_eq!(names[charge], "ctd_charge_c1");
+        assert_eq!(values[charge..charge + 3], [0.25, 0.625, 0.125]);
+        // KA and AK, KD, then DA
+        assert_eq!(names[charge + 4], "ctd_charge_t13");
+        assert_eq!(
+            values[charge + 3..charge + 6],
+            [2.0 / 7.0, 1.0 / 7.0, 1.0 / 7.0]
+        );
+        // K at 1 and 4, so 1 for the first, 25% and 50%, then 4
+        assert_eq!(names[charge + 6], "ctd_charge_d1_first");
+        assert_eq!(
+            values[charge + 6..charge + 11],
+            [0.125, 0.125, 0.125, 0.125, 0.5]
+        );
+        // A at 2, 3, 6, 7 and 8
+        assert_eq!(
+            values[charge + 11..charge + 16],
+            [0.25, 0.25, 0.375, 0.75, 1.0]
+        );
+    }
+
+    #[test]
+    fn test_absent_group() {
+        let values = ctd::ctd("AAAA");
+        let charge = 4 * 21;
+        assert_eq!(values[charge + 1], 1.0);
+        assert_eq!(values[charge + 6..charge + 11], [0.0; 5]);
+    }
+
+    #[test]
+    fn test_too_short() {
+        // A single residue has no transitions, and nothing has no composition
+        let values = ctd::ctd("K");
+        let charge = 4 * 21;
+        assert_eq!(values[charge..charge + 6], [1.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
+        assert!(values.iter().all(|x| x.is_finite()));
+        assert!(ctd::ctd("").iter().all(|x| *x == 0.0));
+    }
+}
@@ -0,0 +1,2 @@
+pub mod composition;
+pub mod ctd;
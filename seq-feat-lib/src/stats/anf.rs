@@ -0,0 +1,129 @@
+//! Accumulated nucleotide frequency (ANF) encoding
+//!
+//! ANF is a per-position encoding used by MathFeature and iLearn, where each
+//! base is replaced by the density of that same base in the sequence up to
+//! and including that position. For a sequence `s` the value at position `i`
+//! (counting from 1) is `|{j <= i : s_j = s_i}| / i`.
+use crate::stats::composition::{base_index, OTHER};
+use crate::stats::fourier;
+use crate::stats::mapping::{Mapping, NumericMapping};
+use wasm_bindgen::prelude::*;
+
+/// Calculate the accumulated nucleotide frequency of every position
+///
+/// Characters that aren't nucleotides get an ANF of zero, but still count
+/// towards the position.
+#[wasm_bindgen(js_name=anf)]
+pub fn anf(clean_seq: &str) -> Vec<f64> {
+    let mut running = [0u64; 5];
+
+    clean_seq
+        .bytes()
+        .enumerate()
+        .map(|(idx, byte)| {
+            let base = base_index(byte);
+            running[base] += 1;
+            if base == OTHER {
+                0.0
+            } else {
+                running[base] as f64 / (idx + 1) as f64
+            }
+        })
+        .collect()
+}
+
+/// Combine the one-hot encoding with ANF into a per-position matrix
+///
+/// Each row is the binary A, C, G, U indicators from
+/// `fourier::seq_2_binary` followed by the ANF value. See `ncp_anf` for the
+/// same thing with the chemical property encoding.
+pub fn binary_anf(clean_seq: &str) -> Vec<[f64; 5]> {
+    let (b_a, b_c, b_g, b_u) = fourier::seq_2_binary(clean_seq);
+
+    anf(clean_seq)
+        .into_iter()
+        .enumerate()
+        .map(|(idx, density)| {
+            [
+                b_a[idx] as f64,
+                b_c[idx] as f64,
+                b_g[idx] as f64,
+                b_u[idx] as f64,
+                density,
+            ]
+        })
+        .collect()
+}
+
+/// Combine the nucleotide chemical property (NCP) encoding with ANF
+///
+/// Each row is the three NCP bits from `mapping::Mapping::Ncp` followed by
+/// the ANF value, the standard NCP+ANF input from iLearn.
+pub fn ncp_anf(clean_seq: &str) -> Vec<[f64; 4]> {
+    let ncp = Mapping::Ncp.map(clean_seq);
+
+    anf(clean_seq)
+        .into_iter()
+        .enumerate()
+        .map(|(idx, density)| [ncp[0][idx], ncp[1][idx], ncp[2][idx], density])
+        .collect()
+}
+
+/// Pad with zeros or truncate an encoding to a fixed length
+///
+/// Per-position encodings vary in length with the sequence, so they need to
+/// be forced to a common length before they can be stacked into a matrix.
+pub fn fixed_length(mut encoding: Vec<f64>, length: usize) -> Vec<f64> {
+    encoding.resize(length, 0.0);
+    encoding
+}
+
+#[cfg(test)]
+mod test {
+    use crate::stats::anf;
+
+    #[test]
+    fn test_anf() {
+        let res = anf::anf("UCGUUCAUGG");
+        let expected = [
+            1.0,
+            0.5,
+            1.0 / 3.0,
+            0.5,
+            0.6,
+            2.0 / 6.0,
+            1.0 / 7.0,
+            0.5,
+            2.0 / 9.0,
+            0.3,
+        ];
+
+        assert_eq!(res.len(), expected.len());
+        for (x, y) in res.iter().zip(expected) {
+            assert!((x - y).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_binary_anf() {
+        let res = anf::binary_anf("AAC");
+
+        assert_eq!(res[0], [1.0, 0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(res[1], [1.0, 0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(res[2], [0.0, 1.0, 0.0, 0.0, 1.0 / 3.0]);
+    }
+
+    #[test]
+    fn test_ncp_anf() {
+        let res = anf::ncp_anf("GU");
+
+        assert_eq!(res[0], [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(res[1], [0.0, 0.0, 1.0, 0.5]);
+    }
+
+    #[test]
+    fn test_fixed_length() {
+        assert_eq!(anf::fixed_length(vec![1.0, 0.5], 4), [1.0, 0.5, 0.0, 0.0]);
+        assert_eq!(anf::fixed_length(vec![1.0, 0.5, 0.3], 2), [1.0, 0.5]);
+    }
+}
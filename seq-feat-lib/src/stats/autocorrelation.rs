@@ -0,0 +1,381 @@
+//! Autocovariance and autocorrelation of dinucleotide properties
+//!
+//! Each dinucleotide step along the sequence is replaced by its value for a
+//! physicochemical property (see `pseknc::PropertyTable`), and the resulting
+//! numeric series is summarised by how it correlates with itself at lags
+//! `1..=max_lag`. The descriptors here follow the definitions used by repDNA
+//! and iLearn:
+//!
+//! - DAC, dinucleotide auto covariance, per property
+//! - DCC, dinucleotide cross covariance, between each ordered pair of
+//!   different properties
+//! - DACC, DAC followed by DCC, with its own column prefix so it can be
+//!   computed alongside them
+//! - Normalised Moreau-Broto, Moran and Geary autocorrelation, per property
+//!
+//! Property values are standardised across the 16 dinucleotides before use.
+use crate::stats::pseknc::{dinucleotide_steps, PropertyTable};
+use anyhow::{anyhow, Result};
+use std::str::FromStr;
+
+/// The autocorrelation descriptors available
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Autocorrelation {
+    Dac,
+    Dcc,
+    Dacc,
+    MoreauBroto,
+    Moran,
+    Geary,
+}
+
+impl Autocorrelation {
+    /// The short name used as a column prefix and on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            Autocorrelation::Dac => "dac",
+            Autocorrelation::Dcc => "dcc",
+            Autocorrelation::Dacc => "dacc",
+            Autocorrelation::MoreauBroto => "moreau_broto",
+            Autocorrelation::Moran => "moran",
+            Autocorrelation::Geary => "geary",
+        }
+    }
+}
+
+impl FromStr for Autocorrelation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        [
+            Autocorrelation::Dac,
+            Autocorrelation::Dcc,
+            Autocorrelation::Dacc,
+            Autocorrelation::MoreauBroto,
+            Autocorrelation::Moran,
+            Autocorrelation::Geary,
+        ]
+        .into_iter()
+        .find(|x| x.name() == s.to_lowercase())
+        .ok_or_else(|| anyhow!("Unknown autocorrelation descriptor {}", s))
+    }
+}
+
+/// Get the standardised property series along the sequence
+///
+/// Steps that touch a non nucleotide character are `None`, so the steps
+/// either side of one stay as far apart as they are in the sequence.
+fn property_series(
+    clean_seq: &str,
+    table: &PropertyTable,
+    max_lag: usize,
+) -> Result<Vec<Vec<Option<f64>>>> {
+    let steps = dinucleotide_steps(clean_seq);
+    // Every lag needs at least one pair of steps to average over
+    let too_short = (1..=max_lag).any(|lag| {
+        steps
+            .iter()
+            .zip(steps.iter().skip(lag))
+            .all(|(x, y)| x.is_none() || y.is_none())
+    });
+    if steps.len() <= max_lag || too_short {
+        return Err(anyhow!(
+            "Sequence of length {} is too short for lag {}",
+            clean_seq.len(),
+            max_lag
+        ));
+    }
+
+    Ok(table
+        .standardised()
+        .values
+        .iter()
+        .map(|values| steps.iter().map(|x| x.map(|x| values[x])).collect())
+        .collect())
+}
+
+fn mean(series: &[Option<f64>]) -> f64 {
+    let values: Vec<f64> = series.iter().flatten().copied().collect();
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// Pairs of values `lag` steps apart, skipping any step that's missing
+fn lagged_pairs<'a>(
+    first: &'a [Option<f64>],
+    second: &'a [Option<f64>],
+    lag: usize,
+) -> impl Iterator<Item = (f64, f64)> + 'a {
+    first
+        .iter()
+        .zip(second.iter().skip(lag))
+        .filter_map(|(x, y)| Some(((*x)?, (*y)?)))
+}
+
+/// Mean of a function of each pair of values `lag` steps apart
+fn lagged_mean<F: Fn(f64, f64) -> f64>(
+    first: &[Option<f64>],
+    second: &[Option<f64>],
+    lag: usize,
+    f: F,
+) -> f64 {
+    let (sum, count) = lagged_pairs(first, second, lag)
+        .fold((0.0, 0), |(sum, count), (x, y)| (sum + f(x, y), count + 1));
+    sum / count as f64
+}
+
+/// Covariance between one series and another shifted by `lag`
+fn lagged_covariance(first: &[Option<f64>], second: &[Option<f64>], lag: usize) -> f64 {
+    let (mean_first, mean_second) = (mean(first), mean(second));
+    lagged_mean(first, second, lag, |x, y| {
+        (x - mean_first) * (y - mean_second)
+    })
+}
+
+/// Dinucleotide auto covariance, grouped by lag then property
+pub fn dac(clean_seq: &str, table: &PropertyTable, max_lag: usize) -> Result<Vec<f64>> {
+    let series = property_series(clean_seq, table, max_lag)?;
+    let mut values = Vec::with_capacity(max_lag * series.len());
+    for lag in 1..=max_lag {
+        for property in &series {
+            values.push(lagged_covariance(property, property, lag));
+        }
+    }
+    Ok(values)
+}
+
+/// Dinucleotide cross covariance, grouped by lag then property pair
+pub fn dcc(clean_seq: &str, table: &PropertyTable, max_lag: usize) -> Result<Vec<f64>> {
+    let series = property_series(clean_seq, table, max_lag)?;
+    let mut values = Vec::with_capacity(max_lag * series.len() * series.len());
+    for lag in 1..=max_lag {
+        for (first_idx, first) in series.iter().enumerate() {
+            for (second_idx, second) in series.iter().enumerate() {
+                if first_idx != second_idx {
+                    values.push(lagged_covariance(first, second, lag));
+                }
+            }
+        }
+    }
+    Ok(values)
+}
+
+/// Normalised Moreau-Broto autocorrelation, grouped by lag then property
+pub fn moreau_broto(clean_seq: &str, table: &PropertyTable, max_lag: usize) -> Result<Vec<f64>> {
+    let series = property_series(clean_seq, table, max_lag)?;
+    let mut values = Vec::with_capacity(max_lag * series.len());
+    for lag in 1..=max_lag {
+        for property in &series {
+            values.push(lagged_mean(property, property, lag, |x, y| x * y));
+        }
+    }
+    Ok(values)
+}
+
+/// Moran autocorrelation, grouped by lag then property
+///
+/// A constant property series has no variance to correlate, and gives zero.
+pub fn moran(clean_seq: &str, table: &PropertyTable, max_lag: usize) -> Result<Vec<f64>> {
+    let series = property_series(clean_seq, table, max_lag)?;
+    let mut values = Vec::with_capacity(max_lag * series.len());
+    for lag in 1..=max_lag {
+        for property in &series {
+            let variance = lagged_covariance(property, property, 0);
+            if variance > 0.0 {
+                values.push(lagged_covariance(property, property, lag) / variance);
+            } else {
+                values.push(0.0);
+            }
+        }
+    }
+    Ok(values)
+}
+
+/// Geary autocorrelation, grouped by lag then property
+///
+/// A constant property series has no variance to correlate, and gives zero.
+pub fn geary(clean_seq: &str, table: &PropertyTable, max_lag: usize) -> Result<Vec<f64>> {
+    let series = property_series(clean_seq, table, max_lag)?;
+    let mut values = Vec::with_capacity(max_lag * series.len());
+    for lag in 1..=max_lag {
+        for property in &series {
+            let len = property.iter().flatten().count() as f64;
+            let property_mean = mean(property);
+            let sum_squares: f64 = property
+                .iter()
+                .flatten()
+                .map(|x| (x - property_mean).powi(2))
+                .sum();
+            let mean_difference = lagged_mean(property, property, lag, |x, y| (x - y).powi(2));
+            if sum_squares > 0.0 {
+                values.push((len - 1.0) / 2.0 * mean_difference / sum_squares);
+            } else {
+                values.push(0.0);
+            }
+        }
+    }
+    Ok(values)
+}
+
+/// Calculate any of the autocorrelation descriptors
+///
+/// Errors if the sequence doesn't have more dinucleotide steps than
+/// `max_lag`.
+pub fn autocorrelation(
+    clean_seq: &str,
+    table: &PropertyTable,
+    max_lag: usize,
+    kind: Autocorrelation,
+) -> Result<Vec<f64>> {
+    match kind {
+        Autocorrelation::Dac => dac(clean_seq, table, max_lag),
+        Autocorrelation::Dcc => dcc(clean_seq, table, max_lag),
+        Autocorrelation::Dacc => {
+            let mut values = dac(clean_seq, table, max_lag)?;
+            values.extend(dcc(clean_seq, table, max_lag)?);
+            Ok(values)
+        }
+        Autocorrelation::MoreauBroto => moreau_broto(clean_seq, table, max_lag),
+        Autocorrelation::Moran => moran(clean_seq, table, max_lag),
+        Autocorrelation::Geary => geary(clean_seq, table, max_lag),
+    }
+}
+
+/// Get the column names for `autocorrelation`, in output order
+///
+/// Per property descriptors are named like `moran_lag2_twist`, and cross
+/// covariances like `dcc_lag2_twist_roll`. Both parts of DACC are prefixed
+/// `dacc_`, so its columns don't clash with DAC's or DCC's.
+pub fn autocorrelation_names(
+    table: &PropertyTable,
+    max_lag: usize,
+    kind: Autocorrelation,
+) -> Vec<String> {
+    let per_property = |prefix: &str| {
+        let mut names = Vec::new();
+        for lag in 1..=max_lag {
+            for property in &table.names {
+                names.push(format!("{}_lag{}_{}", prefix, lag, property));
+            }
+        }
+        names
+    };
+    let cross_property = |prefix: &str| {
+        let mut names = Vec::new();
+        for lag in 1..=max_lag {
+            for first in &table.names {
+                for second in table.names.iter().filter(|x| *x != first) {
+                    names.push(format!("{}_lag{}_{}_{}", prefix, lag, first, second));
+                }
+            }
+        }
+        names
+    };
+
+    match kind {
+        Autocorrelation::Dcc => cross_property("dcc"),
+        Autocorrelation::Dacc => {
+            let mut names = per_property("dacc");
+            names.extend(cross_property("dacc"));
+            names
+        }
+        _ => per_property(kind.name()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::stats::autocorrelation::{self, Autocorrelation};
+    use crate::stats::pseknc::PropertyTable;
+
+    const SEQ: &str = "GGCAUGGAGUCCUGUGGUAUCCACGAGAUCACCUUCAACUCCAUCAUGAAGUGUGAUGUGG";
+
+    #[test]
+    fn test_output_lengths_match_names() {
+        let table = PropertyTable::rna();
+        for kind in [
+            Autocorrelation::Dac,
+            Autocorrelation::Dcc,
+            Autocorrelation::Dacc,
+            Autocorrelation::MoreauBroto,
+            Autocorrelation::Moran,
+            Autocorrelation::Geary,
+        ] {
+            let values = autocorrelation::autocorrelation(SEQ, &table, 3, kind).unwrap();
+            let names = autocorrelation::autocorrelation_names(&table, 3, kind);
+            assert_eq!(values.len(), names.len(), "{:?}", kind);
+            assert!(values.iter().all(|x| x.is_finite()));
+        }
+        assert_eq!(
+            autocorrelation::autocorrelation_names(&table, 2, Autocorrelation::Dcc).len(),
+            2 * 7 * 6
+        );
+    }
+
+    #[test]
+    fn test_dacc_names_distinct() {
+        let table = PropertyTable::rna();
+        let mut names = Vec::new();
+        for kind in [
+            Autocorrelation::Dac,
+            Autocorrelation::Dcc,
+            Autocorrelation::Dacc,
+        ] {
+            names.extend(autocorrelation::autocorrelation_names(&table, 2, kind));
+        }
+        let count = names.len();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), count);
+        assert_eq!(
+            autocorrelation::autocorrelation_names(&table, 1, Autocorrelation::Dacc)[0],
+            "dacc_lag1_twist"
+        );
+    }
+
+    #[test]
+    fn test_alternating_sequence() {
+        // AUAUAU... alternates between the AU and UA steps, so at odd lags
+        // the series is perfectly anticorrelated and at even lags perfectly
+        // correlated
+        let table = PropertyTable::rna().select(&["twist"]).unwrap();
+        let seq = "AUAUAUAUAUAUAUAUAUAUA";
+
+        let moran = autocorrelation::moran(seq, &table, 2).unwrap();
+        assert!((moran[0] + 1.0).abs() < 1e-12);
+        assert!((moran[1] - 1.0).abs() < 1e-12);
+
+        let geary = autocorrelation::geary(seq, &table, 2).unwrap();
+        assert!(geary[0] > 1.0);
+        assert_eq!(geary[1], 0.0);
+    }
+
+    #[test]
+    fn test_parse_kind() {
+        assert_eq!(
+            "Moran".parse::<Autocorrelation>().unwrap(),
+            Autocorrelation::Moran
+        );
+        assert!("nope".parse::<Autocorrelation>().is_err());
+    }
+
+    #[test]
+    fn test_too_short() {
+        let table = PropertyTable::rna();
+        assert!(autocorrelation::dac("ACG", &table, 2).is_err());
+        // No pair of steps one apart is free of the N
+        assert!(autocorrelation::dac("ACNGU", &table, 1).is_err());
+    }
+
+    #[test]
+    fn test_unknown_base() {
+        // Steps either side of the N aren't neighbours, so every lag 1 pair
+        // is AU next to UA as it is without the N, rather than the AU steps
+        // either side of it
+        let table = PropertyTable::rna().select(&["twist"]).unwrap();
+        let half = "AUAUAUAUAU";
+        let whole = format!("{}N{}", half, half);
+        let expected = autocorrelation::moreau_broto(half, &table, 1).unwrap();
+        let values = autocorrelation::moreau_broto(&whole, &table, 1).unwrap();
+        assert!((values[0] - expected[0]).abs() < 1e-12);
+    }
+}
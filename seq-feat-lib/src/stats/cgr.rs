@@ -0,0 +1,165 @@
+//! Chaos game representation (CGR) and frequency CGR (FCGR)
+//!
+//! The chaos game places each base at a corner of the unit square, with A at
+//! `(0, 0)`, C at `(0, 1)`, G at `(1, 1)` and U at `(1, 0)`. Starting from
+//! the centre, each base in turn moves the current point halfway towards its
+//! corner. Every point encodes the whole sequence before it, with the most
+//! recent bases deciding the coarsest position.
+//!
+//! Dividing the square into a `2^k` by `2^k` grid, every cell holds the
+//! points that end a particular kmer, so counting points per cell is the
+//! same as counting kmers. That grid of counts is the FCGR, a fixed size
+//! image for any length of sequence.
+use crate::stats::composition::{base_index, kmer_counts, C, G, OTHER, U};
+use std::io::{self, Write};
+use wasm_bindgen::prelude::*;
+
+/// Corner of the unit square for each base, in counter index order
+const CORNERS: [(f64, f64); 4] = [(0.0, 0.0), (0.0, 1.0), (1.0, 1.0), (1.0, 0.0)];
+
+/// Get the CGR trajectory as a flat array
+///
+/// Values are interleaved as `x, y` for each base, so the output is twice the
+/// length of the sequence. Characters that aren't nucleotides leave the
+/// point where it is.
+#[wasm_bindgen(js_name=cgr)]
+pub fn trajectory(clean_seq: &str) -> Vec<f64> {
+    let mut point = (0.5, 0.5);
+    let mut points = Vec::with_capacity(2 * clean_seq.len());
+
+    for byte in clean_seq.bytes() {
+        let base = base_index(byte);
+        if base != OTHER {
+            let corner = CORNERS[base];
+            point = ((point.0 + corner.0) / 2.0, (point.1 + corner.1) / 2.0);
+        }
+        points.push(point.0);
+        points.push(point.1);
+    }
+
+    points
+}
+
+/// Get the FCGR cell of a kmer, from its `kmer_counts` index
+///
+/// Returns the row counting down from the top of the image (high y) and the
+/// column counting from the left. The last base of the kmer picks the
+/// quadrant, so it gives the most significant bit of each coordinate.
+fn cell(index: usize, k: usize) -> (usize, usize) {
+    let (mut row, mut column) = (0, 0);
+    for position in 0..k {
+        // Digit 0 is the last base of the kmer
+        let base = (index >> (2 * position)) & 3;
+        let bit = 1 << (k - 1 - position);
+        if base == G || base == U {
+            column |= bit;
+        }
+        if base == C || base == G {
+            row |= bit;
+        }
+    }
+    ((1 << k) - 1 - row, column)
+}
+
+/// Calculate the FCGR counts at resolution `2^k` by `2^k`
+///
+/// Cells are in row major order with the first row at the top of the image,
+/// so the CGR corners end up where they are drawn: C top left, G top right,
+/// A bottom left and U bottom right. The grid has 4^k cells, so keep k
+/// small.
+pub fn fcgr(clean_seq: &str, k: usize) -> Vec<u64> {
+    let side = 1 << k;
+    let mut grid = vec![0u64; side * side];
+    for (index, count) in kmer_counts(clean_seq, k).into_iter().enumerate() {
+        let (row, column) = cell(index, k);
+        grid[row * side + column] = count;
+    }
+    grid
+}
+
+/// Calculate the FCGR as kmer frequencies, which sum to one
+///
+/// A sequence with no kmers gives all zeros.
+pub fn fcgr_frequencies(clean_seq: &str, k: usize) -> Vec<f64> {
+    frequencies(&fcgr(clean_seq, k))
+}
+
+/// Normalise FCGR counts to frequencies, see `fcgr_frequencies`
+pub fn frequencies(grid: &[u64]) -> Vec<f64> {
+    let total = grid.iter().sum::<u64>() as f64;
+    grid.iter()
+        .map(|x| if total > 0.0 { *x as f64 / total } else { 0.0 })
+        .collect()
+}
+
+/// Scale FCGR counts to 8 bit grey levels
+///
+/// The most frequent kmer is black and absent kmers are white, the usual way
+/// CGR images are drawn.
+pub fn fcgr_pixels(grid: &[u64]) -> Vec<u8> {
+    let max = grid.iter().max().copied().unwrap_or(0).max(1) as f64;
+    grid.iter()
+        .map(|x| 255 - (255.0 * *x as f64 / max).round() as u8)
+        .collect()
+}
+
+/// Write FCGR counts as a binary PGM image
+pub fn write_pgm<W: Write>(writer: &mut W, grid: &[u64], k: usize) -> io::Result<()> {
+    let side = 1 << k;
+    write!(writer, "P5\n{} {}\n255\n", side, side)?;
+    writer.write_all(&fcgr_pixels(grid))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::stats::cgr;
+    use crate::stats::composition;
+
+    #[test]
+    fn test_trajectory() {
+        assert_eq!(
+            cgr::trajectory("ANG"),
+            [0.25, 0.25, 0.25, 0.25, 0.625, 0.625]
+        );
+    }
+
+    #[test]
+    fn test_corners() {
+        // Single bases land in the quadrant of their corner
+        let grid = cgr::fcgr("ACGU", 1);
+        assert_eq!(grid, [1, 1, 1, 1]);
+        let c_only = cgr::fcgr("CC", 1);
+        assert_eq!(c_only, [2, 0, 0, 0]);
+        let u_only = cgr::fcgr("U", 1);
+        assert_eq!(u_only, [0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn test_fcgr_matches_trajectory() {
+        // The cell each point falls in is the cell of the kmer it ends
+        let seq = "GGCAUGGAGUCCUGUGGUAUCCACGAGAUCACC";
+        let k = 3;
+        let side = 1 << k;
+        let points = cgr::trajectory(seq);
+        let mut grid = vec![0u64; side * side];
+        for point in points.chunks_exact(2).skip(k - 1) {
+            let column = (point[0] * side as f64) as usize;
+            let row = side - 1 - (point[1] * side as f64) as usize;
+            grid[row * side + column] += 1;
+        }
+
+        assert_eq!(cgr::fcgr(seq, k), grid);
+        assert_eq!(
+            grid.iter().sum::<u64>(),
+            composition::kmer_counts(seq, k).iter().sum::<u64>()
+        );
+    }
+
+    #[test]
+    fn test_pgm() {
+        let mut image = Vec::new();
+        cgr::write_pgm(&mut image, &cgr::fcgr("CCG", 1), 1).unwrap();
+        assert_eq!(&image[..11], b"P5\n2 2\n255\n");
+        assert_eq!(&image[11..], [0, 127, 255, 255]);
+    }
+}
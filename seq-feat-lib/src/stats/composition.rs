@@ -0,0 +1,302 @@
+//! Single pass nucleotide composition kernel
+//!
+//! Most of the simple statistics in this crate boil down to counting bases,
+//! either over the whole sequence, split by codon position (phase), or in
+//! sliding windows. Rather than rescanning the string once per base and per
+//! phase, everything here walks the bytes exactly once and uses a 256 entry
+//! lookup table to turn each byte into a counter index, which keeps the inner
+//! loop branch free.
+
+/// Counter index for A
+pub const A: usize = 0;
+/// Counter index for C
+pub const C: usize = 1;
+/// Counter index for G
+pub const G: usize = 2;
+/// Counter index for U (T is folded into U)
+pub const U: usize = 3;
+/// Counter index for anything that isn't a nucleotide
+pub const OTHER: usize = 4;
+
+/// The bases in counter index order
+pub const BASES: [char; 4] = ['A', 'C', 'G', 'U'];
+
+const fn build_base_index() -> [u8; 256] {
+    let mut table = [OTHER as u8; 256];
+    table[b'A' as usize] = A as u8;
+    table[b'C' as usize] = C as u8;
+    table[b'G' as usize] = G as u8;
+    table[b'U' as usize] = U as u8;
+    table[b'T' as usize] = U as u8;
+    table
+}
+
+/// Lookup table from a byte to its counter index
+pub static BASE_INDEX: [u8; 256] = build_base_index();
+
+/// Get the counter index of a single byte
+#[inline(always)]
+pub fn base_index(byte: u8) -> usize {
+    BASE_INDEX[byte as usize] as usize
+}
+
+/// Total and per-phase base counts for a sequence
+///
+/// Counts are stored in `[A, C, G, U]` order, see the index constants in
+/// this module. Phase `p` counts the bases at positions `p, p + 3, p + 6...`
+/// so the three phases together add up to the total.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Composition {
+    /// Length of the sequence in bytes
+    pub length: usize,
+    /// Base counts over the whole sequence
+    pub total: [u64; 4],
+    /// Base counts for each of the three codon positions
+    pub phased: [[u64; 4]; 3],
+}
+
+impl Composition {
+    /// Count the bases in a sequence in a single pass
+    ///
+    /// Characters that are not A, C, G, U or T are skipped, but still count
+    /// towards the length and the phase of the following bases.
+    pub fn new(seq: &str) -> Composition {
+        let bytes = seq.as_bytes();
+        // One spare slot per phase soaks up the non nucleotide characters so
+        // the loop doesn't need to branch on them
+        let mut phased = [[0u64; 5]; 3];
+
+        let mut codons = bytes.chunks_exact(3);
+        for codon in &mut codons {
+            phased[0][base_index(codon[0])] += 1;
+            phased[1][base_index(codon[1])] += 1;
+            phased[2][base_index(codon[2])] += 1;
+        }
+        for (phase, byte) in codons.remainder().iter().enumerate() {
+            phased[phase][base_index(*byte)] += 1;
+        }
+
+        let mut composition = Composition {
+            length: bytes.len(),
+            ..Default::default()
+        };
+        for (phase, counts) in phased.iter().enumerate() {
+            composition.phased[phase].copy_from_slice(&counts[..4]);
+            for (total, count) in composition.total.iter_mut().zip(counts) {
+                *total += count;
+            }
+        }
+
+        composition
+    }
+
+    /// Number of a given base over the whole sequence
+    pub fn count(&self, base: usize) -> f64 {
+        self.total[base] as f64
+    }
+
+    /// Number of a given base at a given phase
+    pub fn phased_count(&self, phase: usize, base: usize) -> f64 {
+        self.phased[phase][base] as f64
+    }
+
+    /// Fraction of the sequence that is G or C
+    pub fn gc_content(&self) -> f64 {
+        (self.total[G] + self.total[C]) as f64 / self.length as f64
+    }
+}
+
+/// Count bases in sliding windows along a sequence
+///
+/// Windows of `window` bases are taken every `step` bases, starting from the
+/// first base. Counts are kept as a running total, adding the bases entering
+/// the window and removing those leaving it, so the sequence is still only
+/// read once however much the windows overlap. A sequence shorter than the
+/// window gives no windows.
+pub fn windowed(seq: &str, window: usize, step: usize) -> Vec<[u64; 4]> {
+    let bytes = seq.as_bytes();
+    if window == 0 || step == 0 || bytes.len() < window {
+        return Vec::new();
+    }
+
+    let mut windows = Vec::with_capacity((bytes.len() - window) / step + 1);
+    let mut running = [0u64; 5];
+    for byte in &bytes[..window] {
+        running[base_index(*byte)] += 1;
+    }
+
+    let mut start = 0;
+    loop {
+        windows.push([running[A], running[C], running[G], running[U]]);
+        if start + step + window > bytes.len() {
+            break;
+        }
+        if step < window {
+            for byte in &bytes[start..start + step] {
+                running[base_index(*byte)] -= 1;
+            }
+            for byte in &bytes[start + window..start + window + step] {
+                running[base_index(*byte)] += 1;
+            }
+        } else {
+            running = [0; 5];
+            for byte in &bytes[start + step..start + step + window] {
+                running[base_index(*byte)] += 1;
+            }
+        }
+        start += step;
+    }
+
+    windows
+}
+
+/// Count overlapping kmers, indexed by their base 4 encoding
+///
+/// Each kmer maps to an index in `0..4^k` by reading its bases as base 4
+/// digits in `[A, C, G, U]` order, so `AA..A` is 0 and `UU..U` is `4^k - 1`.
+/// The index is rolled along the sequence so each base is only looked at
+/// once. Kmers that overlap a non nucleotide character are skipped.
+pub fn kmer_counts(seq: &str, k: usize) -> Vec<u64> {
+    let mut counts = vec![0u64; 1 << (2 * k)];
+    if k == 0 {
+        return counts;
+    }
+
+    let mask = (1usize << (2 * k)) - 1;
+    let mut index = 0usize;
+    let mut valid = 0usize;
+    for byte in seq.bytes() {
+        let base = base_index(byte);
+        if base == OTHER {
+            valid = 0;
+            continue;
+        }
+        index = ((index << 2) | base) & mask;
+        valid += 1;
+        if valid >= k {
+            counts[index] += 1;
+        }
+    }
+
+    counts
+}
+
+/// Count overlapping kmers separately by the phase of their first base
+///
+/// The same as `kmer_counts`, except that a kmer starting at position `i`
+/// is counted in phase `i % 3`, so the three phases add up to
+/// `kmer_counts(seq, k)`.
+pub fn phased_kmer_counts(seq: &str, k: usize) -> [Vec<u64>; 3] {
+    let mut counts = [
+        vec![0u64; 1 << (2 * k)],
+        vec![0u64; 1 << (2 * k)],
+        vec![0u64; 1 << (2 * k)],
+    ];
+    if k == 0 {
+        return counts;
+    }
+
+    let mask = (1usize << (2 * k)) - 1;
+    let mut index = 0usize;
+    let mut valid = 0usize;
+    for (position, byte) in seq.bytes().enumerate() {
+        let base = base_index(byte);
+        if base == OTHER {
+            valid = 0;
+            continue;
+        }
+        index = ((index << 2) | base) & mask;
+        valid += 1;
+        if valid >= k {
+            counts[(position + 1 - k) % 3][index] += 1;
+        }
+    }
+
+    counts
+}
+
+/// Get the kmer strings in the same order as `kmer_counts`
+pub fn kmer_names(k: usize) -> Vec<String> {
+    (0..1usize << (2 * k))
+        .map(|index| {
+            (0..k)
+                .rev()
+                .map(|digit| BASES[(index >> (2 * digit)) & 3])
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::stats::composition::{self, Composition};
+
+    #[test]
+    fn test_composition_counts() {
+        let comp = Composition::new("AUGGCCAUUGUA");
+
+        assert_eq!(comp.length, 12);
+        assert_eq!(comp.total, [3, 2, 3, 4]);
+        assert_eq!(comp.phased[0], [2, 0, 2, 0]);
+        assert_eq!(comp.phased[1], [0, 1, 0, 3]);
+        assert_eq!(comp.phased[2], [1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn test_composition_remainder() {
+        // Length not divisible by three, with a character we don't count
+        let comp = Composition::new("ACGUNAC");
+
+        assert_eq!(comp.length, 7);
+        assert_eq!(comp.total, [2, 2, 1, 1]);
+        assert_eq!(comp.phased[0], [1, 1, 0, 1]);
+        assert_eq!(comp.phased[1], [0, 1, 0, 0]);
+        assert_eq!(comp.phased[2], [1, 0, 1, 0]);
+    }
+
+    #[test]
+    fn test_windowed_counts() {
+        let seq = "AACCGGUU";
+
+        assert_eq!(
+            composition::windowed(seq, 4, 2),
+            vec![[2, 2, 0, 0], [0, 2, 2, 0], [0, 0, 2, 2]]
+        );
+        assert_eq!(
+            composition::windowed(seq, 2, 3),
+            vec![[2, 0, 0, 0], [0, 1, 1, 0], [0, 0, 0, 2]]
+        );
+        assert!(composition::windowed(seq, 9, 1).is_empty());
+    }
+
+    #[test]
+    fn test_kmer_counts() {
+        let names = composition::kmer_names(2);
+        assert_eq!(names.len(), 16);
+        assert_eq!(names[0], "AA");
+        assert_eq!(names[7], "CU");
+        assert_eq!(names[15], "UU");
+
+        let counts = composition::kmer_counts("AACNCU", 2);
+        assert_eq!(counts.iter().sum::<u64>(), 3);
+        assert_eq!(counts[0], 1);
+        assert_eq!(counts[1], 1);
+        assert_eq!(counts[7], 1);
+    }
+
+    #[test]
+    fn test_phased_kmer_counts() {
+        let seq = "AACNCUAAG";
+        let phased = composition::phased_kmer_counts(seq, 2);
+        let total = composition::kmer_counts(seq, 2);
+
+        // AA starts at 0 and 6, both phase 0, and UA at 5
+        assert_eq!(phased[0][0], 2);
+        assert_eq!(phased[2][12], 1);
+        // CU starts at 4
+        assert_eq!(phased[1][7], 1);
+        for (idx, count) in total.iter().enumerate() {
+            assert_eq!(phased.iter().map(|x| x[idx]).sum::<u64>(), *count);
+        }
+    }
+}
@@ -1,66 +1,88 @@
+use crate::stats::composition::{Composition, A, C, G, U};
 use wasm_bindgen::prelude::*;
 
 /// Get the fraction of the sequence that is either a G or C
 ///
 /// GC content is a fairly well established feature.
-/// This function simply counts the ocurrences of each
-/// character and divides by the total sequence length.
+/// This function counts the ocurrences of each character in a
+/// single pass and divides by the total sequence length.
 #[wasm_bindgen(js_name=gc_content)]
 pub fn gc_content(clean_seq: &str) -> f64 {
-    let num_c = c_content(clean_seq);
-    let num_g = g_content(clean_seq);
-
-    (num_c + num_g) / clean_seq.len() as f64
+    Composition::new(clean_seq).gc_content()
 }
 
 /// Calculate the number of A nucleotides in a sequence
 pub fn a_content(clean_seq: &str) -> f64 {
-    clean_seq.matches('A').count() as f64
+    Composition::new(clean_seq).count(A)
 }
 
 /// Calculate the number of C nucleotides in a sequence
 pub fn c_content(clean_seq: &str) -> f64 {
-    clean_seq.matches('C').count() as f64
+    Composition::new(clean_seq).count(C)
 }
 
 /// Calculate the number of G nucleotides in a sequence
 pub fn g_content(clean_seq: &str) -> f64 {
-    clean_seq.matches('G').count() as f64
+    Composition::new(clean_seq).count(G)
 }
 
 /// Calculate the number of U nucleotides in a sequence
 pub fn u_content(clean_seq: &str) -> f64 {
-    clean_seq.matches('U').count() as f64
+    Composition::new(clean_seq).count(U)
 }
 
 // Get nucleotide counts at given phase
 pub fn get_phased_counts(seq: &str, phase: usize) -> (f64, f64, f64, f64) {
-    let phase_a: f64 = seq
-        .chars()
-        .skip(phase)
-        .step_by(3)
-        .map(|x| (x == 'A') as i64 as f64)
-        .sum();
-    let phase_c: f64 = seq
-        .chars()
-        .skip(phase)
-        .step_by(3)
-        .map(|x| (x == 'C') as i64 as f64)
-        .sum();
-    let phase_g: f64 = seq
-        .chars()
-        .skip(phase)
-        .step_by(3)
-        .map(|x| (x == 'G') as i64 as f64)
-        .sum();
-    let phase_u: f64 = seq
-        .chars()
-        .skip(phase)
-        .step_by(3)
-        .map(|x| (x == 'U') as i64 as f64)
-        .sum();
-
-    (phase_a, phase_c, phase_g, phase_u)
+    let comp = Composition::new(seq);
+
+    (
+        comp.phased_count(phase, A),
+        comp.phased_count(phase, C),
+        comp.phased_count(phase, G),
+        comp.phased_count(phase, U),
+    )
+}
+
+/// Column names for `codon_position_content`, in output order
+///
+/// These are used as-is for the output columns, so don't change them
+/// without good reason; trained models depend on them.
+pub const CODON_POSITION_NAMES: [&str; 15] = [
+    "gc1", "gc2", "gc3", "a1", "c1", "g1", "u1", "a2", "c2", "g2", "u2", "a3", "c3", "g3", "u3",
+];
+
+/// Get the base composition at each codon position
+///
+/// The sequence is read in frame from the first base, so position 1 is
+/// bases 0, 3, 6... and so on. To look at a particular reading frame, e.g.
+/// the longest ORF from `coding::orf::longest`, slice the sequence first.
+///
+/// Returns GC1, GC2 and GC3 followed by the A, C, G and U fractions at each
+/// position in turn, matching `CODON_POSITION_NAMES`. Every value is a
+/// fraction of the number of bases at that codon position, and positions
+/// with no bases, as in sequences shorter than 3, give zeros.
+pub fn codon_position_content(clean_seq: &str) -> Vec<f64> {
+    let comp = Composition::new(clean_seq);
+    let mut content = Vec::with_capacity(CODON_POSITION_NAMES.len());
+
+    let position_totals: Vec<f64> = comp
+        .phased
+        .iter()
+        .map(|counts| counts.iter().sum::<u64>() as f64)
+        // Counts at an empty position are all zero, so this leaves them zero
+        .map(|total| total.max(1.0))
+        .collect();
+
+    for (phase, total) in position_totals.iter().enumerate() {
+        content.push((comp.phased_count(phase, G) + comp.phased_count(phase, C)) / total);
+    }
+    for (phase, total) in position_totals.iter().enumerate() {
+        for base in [A, C, G, U] {
+            content.push(comp.phased_count(phase, base) / total);
+        }
+    }
+
+    content
 }
 
 #[cfg(test)]
@@ -71,4 +93,23 @@ mod test {
         assert_eq!(counts::gc_content("GGGGGGCCCCCC"), 1.0);
         assert_eq!(counts::gc_content("GGGGGGAAAAAA"), 0.5);
     }
+
+    #[test]
+    fn test_codon_position_content() {
+        let content = counts::codon_position_content("AUGGCCAUUGUA");
+
+        assert_eq!(content.len(), counts::CODON_POSITION_NAMES.len());
+        assert_eq!(content[..3], [0.5, 0.25, 0.5]);
+        // A, C, G, U at the third position
+        assert_eq!(content[11..], [0.25, 0.25, 0.25, 0.25]);
+    }
+
+    #[test]
+    fn test_codon_position_content_short() {
+        let content = counts::codon_position_content("GA");
+        assert_eq!(content[..3], [1.0, 0.0, 0.0]);
+        assert_eq!(content[7..11], [1.0, 0.0, 0.0, 0.0]);
+        assert_eq!(content[11..], [0.0; 4]);
+        assert_eq!(counts::codon_position_content(""), [0.0; 15]);
+    }
 }
@@ -1,6 +1,9 @@
-//! Functions for the calculation of entropy in sequences
+//! Functions for the calculation of entropy in sequences, and the kmer
+//! composition they build on
+use crate::stats::composition::{base_index, BASES, OTHER};
 use itertools::Itertools;
 use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
 
 /// Get the probability distributionn of kmers of given length
 ///
@@ -31,6 +34,18 @@ pub fn kmer_probabilities(seq: &str, kmer: u64) -> HashMap<String, f64> {
     kmer_probs
 }
 
+/// The probabilities of a distribution in order of their kmers
+///
+/// Summing in hash map order changes the last bits of the entropies from run
+/// to run, so sums go through this to stay reproducible.
+fn sorted_probabilities(kmer_probs: &HashMap<String, f64>) -> Vec<f64> {
+    kmer_probs
+        .iter()
+        .sorted_by(|x, y| x.0.cmp(y.0))
+        .map(|x| *x.1)
+        .collect()
+}
+
 /// Calculate the Shannon entropy for a sequence, using given kmer length range
 ///
 /// This function will repeatedly calculate the kmer probability distribution
@@ -44,9 +59,9 @@ pub fn shannon(seq: &str, kmer_max: u64) -> Vec<f64> {
 
     for kmer in 1..kmer_max + 1 {
         let kmer_probs = kmer_probabilities(seq, kmer);
-        let entropy = -kmer_probs
+        let entropy = -sorted_probabilities(&kmer_probs)
             .iter()
-            .fold(0.0, |acc, x| acc + (x.1 * x.1.log2()));
+            .fold(0.0, |acc, x| acc + (x * x.log2()));
         entropy_list.push(entropy);
     }
 
@@ -59,22 +74,84 @@ pub fn shannon(seq: &str, kmer_max: u64) -> Vec<f64> {
 /// and then calculate the tsallis entropy from it. You can end up with a
 /// number of entropies in the resulting Vec<f64>
 ///
+/// This uses q = 2, as MathFeature does, see `tsallis_q` for other values.
+///
 /// Bear in mind that the kmer_max parameter will have a strong impact on
 /// the runtime of this function, probably factorial.
 pub fn tsallis(seq: &str, kmer_max: u64) -> Vec<f64> {
+    tsallis_q(seq, kmer_max, 2.0)
+}
+
+/// Calculate the Tsallis entropy for a sequence with entropic index `q`
+///
+/// As q approaches 1 this tends to the Shannon entropy in nats, so q must
+/// not be exactly 1.
+pub fn tsallis_q(seq: &str, kmer_max: u64, q: f64) -> Vec<f64> {
     let mut entropy_list: Vec<f64> = Vec::new();
-    let q = 2.0;
 
     for kmer in 1..kmer_max + 1 {
         let kmer_probs = kmer_probabilities(seq, kmer);
-        let entropy =
-            (1.0 / (q - 1.0)) * (1.0 - kmer_probs.iter().fold(0.0, |acc, x| acc + (x.1.powf(q))));
+        let entropy = (1.0 / (q - 1.0))
+            * (1.0
+                - sorted_probabilities(&kmer_probs)
+                    .iter()
+                    .fold(0.0, |acc, x| acc + (x.powf(q))));
         entropy_list.push(entropy);
     }
 
     entropy_list
 }
 
+/// Calculate the k-spaced nucleotide pair composition (CKSNAP)
+///
+/// CKSNAP counts pairs of bases separated by a gap of `g` other bases, for
+/// every gap from 0 up to `gap_max`. A gap of 0 gives the ordinary
+/// (overlapping) dinucleotide composition, while larger gaps pick up short
+/// range dependencies that contiguous kmers can't see.
+///
+/// For each gap, every pair `(s[i], s[i + gap + 1])` is counted and the 16
+/// counts are divided by the number of pairs at that gap. The result has 16
+/// values per gap, with the pairs in `AA, AC, ... UU` order, matching the
+/// names from `cksnap_names`. Gaps too long for the sequence give zeros.
+#[wasm_bindgen(js_name=cksnap)]
+pub fn cksnap(clean_seq: &str, gap_max: usize) -> Vec<f64> {
+    let indices: Vec<usize> = clean_seq.bytes().map(base_index).collect();
+    let mut composition = Vec::with_capacity(16 * (gap_max + 1));
+
+    for gap in 0..=gap_max {
+        let mut counts = [0.0; 16];
+        let mut total = 0.0;
+        for (first, second) in indices.iter().zip(indices.iter().skip(gap + 1)) {
+            if *first != OTHER && *second != OTHER {
+                counts[4 * first + second] += 1.0;
+            }
+            total += 1.0;
+        }
+        if total > 0.0 {
+            counts.iter_mut().for_each(|x| *x /= total);
+        }
+        composition.extend_from_slice(&counts);
+    }
+
+    composition
+}
+
+/// Get the column names for `cksnap`, in output order
+///
+/// Names look like `cksnap_g2_AU`, which is the fraction of A and U bases
+/// separated by two other bases.
+pub fn cksnap_names(gap_max: usize) -> Vec<String> {
+    let mut names = Vec::with_capacity(16 * (gap_max + 1));
+    for gap in 0..=gap_max {
+        for first in BASES {
+            for second in BASES {
+                names.push(format!("cksnap_g{}_{}{}", gap, first, second));
+            }
+        }
+    }
+    names
+}
+
 #[cfg(test)]
 mod test {
     use crate::stats::entropy;
@@ -94,4 +171,41 @@ mod test {
         let entropy = entropy::tsallis(seq, 1);
         assert_eq!(entropy[0], 0.7477052860018152);
     }
+
+    #[test]
+    fn test_tsallis_q() {
+        // Four equally likely bases: (1 - 4 * 0.25^q) / (q - 1)
+        let entropy = entropy::tsallis_q("ACGU", 1, 3.0);
+        assert!((entropy[0] - (1.0 - 4.0 / 64.0) / 2.0).abs() < 1e-12);
+        assert_eq!(
+            entropy::tsallis_q("ACGU", 1, 2.0),
+            entropy::tsallis("ACGU", 1)
+        );
+    }
+
+    #[test]
+    fn test_cksnap() {
+        let res = entropy::cksnap("AUAUAU", 1);
+        let names = entropy::cksnap_names(1);
+
+        assert_eq!(res.len(), 32);
+        assert_eq!(names.len(), 32);
+
+        let get = |name: &str| res[names.iter().position(|x| x == name).unwrap()];
+        // Gap 0: AU AU AU, UA UA
+        assert_eq!(get("cksnap_g0_AU"), 0.6);
+        assert_eq!(get("cksnap_g0_UA"), 0.4);
+        // Gap 1: AA AA, UU UU
+        assert_eq!(get("cksnap_g1_AA"), 0.5);
+        assert_eq!(get("cksnap_g1_UU"), 0.5);
+        assert_eq!(get("cksnap_g1_AU"), 0.0);
+    }
+
+    #[test]
+    fn test_cksnap_short_sequence() {
+        let res = entropy::cksnap("AC", 2);
+
+        assert_eq!(res[1], 1.0);
+        assert!(res[16..].iter().all(|x| *x == 0.0));
+    }
 }
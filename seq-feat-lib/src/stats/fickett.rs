@@ -1,4 +1,4 @@
-use crate::stats::counts::*;
+use crate::stats::composition::{Composition, A, C, G, U};
 use std::collections::HashMap;
 
 /*
@@ -92,14 +92,16 @@ fn lookup_position_prob(base: char, val: f64) -> f64 {
 pub fn score(seq: &str) -> f64 {
     let mut fickett_score: f64 = 0.0;
 
-    let (phase_0_a, phase_0_c, phase_0_g, phase_0_u) = get_phased_counts(seq, 0);
-    let (phase_1_a, phase_1_c, phase_1_g, phase_1_u) = get_phased_counts(seq, 1);
-    let (phase_2_a, phase_2_c, phase_2_g, phase_2_u) = get_phased_counts(seq, 2);
+    // One pass gives us both the phased and total counts
+    let comp = Composition::new(seq);
+    let [phase_0_a, phase_0_c, phase_0_g, phase_0_u] = comp.phased[0].map(|x| x as f64);
+    let [phase_1_a, phase_1_c, phase_1_g, phase_1_u] = comp.phased[1].map(|x| x as f64);
+    let [phase_2_a, phase_2_c, phase_2_g, phase_2_u] = comp.phased[2].map(|x| x as f64);
 
-    let a_content = (phase_0_a + phase_1_a + phase_2_a) / seq.len() as f64;
-    let c_content: f64 = (phase_0_c + phase_1_c + phase_2_c) / seq.len() as f64;
-    let g_content: f64 = (phase_0_g + phase_1_g + phase_2_g) / seq.len() as f64;
-    let u_content: f64 = (phase_0_u + phase_1_u + phase_2_u) / seq.len() as f64;
+    let a_content = comp.count(A) / seq.len() as f64;
+    let c_content: f64 = comp.count(C) / seq.len() as f64;
+    let g_content: f64 = comp.count(G) / seq.len() as f64;
+    let u_content: f64 = comp.count(U) / seq.len() as f64;
 
     let pos_a = [phase_0_a, phase_1_a, phase_2_a]
         .into_iter()
@@ -1,94 +1,478 @@
+use crate::stats::composition::{base_index, Composition, OTHER};
+use crate::stats::mapping::NumericMapping;
 use realfft::{FftError, RealFftPlanner};
 use rustfft::num_complex::Complex;
+use rustfft::{FftNum, FftPlanner};
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
 
 pub fn seq_2_binary(seq: &str) -> (Vec<i64>, Vec<i64>, Vec<i64>, Vec<i64>) {
     let len = seq.len();
-    let mut binary_a: Vec<i64> = Vec::new();
-    let mut binary_c: Vec<i64> = Vec::new();
-    let mut binary_g: Vec<i64> = Vec::new();
-    let mut binary_u: Vec<i64> = Vec::new();
-
-    binary_a.resize(len, 0);
-    binary_c.resize(len, 0);
-    binary_g.resize(len, 0);
-    binary_u.resize(len, 0);
-
-    seq.match_indices('A')
-        .for_each(|(idx, _)| binary_a[idx] = 1);
-    seq.match_indices('C')
-        .for_each(|(idx, _)| binary_c[idx] = 1);
-    seq.match_indices('G')
-        .for_each(|(idx, _)| binary_g[idx] = 1);
-    seq.match_indices('U')
-        .for_each(|(idx, _)| binary_u[idx] = 1);
+    // Spare fifth row catches anything that isn't a nucleotide
+    let mut binary = [
+        vec![0; len],
+        vec![0; len],
+        vec![0; len],
+        vec![0; len],
+        vec![0; len],
+    ];
+
+    for (idx, byte) in seq.bytes().enumerate() {
+        binary[base_index(byte)][idx] = 1;
+    }
 
+    let [binary_a, binary_c, binary_g, binary_u, _] = binary;
     (binary_a, binary_c, binary_g, binary_u)
 }
 
 pub fn seq_2_integer(seq: &str) -> Vec<i64> {
-    let len = seq.len();
-    let mut integer_seq: Vec<i64> = Vec::new();
-    integer_seq.resize(len, 0);
+    const INTEGER_VALUES: [i64; 5] = [1, 2, 3, 4, 0];
 
-    seq.match_indices('A')
-        .for_each(|(idx, _)| integer_seq[idx] = 1);
-    seq.match_indices('C')
-        .for_each(|(idx, _)| integer_seq[idx] = 2);
-    seq.match_indices('G')
-        .for_each(|(idx, _)| integer_seq[idx] = 3);
-    seq.match_indices('U')
-        .for_each(|(idx, _)| integer_seq[idx] = 4);
-
-    integer_seq
+    seq.bytes()
+        .map(|byte| INTEGER_VALUES[base_index(byte)])
+        .collect()
 }
 
 pub fn seq_2_real(seq: &str) -> Vec<f64> {
-    let len = seq.len();
-    let mut float_seq: Vec<f64> = Vec::new();
-    float_seq.resize(len, 0.0);
-
-    seq.match_indices('A')
-        .for_each(|(idx, _)| float_seq[idx] = -1.5);
-    seq.match_indices('C')
-        .for_each(|(idx, _)| float_seq[idx] = 0.5);
-    seq.match_indices('G')
-        .for_each(|(idx, _)| float_seq[idx] = -0.5);
-    seq.match_indices('U')
-        .for_each(|(idx, _)| float_seq[idx] = 1.5);
-
-    float_seq
-}
-
-pub fn fft_sequence<
-    T: Copy
-        + realfft::num_traits::FromPrimitive
-        + realfft::num_traits::Signed
-        + std::marker::Send
-        + std::marker::Sync
-        + std::fmt::Debug
-        + 'static,
->(
-    seq: &Vec<T>,
+    const REAL_VALUES: [f64; 5] = [-1.5, 0.5, -0.5, 1.5, 0.0];
+
+    seq.bytes()
+        .map(|byte| REAL_VALUES[base_index(byte)])
+        .collect()
+}
+
+/// How signals are zero-padded before they are transformed
+///
+/// Padding every sequence to the same length puts their spectra on the same
+/// frequency grid, so they can be compared bin for bin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Padding {
+    /// Transform the signal at its own length
+    #[default]
+    None,
+    /// Pad (or truncate) to exactly this length
+    Fixed(usize),
+    /// Pad up to the next power of two, which is also the fastest to transform
+    PowerOfTwo,
+}
+
+impl Padding {
+    /// Get the transform length for a signal of the given length
+    pub fn padded_len(&self, len: usize) -> usize {
+        match self {
+            Padding::None => len,
+            Padding::Fixed(fixed) => *fixed,
+            Padding::PowerOfTwo => len.next_power_of_two(),
+        }
+    }
+}
+
+/// Reusable state for taking lots of FFTs
+///
+/// Planning an FFT is much more expensive than running it, so the context
+/// keeps hold of its planners (which cache every plan they make) and the
+/// scratch buffers the transforms need. Sequences of the same length then
+/// share one plan, and nothing but the output is allocated per transform.
+///
+/// Most of the time you won't need one of these directly, as the functions
+/// in this module use a per thread context through `with_context`.
+pub struct SpectralContext<T: FftNum> {
+    real_planner: RealFftPlanner<T>,
+    complex_planner: FftPlanner<T>,
+    input: Vec<T>,
+    scratch: Vec<Complex<T>>,
+}
+
+impl<T: FftNum> SpectralContext<T> {
+    pub fn new() -> Self {
+        SpectralContext {
+            real_planner: RealFftPlanner::new(),
+            complex_planner: FftPlanner::new(),
+            input: Vec::new(),
+            scratch: Vec::new(),
+        }
+    }
+
+    /// Take the FFT of a real signal, giving the `n / 2 + 1` non-negative
+    /// frequency bins
+    pub fn fft_real(
+        &mut self,
+        signal: &[T],
+        padding: Padding,
+    ) -> Result<Vec<Complex<T>>, FftError> {
+        let len = padding.padded_len(signal.len());
+        let r2c = self.real_planner.plan_fft_forward(len);
+
+        self.input.clear();
+        self.input
+            .extend_from_slice(&signal[..signal.len().min(len)]);
+        self.input.resize(len, T::zero());
+        self.scratch
+            .resize(r2c.get_scratch_len(), Complex::new(T::zero(), T::zero()));
+        let mut spectrum = r2c.make_output_vec();
+
+        r2c.process_with_scratch(&mut self.input, &mut spectrum, &mut self.scratch)?;
+        Ok(spectrum)
+    }
+
+    /// Take the full length FFT of a complex signal
+    pub fn fft_complex(&mut self, signal: &[Complex<T>], padding: Padding) -> Vec<Complex<T>> {
+        let len = padding.padded_len(signal.len());
+        let fft = self.complex_planner.plan_fft_forward(len);
+
+        let zero = Complex::new(T::zero(), T::zero());
+        let mut spectrum = signal[..signal.len().min(len)].to_vec();
+        spectrum.resize(len, zero);
+        self.scratch.resize(fft.get_inplace_scratch_len(), zero);
+
+        fft.process_with_scratch(&mut spectrum, &mut self.scratch);
+        spectrum
+    }
+}
+
+impl<T: FftNum> Default for SpectralContext<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+thread_local! {
+    static CONTEXTS: RefCell<HashMap<TypeId, Box<dyn Any>>> = RefCell::new(HashMap::new());
+}
+
+/// Run something with this thread's spectral context for sample type `T`
+///
+/// Each thread gets one context per sample type, created on first use and
+/// kept for the life of the thread, so the plans made for one sequence are
+/// reused for every later sequence of the same length.
+pub fn with_context<T: FftNum, R>(f: impl FnOnce(&mut SpectralContext<T>) -> R) -> R {
+    CONTEXTS.with(|contexts| {
+        let mut contexts = contexts.borrow_mut();
+        let context = contexts
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(SpectralContext::<T>::new()))
+            .downcast_mut::<SpectralContext<T>>()
+            .expect("spectral contexts are keyed by their sample type");
+        f(context)
+    })
+}
+
+pub fn fft_sequence<T: FftNum>(seq: &[T]) -> Result<Vec<Complex<T>>, FftError> {
+    fft_sequence_padded(seq, Padding::None)
+}
+
+/// As `fft_sequence`, but zero-padding the signal first
+pub fn fft_sequence_padded<T: FftNum>(
+    seq: &[T],
+    padding: Padding,
 ) -> Result<Vec<Complex<T>>, FftError> {
-    let mut real_planner = RealFftPlanner::<T>::new();
+    with_context(|context: &mut SpectralContext<T>| context.fft_real(seq, padding))
+}
+
+/// Take the full length FFT of every signal from a numeric mapping
+///
+/// Unlike `fft_sequence` this returns all `n` frequency bins, not just the
+/// non-negative half, so spectra from real and complex mappings line up.
+/// Complex mappings give one spectrum per (real, imaginary) channel pair,
+/// everything else gives one spectrum per channel.
+pub fn fft_mapped(clean_seq: &str, mapping: &dyn NumericMapping) -> Vec<Vec<Complex<f64>>> {
+    fft_mapped_padded(clean_seq, mapping, Padding::None)
+}
 
-    let r2c = real_planner.plan_fft_forward(seq.len());
+/// As `fft_mapped`, but zero-padding every signal first
+pub fn fft_mapped_padded(
+    clean_seq: &str,
+    mapping: &dyn NumericMapping,
+    padding: Padding,
+) -> Vec<Vec<Complex<f64>>> {
+    let channels = mapping.map(clean_seq);
+    let signals: Vec<Vec<Complex<f64>>> = if mapping.is_complex() {
+        channels
+            .chunks(2)
+            .map(|pair| {
+                pair[0]
+                    .iter()
+                    .zip(&pair[1])
+                    .map(|(re, im)| Complex::new(*re, *im))
+                    .collect()
+            })
+            .collect()
+    } else {
+        channels
+            .iter()
+            .map(|channel| channel.iter().map(|x| Complex::new(*x, 0.0)).collect())
+            .collect()
+    };
+
+    with_context(|context: &mut SpectralContext<f64>| {
+        signals
+            .iter()
+            .map(|signal| context.fft_complex(signal, padding))
+            .collect()
+    })
+}
+
+/// Power at frequency N/3 summed over the four binary indicator sequences
+///
+/// The DFT of an indicator sequence at N/3 is `sum_n u[n] w^n` with
+/// `w = exp(-2 pi i / 3)`, and since `w^n` only depends on `n mod 3`, that is
+/// `c0 + c1 w + c2 w^2` for the counts of the base at each phase. Its squared
+/// magnitude works out to `c0^2 + c1^2 + c2^2 - c0 c1 - c1 c2 - c0 c2`.
+/// This is exact even when the length isn't divisible by three.
+fn period3_power(phased: &[[u64; 4]; 3]) -> f64 {
+    (0..4)
+        .map(|base| {
+            let [c0, c1, c2] = [0, 1, 2].map(|phase| phased[phase][base] as f64);
+            c0 * c0 + c1 * c1 + c2 * c2 - c0 * c1 - c1 * c2 - c0 * c2
+        })
+        .sum()
+}
 
-    let mut indata = seq.clone();
-    let mut spectrum = r2c.make_output_vec();
+/// Calculate the period-3 signal to noise ratio of a sequence
+///
+/// This is the Tiwari et al. (1997) / Anastassiou (2001) measure of coding
+/// potential: the power at frequency N/3 in the summed spectrum of the
+/// binary indicator sequences from `seq_2_binary`, divided by the average
+/// power over the whole spectrum. By Parseval's theorem the average power is
+/// just the number of bases, so no FFT is needed. Protein coding regions
+/// typically score above 2, while random sequence averages 1.
+#[wasm_bindgen(js_name=period3_snr)]
+pub fn period3_snr(clean_seq: &str) -> f64 {
+    let comp = Composition::new(clean_seq);
+    let num_bases: u64 = comp.total.iter().sum();
+
+    period3_power(&comp.phased) / num_bases as f64
+}
+
+/// Calculate the period-3 signal to noise ratio in sliding windows
+///
+/// Windows of `window` bases are taken every `step` bases, giving a coding
+/// potential profile along the sequence. Per-phase counts are kept as prefix
+/// sums so the whole profile only needs one pass over the sequence.
+pub fn period3_profile(clean_seq: &str, window: usize, step: usize) -> Vec<f64> {
+    let bytes = clean_seq.as_bytes();
+    if window == 0 || step == 0 || bytes.len() < window {
+        return Vec::new();
+    }
+
+    // prefix[i] holds the counts for each absolute phase and base in bytes[..i]
+    let mut prefix = Vec::with_capacity(bytes.len() + 1);
+    let mut running = [[0u64; 4]; 3];
+    prefix.push(running);
+    for (idx, byte) in bytes.iter().enumerate() {
+        let base = base_index(*byte);
+        if base != OTHER {
+            running[idx % 3][base] += 1;
+        }
+        prefix.push(running);
+    }
+
+    (0..=bytes.len() - window)
+        .step_by(step)
+        .map(|start| {
+            let (first, last) = (&prefix[start], &prefix[start + window]);
+            let mut phased = [[0u64; 4]; 3];
+            let mut num_bases = 0;
+            for phase in 0..3 {
+                for base in 0..4 {
+                    phased[phase][base] = last[phase][base] - first[phase][base];
+                    num_bases += phased[phase][base];
+                }
+            }
+            // The power at N/3 doesn't depend on where the phases start
+            period3_power(&phased) / num_bases as f64
+        })
+        .collect()
+}
+
+/// Names of the values in `SpectrumStats::to_vec`, in order
+pub const SPECTRUM_STAT_NAMES: [&str; 19] = [
+    "average",
+    "median",
+    "maximum",
+    "minimum",
+    "peak",
+    "none_levated_peak",
+    "sd_population",
+    "sd_sample",
+    "percentile_15",
+    "percentile_25",
+    "percentile_50",
+    "percentile_75",
+    "amplitude",
+    "variance",
+    "interquartile_range",
+    "semi_interquartile_range",
+    "coefficient_of_variation",
+    "skewness",
+    "kurtosis",
+];
+
+/// Summary statistics of a power spectrum, as in MathFeature's Fourier class
+///
+/// These are reimplemented from `feature_extraction` in MathFeature's
+/// `FourierClass.py`, keeping its definitions so the values are comparable:
+/// - `peak` is `(n / 3) / average`, and `none_levated_peak` the same for the
+///   magnitude (rather than power) spectrum
+/// - `sd_population` is numpy's `std`, `sd_sample` and `variance` use the
+///   `n - 1` denominator from Python's `statistics`
+/// - percentiles interpolate linearly, as numpy does by default
+/// - `skewness` is Pearson's second coefficient, `3 * (mean - median) / sd`
+/// - `kurtosis` is the percentile coefficient, `(q75 - q25) / (2 * (p90 - p10))`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpectrumStats {
+    pub average: f64,
+    pub median: f64,
+    pub maximum: f64,
+    pub minimum: f64,
+    pub peak: f64,
+    pub none_levated_peak: f64,
+    pub sd_population: f64,
+    pub sd_sample: f64,
+    pub percentile_15: f64,
+    pub percentile_25: f64,
+    pub percentile_50: f64,
+    pub percentile_75: f64,
+    pub amplitude: f64,
+    pub variance: f64,
+    pub interquartile_range: f64,
+    pub semi_interquartile_range: f64,
+    pub coefficient_of_variation: f64,
+    pub skewness: f64,
+    pub kurtosis: f64,
+}
+
+impl SpectrumStats {
+    /// Get the statistics in `SPECTRUM_STAT_NAMES` order
+    pub fn to_vec(&self) -> Vec<f64> {
+        vec![
+            self.average,
+            self.median,
+            self.maximum,
+            self.minimum,
+            self.peak,
+            self.none_levated_peak,
+            self.sd_population,
+            self.sd_sample,
+            self.percentile_15,
+            self.percentile_25,
+            self.percentile_50,
+            self.percentile_75,
+            self.amplitude,
+            self.variance,
+            self.interquartile_range,
+            self.semi_interquartile_range,
+            self.coefficient_of_variation,
+            self.skewness,
+            self.kurtosis,
+        ]
+    }
+}
 
-    r2c.process(&mut indata, &mut spectrum)?;
-    Ok(spectrum)
+/// Percentile of sorted values with linear interpolation between ranks
+pub fn percentile(sorted: &[f64], pct: f64) -> f64 {
+    let rank = pct / 100.0 * (sorted.len() - 1) as f64;
+    let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+}
+
+/// Calculate the power spectrum of a sequence under a numeric mapping
+///
+/// This is the squared magnitude of the full length FFT, summed over the
+/// channels of the mapping, so the four binary channels give the usual
+/// `|A|^2 + |C|^2 + |G|^2 + |U|^2` spectrum.
+pub fn power_spectrum(clean_seq: &str, mapping: &dyn NumericMapping) -> Vec<f64> {
+    let mut power = vec![0.0; clean_seq.len()];
+    for spectrum in fft_mapped(clean_seq, mapping) {
+        for (total, x) in power.iter_mut().zip(spectrum) {
+            *total += x.norm_sqr();
+        }
+    }
+    power
+}
+
+/// Calculate the summary statistics of a sequence's power spectrum
+///
+/// Returns `None` for sequences shorter than two bases, which don't have a
+/// spectrum to summarise.
+pub fn spectrum_stats(clean_seq: &str, mapping: &dyn NumericMapping) -> Option<SpectrumStats> {
+    if clean_seq.len() < 2 {
+        return None;
+    }
+
+    let mut spectrum = vec![0.0; clean_seq.len()];
+    let mut magnitude = vec![0.0; clean_seq.len()];
+    for channel_spectrum in fft_mapped(clean_seq, mapping) {
+        for ((power, total), x) in spectrum
+            .iter_mut()
+            .zip(&mut magnitude)
+            .zip(channel_spectrum)
+        {
+            *power += x.norm_sqr();
+            *total += x.norm();
+        }
+    }
+
+    let n = spectrum.len() as f64;
+    let average = spectrum.iter().sum::<f64>() / n;
+    let magnitude_average = magnitude.iter().sum::<f64>() / n;
+    let sum_squares: f64 = spectrum.iter().map(|x| (x - average).powi(2)).sum();
+    let sd_population = (sum_squares / n).sqrt();
+    let variance = sum_squares / (n - 1.0);
+
+    spectrum.sort_by(|a, b| a.total_cmp(b));
+    let median = percentile(&spectrum, 50.0);
+    let (maximum, minimum) = (spectrum[spectrum.len() - 1], spectrum[0]);
+    let (percentile_25, percentile_75) = (percentile(&spectrum, 25.0), percentile(&spectrum, 75.0));
+    let interquartile_range = percentile_75 - percentile_25;
+
+    Some(SpectrumStats {
+        average,
+        median,
+        maximum,
+        minimum,
+        peak: (n / 3.0) / average,
+        none_levated_peak: (n / 3.0) / magnitude_average,
+        sd_population,
+        sd_sample: variance.sqrt(),
+        percentile_15: percentile(&spectrum, 15.0),
+        percentile_25,
+        percentile_50: median,
+        percentile_75,
+        amplitude: maximum - minimum,
+        variance,
+        interquartile_range,
+        semi_interquartile_range: interquartile_range / 2.0,
+        coefficient_of_variation: sd_population / average,
+        skewness: 3.0 * (average - median) / sd_population,
+        kurtosis: interquartile_range
+            / (2.0 * (percentile(&spectrum, 90.0) - percentile(&spectrum, 10.0))),
+    })
+}
+
+/// Get the column names for `spectrum_stats` under a mapping
+pub fn spectrum_stat_names(mapping: &dyn NumericMapping) -> Vec<String> {
+    SPECTRUM_STAT_NAMES
+        .iter()
+        .map(|stat| format!("fourier_{}_{}", mapping.name(), stat))
+        .collect()
 }
 
 #[cfg(test)]
 mod test {
     use crate::stats::fourier;
+    use crate::stats::mapping::Mapping;
+    use crate::utils;
+    use std::collections::HashMap;
+    use std::fs;
+
     #[test]
     fn test_all_as_binary() {
         let seq = "AAAAAAAAAA";
 
-        let (b_a, b_c, b_g, b_u) = fourier::seq_2_binary(&seq);
+        let (b_a, b_c, b_g, b_u) = fourier::seq_2_binary(seq);
 
         assert_eq!(b_a.iter().sum::<i64>(), 10);
         assert_eq!(b_c.iter().sum::<i64>(), 0);
@@ -100,7 +484,7 @@ mod test {
     fn test_5as_5us_binary() {
         let seq = "AUAUAUAUAU";
 
-        let (b_a, b_c, b_g, b_u) = fourier::seq_2_binary(&seq);
+        let (b_a, b_c, b_g, b_u) = fourier::seq_2_binary(seq);
 
         assert_eq!(b_a.iter().sum::<i64>(), 5);
         assert_eq!(b_c.iter().sum::<i64>(), 0);
@@ -112,7 +496,7 @@ mod test {
     fn test_all_as_integer() {
         let seq = "AAAAAAAAAA";
 
-        let res = fourier::seq_2_integer(&seq);
+        let res = fourier::seq_2_integer(seq);
 
         assert_eq!(res.iter().sum::<i64>(), 10);
     }
@@ -121,7 +505,7 @@ mod test {
     fn test_5as_5us_integer() {
         let seq = "AUAUAUAUAU";
 
-        let res = fourier::seq_2_integer(&seq);
+        let res = fourier::seq_2_integer(seq);
 
         assert_eq!(res.iter().sum::<i64>(), 25);
     }
@@ -130,7 +514,7 @@ mod test {
     fn test_all_as_float() {
         let seq = "AAAAAAAAAA";
 
-        let res = fourier::seq_2_real(&seq);
+        let res = fourier::seq_2_real(seq);
 
         assert_eq!(res.iter().sum::<f64>(), -15.0);
     }
@@ -139,7 +523,7 @@ mod test {
     fn test_5as_5us_float() {
         let seq = "AUAUAUAUAU";
 
-        let res = fourier::seq_2_real(&seq);
+        let res = fourier::seq_2_real(seq);
 
         assert_eq!(res.iter().sum::<f64>(), 0.0);
     }
@@ -147,7 +531,7 @@ mod test {
     #[test]
     fn test_simple_fft_binary() {
         let seq = "AGAGAAGAGAAGAGAAGAGAAGAGAAGAGAAGAGAAGAGAAGAGAAGAGAAGAGAAGAGA";
-        let (b_a, _b_c, _b_g, _b_u) = fourier::seq_2_binary(&seq);
+        let (b_a, _b_c, _b_g, _b_u) = fourier::seq_2_binary(seq);
 
         let mut spectrum = fourier::fft_sequence(&b_a).unwrap();
         let pow_spectrum: Vec<i64> = spectrum.iter_mut().map(|x| x.powu(2).re.abs()).collect();
@@ -158,7 +542,7 @@ mod test {
     #[test]
     fn test_simple_fft_integer() {
         let seq = "AAAAAAGGGGGGGGAAAAAAGGGGGGGGGAAAAAAAGGGGGGGGGGGAAAAAAAAAGGGG";
-        let b_a = fourier::seq_2_integer(&seq);
+        let b_a = fourier::seq_2_integer(seq);
 
         let mut spectrum = fourier::fft_sequence(&b_a).unwrap();
         let pow_spectrum: Vec<i64> = spectrum.iter_mut().map(|x| x.powu(2).re.abs()).collect();
@@ -169,11 +553,243 @@ mod test {
     #[test]
     fn test_simple_fft_float() {
         let seq = "AGAGAAGAGAAGAGAAGAGAAGAGAAGAGAAGAGAAGAGAAGAGAAGAGAAGAGAAGAGA";
-        let b_a = fourier::seq_2_real(&seq);
+        let b_a = fourier::seq_2_real(seq);
 
         let mut spectrum = fourier::fft_sequence(&b_a).unwrap();
         let pow_spectrum: Vec<f64> = spectrum.iter_mut().map(|x| x.powu(2).re.abs()).collect();
 
         println!("{:?}", pow_spectrum);
     }
+
+    #[test]
+    fn test_fft_mapped() {
+        let seq = "AUGGCCAUUGUAAGCU";
+        let (b_a, _b_c, _b_g, _b_u) = fourier::seq_2_binary(seq);
+        let b_a: Vec<f64> = b_a.iter().map(|x| *x as f64).collect();
+
+        let half = fourier::fft_sequence(&b_a).unwrap();
+        let full = fourier::fft_mapped(seq, &Mapping::Binary);
+
+        assert_eq!(full.len(), 4);
+        assert_eq!(full[0].len(), seq.len());
+        for (x, y) in half.iter().zip(&full[0]) {
+            assert!((x - y).norm() < 1e-9);
+        }
+        assert_eq!(fourier::fft_mapped(seq, &Mapping::Complex).len(), 1);
+    }
+
+    /// Check against regression values for the definitions above
+    ///
+    /// These were worked out from the definitions rather than by running
+    /// MathFeature, which `test_spectrum_stats_mathfeature` does.
+    fn check_spectrum_stats(mapping: Mapping, expected: [f64; 19]) {
+        let seq = "AUGGCCAUUGUAAGCUAGCUUAGG";
+        let stats = fourier::spectrum_stats(seq, &mapping).unwrap().to_vec();
+
+        for ((name, x), y) in fourier::SPECTRUM_STAT_NAMES.iter().zip(stats).zip(expected) {
+            assert!(
+                (x - y).abs() < 1e-9 * y.abs().max(1.0),
+                "{} {} {}",
+                name,
+                x,
+                y
+            );
+        }
+    }
+
+    #[test]
+    fn test_spectrum_stats_binary() {
+        check_spectrum_stats(
+            Mapping::Binary,
+            [
+                24.000000000000018,
+                17.633974596215587,
+                150.0,
+                3.342878933412907,
+                0.3333333333333331,
+                0.9617704159972413,
+                28.844410203711913,
+                29.464791074152863,
+                8.988275479351124,
+                10.930711804366657,
+                17.633974596215587,
+                22.27234475573682,
+                146.65712106658708,
+                868.1739130434783,
+                11.341632951370164,
+                5.670816475685082,
+                1.2018504251546622,
+                0.6621066638726282,
+                0.15908073244320753,
+            ],
+        );
+    }
+
+    #[test]
+    fn test_spectrum_stats_eiip() {
+        check_spectrum_stats(
+            Mapping::Eiip,
+            [
+                0.33731026999999986,
+                0.009218504928292987,
+                7.788006489999998,
+                0.00010631771033349644,
+                23.717036543239562,
+                38.07970662670954,
+                1.5536505896047244,
+                1.5870662531018425,
+                0.0021082692435836765,
+                0.002349283062684487,
+                0.009218504928292987,
+                0.014609164217249893,
+                7.787900172289665,
+                2.518779291734721,
+                0.012259881154565406,
+                0.006129940577282703,
+                4.605998476135118,
+                0.6335242311242822,
+                0.13626792584055622,
+            ],
+        );
+    }
+
+    #[test]
+    fn test_spectrum_stats_complex() {
+        check_spectrum_stats(
+            Mapping::Complex,
+            [
+                48.000000000000085,
+                16.175527140485393,
+                390.5014353071836,
+                1.0717967697244806,
+                0.16666666666666638,
+                1.4437439579190399,
+                79.59899496852965,
+                81.31099717055235,
+                3.4414332914307724,
+                7.757359312880689,
+                16.175527140485393,
+                62.29276042984222,
+                389.42963853745914,
+                6611.478260869573,
+                54.53540111696153,
+                27.267700558480765,
+                1.6583123951776981,
+                1.199429950293852,
+                0.3344539336137899,
+            ],
+        );
+    }
+
+    /// Compare against MathFeature's own output for the sequences in
+    /// `testdata/fourier.fasta`, written by `testdata/mathfeature_fourier.py`
+    #[test]
+    #[ignore = "needs testdata/mathfeature_fourier.csv from testdata/mathfeature_fourier.py"]
+    fn test_spectrum_stats_mathfeature() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata");
+        let fasta = fs::read_to_string(format!("{}/fourier.fasta", dir)).unwrap();
+        let seqs: HashMap<&str, &str> = fasta
+            .split('>')
+            .filter_map(|x| x.split_once('\n'))
+            .map(|(name, seq)| (name.trim(), seq.trim()))
+            .collect();
+        let reference = fs::read_to_string(format!("{}/mathfeature_fourier.csv", dir)).unwrap();
+        let mut lines = reference.lines();
+        let header: Vec<&str> = lines.next().unwrap().split(',').collect();
+        assert_eq!(&header[2..], &fourier::SPECTRUM_STAT_NAMES[..]);
+
+        for line in lines {
+            let fields: Vec<&str> = line.split(',').collect();
+            let seq = utils::seq::clean(seqs[fields[0]]).unwrap();
+            let mapping: Mapping = fields[1].parse().unwrap();
+            let stats = fourier::spectrum_stats(&seq, &mapping).unwrap().to_vec();
+            for ((name, x), y) in fourier::SPECTRUM_STAT_NAMES
+                .iter()
+                .zip(stats)
+                .zip(&fields[2..])
+            {
+                let y: f64 = y.parse().unwrap();
+                assert!(
+                    (x - y).abs() < 1e-6 * y.abs().max(1.0),
+                    "{} {} {} {} {}",
+                    fields[0],
+                    fields[1],
+                    name,
+                    x,
+                    y
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_spectrum_stats_too_short() {
+        assert!(fourier::spectrum_stats("A", &Mapping::Binary).is_none());
+    }
+
+    #[test]
+    fn test_period3_snr() {
+        // A perfectly periodic sequence puts everything at N/3
+        let seq = "AUG".repeat(20);
+        assert!((fourier::period3_snr(&seq) - 20.0).abs() < 1e-9);
+
+        // and a homopolymer has nothing there
+        assert_eq!(fourier::period3_snr(&"A".repeat(30)), 0.0);
+    }
+
+    #[test]
+    fn test_period3_matches_fft() {
+        let seq = "AUGGCCAUUGUAAGCUAGCUUAGG";
+        let spectrum = fourier::power_spectrum(seq, &Mapping::Binary);
+        let average = spectrum.iter().sum::<f64>() / spectrum.len() as f64;
+
+        let snr = fourier::period3_snr(seq);
+        assert!((snr - spectrum[seq.len() / 3] / average).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_period3_profile() {
+        let seq = format!("{}{}", "A".repeat(30), "AUG".repeat(10));
+        let profile = fourier::period3_profile(&seq, 30, 15);
+
+        assert_eq!(profile.len(), 3);
+        assert_eq!(profile[0], 0.0);
+        assert!((profile[2] - 10.0).abs() < 1e-9);
+        assert!((profile[1] - fourier::period3_snr(&seq[15..45])).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_padding() {
+        assert_eq!(fourier::Padding::None.padded_len(60), 60);
+        assert_eq!(fourier::Padding::Fixed(100).padded_len(60), 100);
+        assert_eq!(fourier::Padding::PowerOfTwo.padded_len(60), 64);
+        assert_eq!(fourier::Padding::PowerOfTwo.padded_len(64), 64);
+
+        let signal = fourier::seq_2_real("AUGGCCAUUGUAAGCUAGCUUAGG");
+        let padded = fourier::fft_sequence_padded(&signal, fourier::Padding::PowerOfTwo).unwrap();
+        assert_eq!(padded.len(), 32 / 2 + 1);
+
+        let mut zero_padded = signal.clone();
+        zero_padded.resize(32, 0.0);
+        let expected = fourier::fft_sequence(&zero_padded).unwrap();
+        for (x, y) in padded.iter().zip(&expected) {
+            assert!((x - y).norm() < 1e-9);
+        }
+
+        let mapped =
+            fourier::fft_mapped_padded("AUGGCCAUUG", &Mapping::Eiip, fourier::Padding::Fixed(8));
+        assert_eq!(mapped[0].len(), 8);
+    }
+
+    #[test]
+    fn test_context_reuse() {
+        // Transforms of different lengths and types share this thread's contexts
+        for len in [10, 12, 10, 12] {
+            let signal = vec![1.0f64; len];
+            let spectrum = fourier::fft_sequence(&signal).unwrap();
+            assert!((spectrum[0].re - len as f64).abs() < 1e-9);
+        }
+        let spectrum = fourier::fft_sequence(&[1.0f32; 6]).unwrap();
+        assert_eq!(spectrum[0].re, 6.0);
+    }
 }
@@ -0,0 +1,374 @@
+//! Fractal dimension and long range correlation of numeric sequence walks
+//!
+//! These descriptors treat a mapped sequence as a time series and measure
+//! how rough it is and how far correlations along it reach:
+//!
+//! - Higuchi and Katz fractal dimension, of the signal as a curve
+//! - Hurst exponent by rescaled range (R/S) analysis
+//! - The scaling exponent of detrended fluctuation analysis (DFA)
+//!
+//! R/S and DFA take the signal as a series of increments and integrate it
+//! themselves, while the fractal dimensions look at the curve as it is. For
+//! the DNA walk, where the steps and the walk are different things, each
+//! measure gets the appropriate one, see `FractalSignal`.
+use crate::stats::composition::{base_index, A, C, G, U};
+use crate::stats::fourier::{seq_2_integer, seq_2_real};
+use anyhow::{anyhow, Result};
+use std::str::FromStr;
+
+/// Stats reported for each signal, in output order
+pub const FRACTAL_STAT_NAMES: [&str; 4] = ["higuchi", "katz", "hurst", "dfa"];
+
+/// Smallest window used for rescaled range analysis
+const MIN_RS_WINDOW: usize = 8;
+
+/// Smallest window used for detrended fluctuation analysis
+const MIN_DFA_WINDOW: usize = 4;
+
+/// The numeric signals fractal features can be computed on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FractalSignal {
+    /// Purine/pyrimidine walk, stepping up for A or G and down for C or U
+    DnaWalk,
+    /// The real mapping from `fourier::seq_2_real`
+    Real,
+    /// The integer mapping from `fourier::seq_2_integer`
+    Integer,
+}
+
+impl FractalSignal {
+    /// The name used as a column prefix and on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            FractalSignal::DnaWalk => "dna_walk",
+            FractalSignal::Real => "real",
+            FractalSignal::Integer => "integer",
+        }
+    }
+
+    /// The signal as a series of increments, for R/S and DFA
+    ///
+    /// For the DNA walk these are the `+1`/`-1` steps, and anything that isn't
+    /// a nucleotide doesn't move. The mappings are used as they are.
+    pub fn increments(&self, clean_seq: &str) -> Vec<f64> {
+        match self {
+            FractalSignal::DnaWalk => clean_seq
+                .bytes()
+                .map(|byte| match base_index(byte) {
+                    A | G => 1.0,
+                    C | U => -1.0,
+                    _ => 0.0,
+                })
+                .collect(),
+            FractalSignal::Real => seq_2_real(clean_seq),
+            FractalSignal::Integer => seq_2_integer(clean_seq)
+                .into_iter()
+                .map(|x| x as f64)
+                .collect(),
+        }
+    }
+
+    /// The signal as a curve, for the fractal dimensions
+    ///
+    /// This is the cumulative walk for the DNA walk, and the mapping as it is
+    /// otherwise.
+    pub fn curve(&self, clean_seq: &str) -> Vec<f64> {
+        let increments = self.increments(clean_seq);
+        match self {
+            FractalSignal::DnaWalk => increments
+                .iter()
+                .scan(0.0, |position, step| {
+                    *position += step;
+                    Some(*position)
+                })
+                .collect(),
+            _ => increments,
+        }
+    }
+}
+
+impl FromStr for FractalSignal {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        [
+            FractalSignal::DnaWalk,
+            FractalSignal::Real,
+            FractalSignal::Integer,
+        ]
+        .into_iter()
+        .find(|x| x.name() == s.to_lowercase())
+        .ok_or_else(|| anyhow!("Unknown fractal signal {}", s))
+    }
+}
+
+/// Least squares slope of `y` against `x`, NaN with fewer than two points
+fn slope(x: &[f64], y: &[f64]) -> f64 {
+    let n = x.len() as f64;
+    if x.len() < 2 {
+        return f64::NAN;
+    }
+    let mean_x = x.iter().sum::<f64>() / n;
+    let mean_y = y.iter().sum::<f64>() / n;
+    let covariance: f64 = x
+        .iter()
+        .zip(y)
+        .map(|(a, b)| (a - mean_x) * (b - mean_y))
+        .sum();
+    let variance: f64 = x.iter().map(|a| (a - mean_x).powi(2)).sum();
+    covariance / variance
+}
+
+/// Window sizes `min, 2 * min, 4 * min...` up to `max`
+fn window_sizes(min: usize, max: usize) -> Vec<usize> {
+    std::iter::successors(Some(min), |x| Some(x * 2))
+        .take_while(|x| *x <= max)
+        .collect()
+}
+
+/// Higuchi fractal dimension, using intervals `1..=k_max`
+///
+/// The curve length `L(k)` is measured by stepping `k` samples at a time
+/// from each of the `k` possible offsets, and the dimension is the slope of
+/// `ln L(k)` against `ln(1 / k)`. A straight line has dimension 1 and white
+/// noise approaches 2. NaN if the signal is too short for two intervals.
+pub fn higuchi(signal: &[f64], k_max: usize) -> f64 {
+    let n = signal.len();
+    let mut log_k = Vec::with_capacity(k_max);
+    let mut log_length = Vec::with_capacity(k_max);
+
+    for k in 1..=k_max.min(n / 2) {
+        let lengths: Vec<f64> = (0..k)
+            .filter_map(|m| {
+                let steps = (n - m - 1) / k;
+                if steps == 0 {
+                    return None;
+                }
+                let distance: f64 = (1..=steps)
+                    .map(|i| (signal[m + i * k] - signal[m + (i - 1) * k]).abs())
+                    .sum();
+                Some(distance * (n - 1) as f64 / (steps * k) as f64 / k as f64)
+            })
+            .collect();
+        let length = lengths.iter().sum::<f64>() / lengths.len() as f64;
+        if length > 0.0 {
+            log_k.push((1.0 / k as f64).ln());
+            log_length.push(length.ln());
+        }
+    }
+
+    slope(&log_k, &log_length)
+}
+
+/// Katz fractal dimension
+///
+/// Uses the same definition as the antropy package, with the curve length
+/// `L` as the sum of absolute differences, the diameter `d` as the furthest
+/// distance from the first sample and `n` steps, giving
+/// `log(n) / (log(n) + log(d / L))`. A straight line has dimension 1. NaN
+/// for a flat signal.
+pub fn katz(signal: &[f64]) -> f64 {
+    if signal.len() < 2 {
+        return f64::NAN;
+    }
+    let steps = (signal.len() - 1) as f64;
+    let length: f64 = signal.windows(2).map(|x| (x[1] - x[0]).abs()).sum();
+    let diameter = signal
+        .iter()
+        .skip(1)
+        .map(|x| (x - signal[0]).abs())
+        .fold(0.0, f64::max);
+    if length == 0.0 || diameter == 0.0 {
+        return f64::NAN;
+    }
+
+    steps.log10() / (steps.log10() + (diameter / length).log10())
+}
+
+/// Hurst exponent by rescaled range analysis
+///
+/// For window sizes doubling from 8 up to the whole series, the series is
+/// split into non overlapping windows. Each window's range of cumulative
+/// deviations from its mean is divided by its standard deviation, and the
+/// exponent is the slope of the log average of this against log window
+/// size. Uncorrelated noise gives about 0.5 (a little over for short
+/// series), persistent series more and anti-persistent series less. NaN if
+/// there aren't two window sizes.
+pub fn hurst_rs(increments: &[f64]) -> f64 {
+    let mut log_size = Vec::new();
+    let mut log_rs = Vec::new();
+
+    for size in window_sizes(MIN_RS_WINDOW, increments.len()) {
+        let rescaled: Vec<f64> = increments
+            .chunks_exact(size)
+            .filter_map(|window| {
+                let mean = window.iter().sum::<f64>() / size as f64;
+                let (mut cumulative, mut min, mut max) = (0.0, 0.0f64, 0.0f64);
+                for x in window {
+                    cumulative += x - mean;
+                    min = min.min(cumulative);
+                    max = max.max(cumulative);
+                }
+                let sd =
+                    (window.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / size as f64).sqrt();
+                (sd > 0.0).then(|| (max - min) / sd)
+            })
+            .collect();
+        if !rescaled.is_empty() {
+            let average = rescaled.iter().sum::<f64>() / rescaled.len() as f64;
+            if average > 0.0 {
+                log_size.push((size as f64).ln());
+                log_rs.push(average.ln());
+            }
+        }
+    }
+
+    slope(&log_size, &log_rs)
+}
+
+/// Scaling exponent from detrended fluctuation analysis
+///
+/// The series is integrated into a profile about its mean, which is split
+/// into non overlapping windows with sizes doubling from 4 up to a quarter of
+/// the series. Each window has its least squares line removed, and the
+/// fluctuation `F(n)` is the root mean square of what's left over all windows
+/// of size `n`. The exponent is the slope of `log F(n)` against `log n`, 0.5
+/// for uncorrelated noise and 1.5 for a random walk. NaN if there aren't two
+/// window sizes.
+pub fn dfa(increments: &[f64]) -> f64 {
+    let mean = increments.iter().sum::<f64>() / increments.len() as f64;
+    let profile: Vec<f64> = increments
+        .iter()
+        .scan(0.0, |total, x| {
+            *total += x - mean;
+            Some(*total)
+        })
+        .collect();
+
+    let mut log_size = Vec::new();
+    let mut log_fluctuation = Vec::new();
+
+    for size in window_sizes(MIN_DFA_WINDOW, increments.len() / 4) {
+        // Positions within a window, centred so the fit is a simple ratio
+        let centre = (size - 1) as f64 / 2.0;
+        let positions: Vec<f64> = (0..size).map(|x| x as f64 - centre).collect();
+        let position_variance: f64 = positions.iter().map(|x| x * x).sum();
+
+        let mut squared_residuals = 0.0;
+        let mut count = 0;
+        for window in profile.chunks_exact(size) {
+            let window_mean = window.iter().sum::<f64>() / size as f64;
+            let gradient = positions
+                .iter()
+                .zip(window)
+                .map(|(t, y)| t * (y - window_mean))
+                .sum::<f64>()
+                / position_variance;
+            squared_residuals += positions
+                .iter()
+                .zip(window)
+                .map(|(t, y)| (y - window_mean - gradient * t).powi(2))
+                .sum::<f64>();
+            count += size;
+        }
+
+        let fluctuation = (squared_residuals / count as f64).sqrt();
+        if fluctuation > 0.0 {
+            log_size.push((size as f64).ln());
+            log_fluctuation.push(fluctuation.ln());
+        }
+    }
+
+    slope(&log_size, &log_fluctuation)
+}
+
+/// Calculate all the fractal features of a sequence for one signal
+///
+/// Returns the stats in `FRACTAL_STAT_NAMES` order. Any that the sequence is
+/// too short or too uniform for are NaN.
+pub fn fractal_features(clean_seq: &str, signal: FractalSignal, k_max: usize) -> Vec<f64> {
+    let curve = signal.curve(clean_seq);
+    let increments = signal.increments(clean_seq);
+
+    vec![
+        higuchi(&curve, k_max),
+        katz(&curve),
+        hurst_rs(&increments),
+        dfa(&increments),
+    ]
+}
+
+/// Get the column names for `fractal_features`, like `fractal_dna_walk_dfa`
+pub fn fractal_feature_names(signal: FractalSignal) -> Vec<String> {
+    FRACTAL_STAT_NAMES
+        .iter()
+        .map(|stat| format!("fractal_{}_{}", signal.name(), stat))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::stats::fractal::{self, FractalSignal};
+
+    /// Deterministic uniform noise in `-1..1`, from a 64 bit LCG
+    fn noise(n: usize) -> Vec<f64> {
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        (0..n)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                (state >> 11) as f64 / (1u64 << 53) as f64 * 2.0 - 1.0
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_straight_line() {
+        let line: Vec<f64> = (0..100).map(|x| 0.5 * x as f64).collect();
+
+        assert!((fractal::higuchi(&line, 8) - 1.0).abs() < 1e-9);
+        assert!((fractal::katz(&line) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_noise() {
+        let white = noise(4096);
+        let walk: Vec<f64> = white
+            .iter()
+            .scan(0.0, |total, x| {
+                *total += x;
+                Some(*total)
+            })
+            .collect();
+
+        let higuchi = fractal::higuchi(&white, 8);
+        assert!((higuchi - 2.0).abs() < 0.1, "{}", higuchi);
+        let hurst = fractal::hurst_rs(&white);
+        assert!(hurst > 0.45 && hurst < 0.7, "{}", hurst);
+        let dfa_white = fractal::dfa(&white);
+        assert!((dfa_white - 0.5).abs() < 0.1, "{}", dfa_white);
+        let dfa_walk = fractal::dfa(&walk);
+        assert!((dfa_walk - 1.5).abs() < 0.1, "{}", dfa_walk);
+    }
+
+    #[test]
+    fn test_features() {
+        let seq = "GGCAUGGAGUCCUGUGGUAUCCACGAGAUCACCUUCAACUCCAUCAUGAAGUGUGAUGUGG";
+        for signal in ["dna_walk", "real", "integer"] {
+            let signal: FractalSignal = signal.parse().unwrap();
+            let features = fractal::fractal_features(seq, signal, 8);
+            assert_eq!(features.len(), fractal::fractal_feature_names(signal).len());
+            assert!(features.iter().all(|x| x.is_finite()), "{:?}", signal);
+        }
+        assert_eq!(
+            fractal::fractal_feature_names(FractalSignal::DnaWalk)[3],
+            "fractal_dna_walk_dfa"
+        );
+
+        // Too short for anything but Katz
+        let short = fractal::fractal_features("ACG", FractalSignal::Real, 8);
+        assert!(short[0].is_nan() && short[2].is_nan() && short[3].is_nan());
+        assert!(fractal::katz(&[1.0, 1.0, 1.0]).is_nan());
+    }
+}
@@ -0,0 +1,358 @@
+//! Complex network features of the kmer graph
+//!
+//! The sequence is read as a stream of overlapping kmers, and each distinct
+//! kmer becomes a node. Consecutive kmers in the stream are joined by an
+//! edge, so the graph is the part of the de Bruijn graph that the sequence
+//! actually walks. Following MathFeature's complex network descriptors, the
+//! graph is treated as simple and undirected: repeated adjacencies give a
+//! single edge and self loops (from runs like `AAAA`) are dropped.
+//!
+//! The topology is summarised by size, degree distribution, clustering,
+//! degree assortativity, counts of the two connected motifs on three nodes
+//! (triangles and open triads) and betweenness centrality.
+use crate::stats::composition::{base_index, OTHER};
+use std::collections::VecDeque;
+
+/// Stats reported for each graph, in output order
+pub const GRAPH_STAT_NAMES: [&str; 14] = [
+    "nodes",
+    "edges",
+    "average_degree",
+    "degree_sd",
+    "degree_skewness",
+    "max_degree",
+    "average_clustering",
+    "transitivity",
+    "assortativity",
+    "triangles",
+    "open_triads",
+    "betweenness_mean",
+    "betweenness_sd",
+    "betweenness_max",
+];
+
+/// A simple undirected graph of the kmers in a sequence
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KmerGraph {
+    /// The `kmer_counts` index of each node's kmer, in order of appearance
+    pub kmers: Vec<usize>,
+    /// Sorted neighbours of each node
+    pub adjacency: Vec<Vec<usize>>,
+}
+
+impl KmerGraph {
+    /// Build the graph from the overlapping kmers of a sequence
+    ///
+    /// Kmers are indexed as in `composition::kmer_counts`, and a non
+    /// nucleotide character breaks the stream, so the kmers either side of it
+    /// aren't joined.
+    ///
+    /// Nodes are looked up in a table of all 4^k kmers, so memory grows
+    /// exponentially with k and callers should keep it small.
+    ///
+    /// # Panics
+    /// If 4^k doesn't fit in a `usize`.
+    pub fn new(clean_seq: &str, k: usize) -> KmerGraph {
+        let mut graph = KmerGraph {
+            kmers: Vec::new(),
+            adjacency: Vec::new(),
+        };
+        if k == 0 {
+            return graph;
+        }
+
+        assert!(2 * k < usize::BITS as usize, "k of {} is too large", k);
+        let mut node_ids = vec![usize::MAX; 1 << (2 * k)];
+        let mask = (1usize << (2 * k)) - 1;
+        let mut index = 0usize;
+        let mut valid = 0usize;
+        let mut previous: Option<usize> = None;
+
+        for byte in clean_seq.bytes() {
+            let base = base_index(byte);
+            if base == OTHER {
+                valid = 0;
+                previous = None;
+                continue;
+            }
+            index = ((index << 2) | base) & mask;
+            valid += 1;
+            if valid < k {
+                continue;
+            }
+
+            if node_ids[index] == usize::MAX {
+                node_ids[index] = graph.kmers.len();
+                graph.kmers.push(index);
+                graph.adjacency.push(Vec::new());
+            }
+            let node = node_ids[index];
+            if let Some(previous) = previous.filter(|x| *x != node) {
+                graph.adjacency[previous].push(node);
+                graph.adjacency[node].push(previous);
+            }
+            previous = Some(node);
+        }
+
+        for neighbours in graph.adjacency.iter_mut() {
+            neighbours.sort_unstable();
+            neighbours.dedup();
+        }
+        graph
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        self.kmers.len()
+    }
+
+    pub fn num_edges(&self) -> usize {
+        self.degrees().iter().sum::<usize>() / 2
+    }
+
+    pub fn degrees(&self) -> Vec<usize> {
+        self.adjacency.iter().map(|x| x.len()).collect()
+    }
+
+    /// Number of triangles each node is part of
+    pub fn triangles(&self) -> Vec<usize> {
+        self.adjacency
+            .iter()
+            .map(|neighbours| {
+                let mut count = 0;
+                for (idx, first) in neighbours.iter().enumerate() {
+                    for second in &neighbours[idx + 1..] {
+                        if self.adjacency[*first].binary_search(second).is_ok() {
+                            count += 1;
+                        }
+                    }
+                }
+                count
+            })
+            .collect()
+    }
+
+    /// Normalised betweenness centrality of every node
+    ///
+    /// Uses Brandes' algorithm, with the same normalisation as networkx:
+    /// divided by `(n - 1)(n - 2)`, counting each pair of endpoints in both
+    /// directions. This takes time proportional to nodes times edges.
+    pub fn betweenness(&self) -> Vec<f64> {
+        let n = self.num_nodes();
+        let mut centrality = vec![0.0; n];
+
+        for source in 0..n {
+            let mut order = Vec::with_capacity(n);
+            let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); n];
+            let mut paths = vec![0.0; n];
+            let mut distance = vec![usize::MAX; n];
+            paths[source] = 1.0;
+            distance[source] = 0;
+
+            let mut queue = VecDeque::from([source]);
+            while let Some(node) = queue.pop_front() {
+                order.push(node);
+                for neighbour in &self.adjacency[node] {
+                    if distance[*neighbour] == usize::MAX {
+                        distance[*neighbour] = distance[node] + 1;
+                        queue.push_back(*neighbour);
+                    }
+                    if distance[*neighbour] == distance[node] + 1 {
+                        paths[*neighbour] += paths[node];
+                        predecessors[*neighbour].push(node);
+                    }
+                }
+            }
+
+            let mut dependency = vec![0.0; n];
+            for node in order.into_iter().rev() {
+                for predecessor in &predecessors[node] {
+                    dependency[*predecessor] +=
+                        paths[*predecessor] / paths[node] * (1.0 + dependency[node]);
+                }
+                if node != source {
+                    centrality[node] += dependency[node];
+                }
+            }
+        }
+
+        if n > 2 {
+            let scale = 1.0 / ((n - 1) * (n - 2)) as f64;
+            for x in centrality.iter_mut() {
+                *x *= scale;
+            }
+        }
+        centrality
+    }
+
+    /// Degree assortativity, the Pearson correlation of the degrees at
+    /// either end of each edge
+    ///
+    /// NaN if every edge joins nodes of the same degrees, as in networkx.
+    pub fn assortativity(&self) -> f64 {
+        let degrees = self.degrees();
+        let mut ends = Vec::new();
+        for (node, neighbours) in self.adjacency.iter().enumerate() {
+            for neighbour in neighbours {
+                ends.push((degrees[node] as f64, degrees[*neighbour] as f64));
+            }
+        }
+        if ends.is_empty() {
+            return f64::NAN;
+        }
+
+        // Every edge is counted both ways round, so both ends have the same
+        // mean and variance
+        let count = ends.len() as f64;
+        let mean = ends.iter().map(|x| x.0).sum::<f64>() / count;
+        let variance = ends.iter().map(|x| (x.0 - mean).powi(2)).sum::<f64>() / count;
+        let covariance = ends
+            .iter()
+            .map(|x| (x.0 - mean) * (x.1 - mean))
+            .sum::<f64>()
+            / count;
+        if variance > 0.0 {
+            covariance / variance
+        } else {
+            f64::NAN
+        }
+    }
+}
+
+/// Mean, population standard deviation and skewness of some values
+///
+/// Skewness is the biased moment estimate, as scipy's default, and zero when
+/// there's no spread.
+fn moments(values: &[f64]) -> (f64, f64, f64) {
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let m2 = values.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / n;
+    let m3 = values.iter().map(|x| (x - mean).powi(3)).sum::<f64>() / n;
+    let skewness = if m2 > 0.0 { m3 / m2.powf(1.5) } else { 0.0 };
+    (mean, m2.sqrt(), skewness)
+}
+
+/// Calculate the graph features of a sequence's kmer graph
+///
+/// Returns the stats in `GRAPH_STAT_NAMES` order. Local clustering of nodes
+/// with fewer than two neighbours counts as zero in the average, as in
+/// networkx. A sequence with no kmers gives NaN for the averages.
+pub fn graph_features(clean_seq: &str, k: usize) -> Vec<f64> {
+    let graph = KmerGraph::new(clean_seq, k);
+    let degrees: Vec<f64> = graph.degrees().iter().map(|x| *x as f64).collect();
+    let triangles = graph.triangles();
+
+    let (average_degree, degree_sd, degree_skewness) = moments(&degrees);
+    let max_degree = degrees.iter().cloned().fold(f64::NAN, f64::max);
+
+    let clustering: Vec<f64> = degrees
+        .iter()
+        .zip(&triangles)
+        .map(|(degree, triangles)| {
+            if *degree < 2.0 {
+                0.0
+            } else {
+                2.0 * *triangles as f64 / (degree * (degree - 1.0))
+            }
+        })
+        .collect();
+    let average_clustering = clustering.iter().sum::<f64>() / clustering.len() as f64;
+
+    let total_triangles = triangles.iter().sum::<usize>() as f64 / 3.0;
+    let triads: f64 = degrees.iter().map(|x| x * (x - 1.0) / 2.0).sum();
+    let transitivity = if triads > 0.0 {
+        3.0 * total_triangles / triads
+    } else {
+        0.0
+    };
+
+    let betweenness = graph.betweenness();
+    let (betweenness_mean, betweenness_sd, _) = moments(&betweenness);
+    let betweenness_max = betweenness.into_iter().fold(f64::NAN, f64::max);
+
+    vec![
+        graph.num_nodes() as f64,
+        graph.num_edges() as f64,
+        average_degree,
+        degree_sd,
+        degree_skewness,
+        max_degree,
+        average_clustering,
+        transitivity,
+        graph.assortativity(),
+        total_triangles,
+        triads - 3.0 * total_triangles,
+        betweenness_mean,
+        betweenness_sd,
+        betweenness_max,
+    ]
+}
+
+/// Get the column names for `graph_features`, like `graph_k3_transitivity`
+pub fn graph_feature_names(k: usize) -> Vec<String> {
+    GRAPH_STAT_NAMES
+        .iter()
+        .map(|stat| format!("graph_k{}_{}", k, stat))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::stats::graph::{self, KmerGraph};
+
+    fn close(x: f64, y: f64) -> bool {
+        (x - y).abs() < 1e-12
+    }
+
+    #[test]
+    fn test_build() {
+        // AAC, ACA, CAA, AAC again, then a break
+        let graph = KmerGraph::new("AACAACNAAC", 3);
+        assert_eq!(graph.num_nodes(), 3);
+        assert_eq!(graph.num_edges(), 3);
+
+        // Runs of one base make a self loop, which is dropped
+        let graph = KmerGraph::new("AAAAC", 2);
+        assert_eq!(graph.num_nodes(), 2);
+        assert_eq!(graph.adjacency, [vec![1], vec![0]]);
+    }
+
+    #[test]
+    fn test_cycle() {
+        // A - C - G - U - A is a four cycle
+        let features = graph::graph_features("ACGUACGU", 1);
+        assert_eq!(features[..3], [4.0, 4.0, 2.0]);
+        assert_eq!(features[3], 0.0);
+        assert_eq!(features[6], 0.0);
+        assert!(features[8].is_nan());
+        assert_eq!(features[9..11], [0.0, 4.0]);
+        assert!(close(features[11], 1.0 / 6.0));
+        assert!(close(features[12], 0.0));
+    }
+
+    #[test]
+    fn test_triangle() {
+        let features = graph::graph_features("ACGA", 1);
+        assert_eq!(features[6..8], [1.0, 1.0]);
+        assert_eq!(features[9..11], [1.0, 0.0]);
+        assert_eq!(features[13], 0.0);
+    }
+
+    #[test]
+    fn test_star() {
+        // A in the middle of C, G and U
+        let features = graph::graph_features("ACAGAU", 1);
+        assert_eq!(features[5], 3.0);
+        assert!(close(features[4], 1.0 / 0.75f64.sqrt()));
+        assert!(close(features[8], -1.0));
+        assert_eq!(features[9..11], [0.0, 3.0]);
+        assert!(close(features[11], 0.25));
+        assert!(close(features[13], 1.0));
+    }
+
+    #[test]
+    fn test_names() {
+        let names = graph::graph_feature_names(3);
+        assert_eq!(names.len(), graph::graph_features("ACGUAGCUAG", 3).len());
+        assert_eq!(names[1], "graph_k3_edges");
+    }
+}
@@ -0,0 +1,407 @@
+//! Numeric mappings of nucleotide sequences
+//!
+//! Signal processing features (Fourier, wavelets, fractal measures...) need
+//! the sequence as one or more numeric signals. A `NumericMapping` turns a
+//! cleaned sequence into a set of equal length channels, e.g. four binary
+//! indicator channels, or a single EIIP channel.
+//!
+//! The built-in mappings are the ones used by MathFeature, and are available
+//! through the `Mapping` enum. Anything else can either be described as a
+//! `CustomMapping` lookup table, or implemented from scratch on the trait.
+use crate::stats::composition::{base_index, A, BASES, C, G, OTHER, U};
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Something that can turn a sequence into numeric signals
+pub trait NumericMapping {
+    /// A short name for the mapping, used in column names
+    fn name(&self) -> String;
+
+    /// How many channels `map` returns
+    fn channels(&self) -> usize;
+
+    /// Map a cleaned sequence to one signal per channel
+    ///
+    /// Every channel is the same length as the sequence.
+    fn map(&self, clean_seq: &str) -> Vec<Vec<f64>>;
+
+    /// Whether channels come in (real, imaginary) pairs of a complex signal
+    fn is_complex(&self) -> bool {
+        false
+    }
+
+    /// Column name suffixes for each channel
+    ///
+    /// By default single channel and complex mappings have no suffix, and
+    /// anything else is numbered from zero.
+    fn channel_names(&self) -> Vec<String> {
+        numbered_channel_names(self)
+    }
+}
+
+/// Channel names of a mapping without any of its own
+fn numbered_channel_names<M: NumericMapping + ?Sized>(mapping: &M) -> Vec<String> {
+    let channels = mapping.channels();
+    if channels == 1 || mapping.is_complex() {
+        vec![mapping.name()]
+    } else {
+        (0..channels)
+            .map(|idx| format!("{}_{}", mapping.name(), idx))
+            .collect()
+    }
+}
+
+/// Map a sequence through a per-base lookup table with one row per channel
+///
+/// Each row has a value for A, C, G, U and then anything else.
+fn lookup(clean_seq: &str, table: &[[f64; 5]]) -> Vec<Vec<f64>> {
+    table
+        .iter()
+        .map(|values| {
+            clean_seq
+                .bytes()
+                .map(|byte| values[base_index(byte)])
+                .collect()
+        })
+        .collect()
+}
+
+/// The built-in mappings
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mapping {
+    /// Four binary indicator channels, as `fourier::seq_2_binary`
+    Binary,
+    /// A=1, C=2, G=3, U=4, as `fourier::seq_2_integer`
+    Integer,
+    /// A=-1.5, C=0.5, G=-0.5, U=1.5, as `fourier::seq_2_real`
+    Real,
+    /// Electron-ion interaction potential
+    Eiip,
+    /// Total atomic number of the base, using thymine's value for U as
+    /// MathFeature does
+    AtomicNumber,
+    /// Nucleotide chemical property, three channels for ring structure,
+    /// hydrogen bonding and chemical functionality
+    Ncp,
+    /// A and U = 1, C and G = -1
+    PairedNumeric,
+    /// A=1+i, C=-1-i, G=-1+i, U=1-i
+    Complex,
+    /// The three cumulative Z-curve coordinates
+    ZCurve,
+    /// A user defined lookup table
+    Custom(CustomMapping),
+}
+
+impl Mapping {
+    /// All of the built-in mappings that don't need any parameters
+    pub fn builtin() -> Vec<Mapping> {
+        vec![
+            Mapping::Binary,
+            Mapping::Integer,
+            Mapping::Real,
+            Mapping::Eiip,
+            Mapping::AtomicNumber,
+            Mapping::Ncp,
+            Mapping::PairedNumeric,
+            Mapping::Complex,
+            Mapping::ZCurve,
+        ]
+    }
+
+    fn table(&self) -> Option<Vec<[f64; 5]>> {
+        match self {
+            Mapping::Binary => Some(vec![
+                [1.0, 0.0, 0.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0, 0.0, 0.0],
+                [0.0, 0.0, 1.0, 0.0, 0.0],
+                [0.0, 0.0, 0.0, 1.0, 0.0],
+            ]),
+            Mapping::Integer => Some(vec![[1.0, 2.0, 3.0, 4.0, 0.0]]),
+            Mapping::Real => Some(vec![[-1.5, 0.5, -0.5, 1.5, 0.0]]),
+            Mapping::Eiip => Some(vec![[0.1260, 0.1340, 0.0806, 0.1335, 0.0]]),
+            Mapping::AtomicNumber => Some(vec![[70.0, 58.0, 78.0, 66.0, 0.0]]),
+            Mapping::Ncp => Some(vec![
+                [1.0, 0.0, 1.0, 0.0, 0.0],
+                [1.0, 1.0, 0.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0, 1.0, 0.0],
+            ]),
+            Mapping::PairedNumeric => Some(vec![[1.0, -1.0, -1.0, 1.0, 0.0]]),
+            Mapping::Complex => Some(vec![
+                [1.0, -1.0, -1.0, 1.0, 0.0],
+                [1.0, -1.0, 1.0, -1.0, 0.0],
+            ]),
+            _ => None,
+        }
+    }
+}
+
+impl NumericMapping for Mapping {
+    fn name(&self) -> String {
+        match self {
+            Mapping::Binary => "binary".to_owned(),
+            Mapping::Integer => "integer".to_owned(),
+            Mapping::Real => "real".to_owned(),
+            Mapping::Eiip => "eiip".to_owned(),
+            Mapping::AtomicNumber => "atomic_number".to_owned(),
+            Mapping::Ncp => "ncp".to_owned(),
+            Mapping::PairedNumeric => "paired_numeric".to_owned(),
+            Mapping::Complex => "complex".to_owned(),
+            Mapping::ZCurve => "z_curve".to_owned(),
+            Mapping::Custom(custom) => custom.name(),
+        }
+    }
+
+    fn channels(&self) -> usize {
+        match self {
+            Mapping::ZCurve => 3,
+            Mapping::Custom(custom) => custom.channels(),
+            _ => self.table().map_or(0, |table| table.len()),
+        }
+    }
+
+    fn map(&self, clean_seq: &str) -> Vec<Vec<f64>> {
+        match self {
+            Mapping::ZCurve => z_curve(clean_seq),
+            Mapping::Custom(custom) => custom.map(clean_seq),
+            _ => lookup(clean_seq, &self.table().unwrap_or_default()),
+        }
+    }
+
+    fn is_complex(&self) -> bool {
+        matches!(self, Mapping::Complex)
+    }
+
+    /// Binary channels are named by base, as `binary_A`
+    fn channel_names(&self) -> Vec<String> {
+        match self {
+            Mapping::Binary => BASES
+                .iter()
+                .map(|base| format!("{}_{}", self.name(), base))
+                .collect(),
+            Mapping::Custom(custom) => custom.channel_names(),
+            _ => numbered_channel_names(self),
+        }
+    }
+}
+
+impl FromStr for Mapping {
+    type Err = anyhow::Error;
+
+    /// Parse the name of one of the built-in mappings
+    fn from_str(s: &str) -> Result<Self> {
+        Mapping::builtin()
+            .into_iter()
+            .find(|x| x.name() == s.to_lowercase())
+            .ok_or_else(|| anyhow!("Unknown numeric mapping {}", s))
+    }
+}
+
+/// Calculate the cumulative Z-curve coordinates
+///
+/// At each position the three channels are
+/// - x, purine vs pyrimidine: `(A + G) - (C + U)`
+/// - y, amino vs keto: `(A + C) - (G + U)`
+/// - z, weak vs strong hydrogen bonding: `(A + U) - (G + C)`
+///
+/// where each base is the count so far, including the current position.
+pub fn z_curve(clean_seq: &str) -> Vec<Vec<f64>> {
+    let mut counts = [0.0; 5];
+    let mut trajectory: Vec<Vec<f64>> = (0..3)
+        .map(|_| Vec::with_capacity(clean_seq.len()))
+        .collect();
+
+    for byte in clean_seq.bytes() {
+        counts[base_index(byte)] += 1.0;
+        trajectory[0].push((counts[A] + counts[G]) - (counts[C] + counts[U]));
+        trajectory[1].push((counts[A] + counts[C]) - (counts[G] + counts[U]));
+        trajectory[2].push((counts[A] + counts[U]) - (counts[G] + counts[C]));
+    }
+
+    trajectory
+}
+
+/// A user defined per-base lookup mapping
+///
+/// Each base maps to a fixed number of channel values. Anything without an
+/// entry in the table maps to zero on every channel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustomMapping {
+    name: String,
+    table: Vec<[f64; 5]>,
+}
+
+impl CustomMapping {
+    /// Build a mapping from the values for each base
+    ///
+    /// Every base has to have the same number of values. T is treated as U.
+    pub fn new(name: &str, values: &HashMap<char, Vec<f64>>) -> Result<CustomMapping> {
+        let channels = values.values().map(|x| x.len()).max().unwrap_or(0);
+        if channels == 0 || values.values().any(|x| x.len() != channels) {
+            return Err(anyhow!(
+                "Mapping {} needs the same, non-zero, number of values for every base",
+                name
+            ));
+        }
+
+        let mut table = vec![[0.0; 5]; channels];
+        for (base, base_values) in values {
+            let idx = base_index(base.to_ascii_uppercase() as u8);
+            if idx == OTHER {
+                return Err(anyhow!("Mapping {} has an entry for {}", name, base));
+            }
+            for (row, value) in table.iter_mut().zip(base_values) {
+                row[idx] = *value;
+            }
+        }
+
+        Ok(CustomMapping {
+            name: name.to_owned(),
+            table,
+        })
+    }
+
+    /// Build a single channel mapping from a string like `A=1,C=2,G=3,U=4`
+    pub fn parse(name: &str, spec: &str) -> Result<CustomMapping> {
+        let mut values = HashMap::new();
+        for entry in spec.split(',') {
+            let (base, value) = entry
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Expected BASE=VALUE, got {}", entry))?;
+            let base = base
+                .trim()
+                .chars()
+                .next()
+                .ok_or_else(|| anyhow!("Missing base in {}", entry))?;
+            values.insert(base, vec![value.trim().parse::<f64>()?]);
+        }
+        CustomMapping::new(name, &values)
+    }
+
+    /// The values for each base, one row per channel in A, C, G, U order
+    pub fn table(&self) -> Vec<[f64; 4]> {
+        self.table
+            .iter()
+            .map(|row| [row[A], row[C], row[G], row[U]])
+            .collect()
+    }
+}
+
+impl NumericMapping for CustomMapping {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn channels(&self) -> usize {
+        self.table.len()
+    }
+
+    fn map(&self, clean_seq: &str) -> Vec<Vec<f64>> {
+        lookup(clean_seq, &self.table)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::stats::fourier;
+    use crate::stats::mapping::{self, CustomMapping, Mapping, NumericMapping};
+
+    #[test]
+    fn test_matches_existing_mappings() {
+        let seq = "AUGGCCAUUGUAN";
+        let (b_a, b_c, b_g, b_u) = fourier::seq_2_binary(seq);
+        let binary = Mapping::Binary.map(seq);
+        for (mapped, existing) in binary.iter().zip([b_a, b_c, b_g, b_u]) {
+            let existing: Vec<f64> = existing.iter().map(|x| *x as f64).collect();
+            assert_eq!(*mapped, existing);
+        }
+
+        let integer: Vec<f64> = fourier::seq_2_integer(seq)
+            .iter()
+            .map(|x| *x as f64)
+            .collect();
+        assert_eq!(Mapping::Integer.map(seq), vec![integer]);
+        assert_eq!(Mapping::Real.map(seq), vec![fourier::seq_2_real(seq)]);
+    }
+
+    #[test]
+    fn test_channel_counts() {
+        let seq = "ACGUACGU";
+        for mapping in Mapping::builtin() {
+            let mapped = mapping.map(seq);
+            assert_eq!(mapped.len(), mapping.channels(), "{}", mapping.name());
+            assert!(mapped.iter().all(|x| x.len() == seq.len()));
+        }
+    }
+
+    #[test]
+    fn test_ncp() {
+        assert_eq!(
+            Mapping::Ncp.map("ACGU"),
+            vec![
+                vec![1.0, 0.0, 1.0, 0.0],
+                vec![1.0, 1.0, 0.0, 0.0],
+                vec![1.0, 0.0, 0.0, 1.0]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_z_curve() {
+        let trajectory = mapping::z_curve("AGCU");
+        assert_eq!(trajectory[0], [1.0, 2.0, 1.0, 0.0]);
+        assert_eq!(trajectory[1], [1.0, 0.0, 1.0, 0.0]);
+        assert_eq!(trajectory[2], [1.0, 0.0, -1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_custom_mapping() {
+        let custom = CustomMapping::parse("mine", "A=1, C=2, G=3, T=4").unwrap();
+        assert_eq!(custom.map("UGCAN"), vec![vec![4.0, 3.0, 2.0, 1.0, 0.0]]);
+        assert_eq!(custom.channel_names(), ["mine"]);
+
+        assert!(CustomMapping::parse("bad", "A=1,X=2").is_err());
+        assert!(CustomMapping::parse("bad", "A1").is_err());
+    }
+
+    #[test]
+    fn test_channel_names() {
+        struct Purine;
+
+        impl NumericMapping for Purine {
+            fn name(&self) -> String {
+                "purine".to_owned()
+            }
+
+            fn channels(&self) -> usize {
+                2
+            }
+
+            fn map(&self, clean_seq: &str) -> Vec<Vec<f64>> {
+                let purine: Vec<f64> = clean_seq
+                    .bytes()
+                    .map(|x| if b"AG".contains(&x) { 1.0 } else { 0.0 })
+                    .collect();
+                let pyrimidine = purine.iter().map(|x| 1.0 - x).collect();
+                vec![purine, pyrimidine]
+            }
+
+            fn channel_names(&self) -> Vec<String> {
+                vec!["purine_r".to_owned(), "purine_y".to_owned()]
+            }
+        }
+
+        assert_eq!(Purine.channel_names(), ["purine_r", "purine_y"]);
+        assert_eq!(Mapping::Binary.channel_names()[3], "binary_U");
+        assert_eq!(Mapping::Ncp.channel_names()[2], "ncp_2");
+        assert_eq!(Mapping::Complex.channel_names(), ["complex"]);
+        assert_eq!(Mapping::Eiip.channel_names(), ["eiip"]);
+    }
+
+    #[test]
+    fn test_parse_mapping() {
+        assert_eq!("EIIP".parse::<Mapping>().unwrap(), Mapping::Eiip);
+        assert!("nope".parse::<Mapping>().is_err());
+    }
+}
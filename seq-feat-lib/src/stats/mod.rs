@@ -1,4 +1,16 @@
+pub mod anf;
+pub mod autocorrelation;
+pub mod cgr;
+pub mod composition;
 pub mod counts;
 pub mod entropy;
 pub mod fickett;
 pub mod fourier;
+pub mod fractal;
+pub mod graph;
+pub mod mapping;
+pub mod null;
+pub mod pseknc;
+pub mod spectral;
+pub mod wavelet;
+pub mod zcurve;
@@ -0,0 +1,172 @@
+//! Scores against a null distribution of shuffled sequences
+//!
+//! A feature like the MFE depends strongly on composition, so on its own it
+//! can't say whether a sequence is unusually structured. Comparing it to the
+//! same feature on shuffles that keep the kmer counts, as in RNAz and
+//! Clote et al. (2005), gives a z-score and empirical p-value that can.
+use crate::utils::seq::kmer_shuffle;
+use anyhow::{anyhow, Result};
+use std::str::FromStr;
+
+/// Null scores reported for each feature, in output order
+pub const NULL_STAT_NAMES: [&str; 2] = ["z_score", "p_value"];
+
+/// Which end of the null distribution counts as extreme
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tail {
+    /// Low values are interesting, as for free energies
+    Lower,
+    /// High values are interesting, as for coding scores
+    Upper,
+}
+
+impl Tail {
+    /// The name used in configs, `lower` or `upper`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tail::Lower => "lower",
+            Tail::Upper => "upper",
+        }
+    }
+}
+
+impl FromStr for Tail {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        [Tail::Lower, Tail::Upper]
+            .into_iter()
+            .find(|x| x.name() == s.to_lowercase())
+            .ok_or_else(|| anyhow!("Unknown tail {}, use lower or upper", s))
+    }
+}
+
+/// A feature's value compared with its values on shuffled sequences
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NullScore {
+    pub value: f64,
+    /// Mean over the shuffles
+    pub mean: f64,
+    /// Sample standard deviation over the shuffles
+    pub sd: f64,
+    /// Standard deviations from the shuffled mean, NaN if they don't vary
+    pub z_score: f64,
+    /// Fraction of shuffles at least as extreme, counting the sequence itself
+    pub p_value: f64,
+}
+
+impl NullScore {
+    /// The values in `NULL_STAT_NAMES` order
+    pub fn stats(&self) -> Vec<f64> {
+        vec![self.z_score, self.p_value]
+    }
+}
+
+/// Score a feature against `shuffles` shuffles of a sequence
+///
+/// Each shuffle keeps the counts of kmers of length `k`, 2 for the usual
+/// dinucleotide shuffle, and the shuffles are seeded from `seed` so the
+/// scores are reproducible. The p-value is `(1 + extreme) / (1 + shuffles)`,
+/// so it's never zero.
+pub fn null_score<F: Fn(&str) -> f64>(
+    clean_seq: &str,
+    feature: F,
+    tail: Tail,
+    shuffles: usize,
+    k: usize,
+    seed: u64,
+) -> NullScore {
+    let value = feature(clean_seq);
+    null_score_of(value, clean_seq, feature, tail, shuffles, k, seed)
+}
+
+/// Score a feature already computed on a sequence as `value` against
+/// shuffles of it, as `null_score`
+///
+/// This saves computing an expensive feature, like the MFE, twice.
+pub fn null_score_of<F: Fn(&str) -> f64>(
+    value: f64,
+    clean_seq: &str,
+    feature: F,
+    tail: Tail,
+    shuffles: usize,
+    k: usize,
+    seed: u64,
+) -> NullScore {
+    let null: Vec<f64> = (0..shuffles as u64)
+        .map(|idx| feature(&kmer_shuffle(clean_seq, k, seed.wrapping_add(idx))))
+        .collect();
+
+    let count = null.len() as f64;
+    let mean = null.iter().sum::<f64>() / count;
+    let sd = (null.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (count - 1.0)).sqrt();
+    let extreme = null
+        .iter()
+        .filter(|x| match tail {
+            Tail::Lower => **x <= value,
+            Tail::Upper => **x >= value,
+        })
+        .count();
+
+    NullScore {
+        value,
+        mean,
+        sd,
+        z_score: if sd > 0.0 {
+            (value - mean) / sd
+        } else {
+            f64::NAN
+        },
+        p_value: (1 + extreme) as f64 / (1 + shuffles) as f64,
+    }
+}
+
+/// Get the column names for a feature's null scores, like `mfe_z_score`
+pub fn null_score_names(feature: &str) -> Vec<String> {
+    NULL_STAT_NAMES
+        .iter()
+        .map(|stat| format!("{}_{}", feature, stat))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::stats::counts::gc_content;
+    use crate::stats::null::{self, Tail};
+    use std::cell::Cell;
+
+    #[test]
+    fn test_invariant_feature() {
+        // Shuffling keeps the composition, so GC content never changes
+        let score = null::null_score("GGGAAACCCAUAUGC", gc_content, Tail::Upper, 20, 2, 1);
+        assert_eq!(score.mean, score.value);
+        assert_eq!(score.sd, 0.0);
+        assert!(score.z_score.is_nan());
+        assert_eq!(score.p_value, 1.0);
+    }
+
+    #[test]
+    fn test_ordered_sequence() {
+        // With every C at the end, the first C comes later than in almost
+        // any shuffle
+        let first_c = |seq: &str| seq.find('C').unwrap() as f64;
+        let seq = "AGAGAGAGAGACUCUCUCUCUC";
+        let score = null::null_score(seq, first_c, Tail::Upper, 99, 1, 5);
+        assert!(score.z_score > 2.0);
+        assert!(score.p_value < 0.05);
+        assert_eq!(score, null::null_score(seq, first_c, Tail::Upper, 99, 1, 5));
+        assert_eq!(score.stats().len(), null::NULL_STAT_NAMES.len());
+        assert_eq!(null::null_score_names("mfe")[0], "mfe_z_score");
+
+        // Given the value, only the shuffles are scored
+        let calls = Cell::new(0);
+        let counted = |seq: &str| {
+            calls.set(calls.get() + 1);
+            first_c(seq)
+        };
+        let value = first_c(seq);
+        let given = null::null_score_of(value, seq, counted, Tail::Upper, 99, 1, 5);
+        assert_eq!(given, score);
+        assert_eq!(calls.get(), 99);
+    }
+}
@@ -0,0 +1,465 @@
+//! Pseudo k-tuple nucleotide composition (PseKNC)
+//!
+//! PseKNC extends the plain kmer composition with a set of sequence order
+//! correlation factors, computed from physicochemical properties of the
+//! dinucleotide steps along the sequence. This follows Chen et al. (2014)
+//! "PseKNC: A flexible web server for generating pseudo K-tuple nucleotide
+//! composition", which is also the formulation used by MathFeature.
+//!
+//! There are two flavours:
+//! - Type I (parallel correlation) gives one correlation factor per lag,
+//!   averaging the squared property differences over all properties
+//! - Type II (series correlation) gives one correlation factor per lag per
+//!   property, from the product of the property values
+use crate::stats::composition::{base_index, kmer_counts, kmer_names, OTHER};
+use anyhow::{anyhow, Result};
+use std::io::BufRead;
+use std::str::FromStr;
+
+/// Dinucleotides in the column order used by `PropertyTable`
+pub const DINUCLEOTIDES: [&str; 16] = [
+    "AA", "AC", "AG", "AU", "CA", "CC", "CG", "CU", "GA", "GC", "GG", "GU", "UA", "UC", "UG", "UU",
+];
+
+/// The built-in RNA dinucleotide properties, as `(name, values)`
+///
+/// Twist, tilt, roll, shift, slide and rise are the RNA step parameters
+/// used with PseKNC in the iRNA family of papers. Free energy is the Turner
+/// 2004 Watson-Crick stacking energy in kcal/mol for the step paired with its
+/// complement.
+pub const RNA_PROPERTIES: [(&str, [f64; 16]); 7] = [
+    (
+        "twist",
+        [
+            31.0, 32.0, 30.0, 33.0, 31.0, 32.0, 27.0, 30.0, 32.0, 35.0, 32.0, 32.0, 32.0, 32.0,
+            31.0, 31.0,
+        ],
+    ),
+    (
+        "tilt",
+        [
+            -0.8, 0.8, 0.5, 1.1, 1.0, 0.3, -0.1, 0.5, 1.3, 0.0, 0.3, 0.8, -0.2, 1.3, 1.0, -0.8,
+        ],
+    ),
+    (
+        "roll",
+        [
+            7.0, 4.8, 8.5, 7.1, 9.9, 8.7, 12.1, 8.5, 9.4, 6.1, 8.7, 4.8, 10.7, 9.4, 9.9, 7.0,
+        ],
+    ),
+    (
+        "shift",
+        [
+            -0.08, 0.23, -0.04, -0.06, 0.11, -0.01, 0.30, -0.04, 0.07, 0.07, -0.01, 0.23, -0.02,
+            0.07, 0.11, -0.08,
+        ],
+    ),
+    (
+        "slide",
+        [
+            -1.27, -1.43, -1.50, -1.36, -1.46, -1.78, -1.89, -1.50, -1.70, -1.39, -1.78, -1.43,
+            -1.45, -1.70, -1.46, -1.27,
+        ],
+    ),
+    (
+        "rise",
+        [
+            3.18, 3.24, 3.30, 3.24, 3.09, 3.32, 3.30, 3.30, 3.38, 3.22, 3.32, 3.24, 3.26, 3.38,
+            3.09, 3.18,
+        ],
+    ),
+    (
+        "free_energy",
+        [
+            -0.93, -2.24, -2.08, -1.10, -2.11, -3.26, -2.36, -2.08, -2.35, -3.42, -3.26, -2.24,
+            -1.33, -2.35, -2.11, -0.93,
+        ],
+    ),
+];
+
+/// A table of dinucleotide physicochemical property values
+///
+/// Each property has one value per dinucleotide, stored in `DINUCLEOTIDES`
+/// order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropertyTable {
+    pub names: Vec<String>,
+    pub values: Vec<[f64; 16]>,
+}
+
+impl PropertyTable {
+    /// The built-in RNA property table, see `RNA_PROPERTIES`
+    pub fn rna() -> PropertyTable {
+        PropertyTable {
+            names: RNA_PROPERTIES
+                .iter()
+                .map(|(name, _)| name.to_string())
+                .collect(),
+            values: RNA_PROPERTIES.iter().map(|(_, values)| *values).collect(),
+        }
+    }
+
+    /// Read a property table from CSV
+    ///
+    /// The header row names the dinucleotide columns, in any order, after a
+    /// first column holding the property name. Each following row is one
+    /// property, e.g.
+    ///
+    /// ```text
+    /// property,AA,AC,AG,AU,CA,CC,CG,CU,GA,GC,GG,GU,UA,UC,UG,UU
+    /// twist,31,32,30,33,31,32,27,30,32,35,32,32,32,32,31,31
+    /// ```
+    ///
+    /// T is accepted in place of U, and blank lines are skipped.
+    pub fn from_csv<R: BufRead>(reader: R) -> Result<PropertyTable> {
+        let mut lines = reader.lines();
+        let header = lines
+            .next()
+            .ok_or_else(|| anyhow!("Property table is empty"))??;
+
+        let mut columns = Vec::with_capacity(16);
+        for field in header.split(',').skip(1) {
+            let dinucleotide = field.trim().to_uppercase().replace('T', "U");
+            let column = DINUCLEOTIDES
+                .iter()
+                .position(|x| *x == dinucleotide)
+                .ok_or_else(|| anyhow!("Unknown dinucleotide column {}", field))?;
+            columns.push(column);
+        }
+        let mut seen = columns.clone();
+        seen.sort_unstable();
+        seen.dedup();
+        if seen.len() != 16 || columns.len() != 16 {
+            return Err(anyhow!("Property table needs one column per dinucleotide"));
+        }
+
+        let mut table = PropertyTable {
+            names: Vec::new(),
+            values: Vec::new(),
+        };
+        for line in lines {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let mut fields = line.split(',');
+            let name = fields.next().unwrap_or_default().trim().to_owned();
+            let mut values = [f64::NAN; 16];
+            let mut num_values = 0;
+            for (column, field) in columns.iter().zip(fields) {
+                values[*column] = field.trim().parse()?;
+                num_values += 1;
+            }
+            if num_values != 16 {
+                return Err(anyhow!("Property {} does not have 16 values", name));
+            }
+            table.names.push(name);
+            table.values.push(values);
+        }
+
+        Ok(table)
+    }
+
+    /// Keep only the named properties, in the order given
+    pub fn select(&self, names: &[&str]) -> Result<PropertyTable> {
+        let mut table = PropertyTable {
+            names: Vec::with_capacity(names.len()),
+            values: Vec::with_capacity(names.len()),
+        };
+        for name in names {
+            let idx = self
+                .names
+                .iter()
+                .position(|x| x == name)
+                .ok_or_else(|| anyhow!("Unknown property {}", name))?;
+            table.names.push(self.names[idx].clone());
+            table.values.push(self.values[idx]);
+        }
+        Ok(table)
+    }
+
+    /// Standardise each property to zero mean and unit variance
+    ///
+    /// PseKNC works on standardised values so that properties measured in
+    /// different units contribute equally to the correlation factors.
+    pub fn standardised(&self) -> PropertyTable {
+        let values = self
+            .values
+            .iter()
+            .map(|values| {
+                let mean = values.iter().sum::<f64>() / 16.0;
+                let sd = (values.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / 16.0).sqrt();
+                values.map(|x| if sd > 0.0 { (x - mean) / sd } else { 0.0 })
+            })
+            .collect();
+
+        PropertyTable {
+            names: self.names.clone(),
+            values,
+        }
+    }
+}
+
+/// Which set of correlation factors to use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PseKncType {
+    /// Type I, parallel correlation
+    Parallel,
+    /// Type II, series correlation
+    Series,
+}
+
+impl PseKncType {
+    /// The name used in configs, `parallel` or `series`
+    pub fn name(&self) -> &'static str {
+        match self {
+            PseKncType::Parallel => "parallel",
+            PseKncType::Series => "series",
+        }
+    }
+}
+
+impl FromStr for PseKncType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        [PseKncType::Parallel, PseKncType::Series]
+            .into_iter()
+            .find(|x| x.name() == s.to_lowercase())
+            .ok_or_else(|| anyhow!("Unknown PseKNC type {}, use parallel or series", s))
+    }
+}
+
+/// Parameters for `pseknc`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PseKncParams {
+    /// Length of the k-tuples in the composition part
+    pub k: usize,
+    /// Number of lags (λ) to compute correlation factors for
+    pub lambda: usize,
+    /// Weight (w) of the correlation factors against the composition
+    pub weight: f64,
+    pub kind: PseKncType,
+}
+
+impl Default for PseKncParams {
+    fn default() -> Self {
+        PseKncParams {
+            k: 2,
+            lambda: 3,
+            weight: 0.5,
+            kind: PseKncType::Parallel,
+        }
+    }
+}
+
+/// Get the dinucleotide index at every step along the sequence
+///
+/// Steps that touch a non nucleotide character are `None`.
+pub fn dinucleotide_steps(clean_seq: &str) -> Vec<Option<usize>> {
+    let indices: Vec<usize> = clean_seq.bytes().map(base_index).collect();
+    indices
+        .windows(2)
+        .map(|pair| {
+            if pair[0] == OTHER || pair[1] == OTHER {
+                None
+            } else {
+                Some(4 * pair[0] + pair[1])
+            }
+        })
+        .collect()
+}
+
+/// Calculate the correlation factors for lags `1..=lambda`
+///
+/// For type I the result has one value per lag. For type II it has one
+/// value per lag per property, grouped by lag. The property table should
+/// already be standardised.
+pub fn correlation_factors(
+    clean_seq: &str,
+    table: &PropertyTable,
+    lambda: usize,
+    kind: PseKncType,
+) -> Result<Vec<f64>> {
+    let steps = dinucleotide_steps(clean_seq);
+    if steps.len() <= lambda {
+        return Err(anyhow!(
+            "Sequence of length {} is too short for lambda {}",
+            clean_seq.len(),
+            lambda
+        ));
+    }
+
+    let mut factors = Vec::new();
+    for lag in 1..=lambda {
+        let pairs: Vec<(usize, usize)> = steps
+            .iter()
+            .zip(steps.iter().skip(lag))
+            .filter_map(|(x, y)| Some(((*x)?, (*y)?)))
+            .collect();
+        let num_pairs = (steps.len() - lag) as f64;
+
+        match kind {
+            PseKncType::Parallel => {
+                let theta = pairs
+                    .iter()
+                    .map(|(x, y)| {
+                        table
+                            .values
+                            .iter()
+                            .map(|values| (values[*x] - values[*y]).powi(2))
+                            .sum::<f64>()
+                            / table.values.len() as f64
+                    })
+                    .sum::<f64>()
+                    / num_pairs;
+                factors.push(theta);
+            }
+            PseKncType::Series => {
+                for values in &table.values {
+                    let tau = pairs
+                        .iter()
+                        .map(|(x, y)| values[*x] * values[*y])
+                        .sum::<f64>()
+                        / num_pairs;
+                    factors.push(tau);
+                }
+            }
+        }
+    }
+
+    Ok(factors)
+}
+
+/// Calculate pseudo k-tuple nucleotide composition
+///
+/// The first `4^k` values are the k-tuple frequencies and the rest are the
+/// correlation factors, all normalised together so that
+///
+/// ```text
+/// d_u = f_u / (sum(f) + w * sum(theta))      for the k-tuples
+/// d_u = w * theta_j / (sum(f) + w * sum(theta))  for the correlation factors
+/// ```
+///
+/// The property table is standardised before use, so any table can be
+/// passed in as-is. Errors if the sequence is too short for the lags asked
+/// for.
+pub fn pseknc(clean_seq: &str, table: &PropertyTable, params: &PseKncParams) -> Result<Vec<f64>> {
+    let counts = kmer_counts(clean_seq, params.k);
+    let total = counts.iter().sum::<u64>() as f64;
+    let frequencies = counts
+        .iter()
+        .map(|x| if total > 0.0 { *x as f64 / total } else { 0.0 });
+
+    let factors =
+        correlation_factors(clean_seq, &table.standardised(), params.lambda, params.kind)?;
+
+    let denominator = 1.0 + params.weight * factors.iter().sum::<f64>();
+
+    Ok(frequencies
+        .chain(factors.iter().map(|x| params.weight * x))
+        .map(|x| x / denominator)
+        .collect())
+}
+
+/// Get the column names for `pseknc`, in output order
+///
+/// k-tuples are named like `pseknc_AC`, type I factors `pseknc_lambda_1`
+/// and type II factors `pseknc_lambda_1_twist`.
+pub fn pseknc_names(table: &PropertyTable, params: &PseKncParams) -> Vec<String> {
+    let mut names: Vec<String> = kmer_names(params.k)
+        .iter()
+        .map(|kmer| format!("pseknc_{}", kmer))
+        .collect();
+
+    for lag in 1..=params.lambda {
+        match params.kind {
+            PseKncType::Parallel => names.push(format!("pseknc_lambda_{}", lag)),
+            PseKncType::Series => {
+                for name in &table.names {
+                    names.push(format!("pseknc_lambda_{}_{}", lag, name));
+                }
+            }
+        }
+    }
+
+    names
+}
+
+#[cfg(test)]
+mod test {
+    use crate::stats::pseknc::{self, PropertyTable, PseKncParams, PseKncType};
+
+    #[test]
+    fn test_builtin_table_symmetry() {
+        // Every step should match the step on the complementary strand
+        let table = PropertyTable::rna();
+        let complement = [15, 11, 7, 3, 14, 10, 6, 2, 13, 9, 5, 1, 12, 8, 4, 0];
+        for (name, values) in table.names.iter().zip(&table.values) {
+            for (idx, comp_idx) in complement.iter().enumerate() {
+                assert_eq!(values[idx], values[*comp_idx], "{} {}", name, idx);
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_csv() {
+        let csv = "property,UU,UG,UC,UA,GU,GG,GC,GA,CU,CG,CC,CA,AU,AG,AC,AA\n\
+                   test,15,14,13,12,11,10,9,8,7,6,5,4,3,2,1,0\n\n";
+        let table = PropertyTable::from_csv(csv.as_bytes()).unwrap();
+
+        assert_eq!(table.names, ["test"]);
+        assert_eq!(table.values[0][0], 0.0);
+        assert_eq!(table.values[0][15], 15.0);
+
+        let bad = "property,AA,AC\ntest,1,2\n";
+        assert!(PropertyTable::from_csv(bad.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_pseknc_type_1() {
+        let table = PropertyTable::rna();
+        let params = PseKncParams::default();
+        let res = pseknc::pseknc("GGCAUGGAGUCCUGUGGUAUCCACGAGAUCACC", &table, &params).unwrap();
+        let names = pseknc::pseknc_names(&table, &params);
+
+        assert_eq!(res.len(), 16 + 3);
+        assert_eq!(names.len(), res.len());
+        assert_eq!(names[16], "pseknc_lambda_1");
+        assert!((res.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+        assert!(res.iter().all(|x| *x >= 0.0));
+    }
+
+    #[test]
+    fn test_pseknc_type_2() {
+        let table = PropertyTable::rna().select(&["twist", "rise"]).unwrap();
+        let params = PseKncParams {
+            k: 1,
+            lambda: 2,
+            weight: 0.1,
+            kind: PseKncType::Series,
+        };
+        let res = pseknc::pseknc("GGCAUGGAGUCCUGUGG", &table, &params).unwrap();
+        let names = pseknc::pseknc_names(&table, &params);
+
+        assert_eq!(res.len(), 4 + 2 * 2);
+        assert_eq!(names[5], "pseknc_lambda_1_rise");
+    }
+
+    #[test]
+    fn test_pseknc_too_short() {
+        let table = PropertyTable::rna();
+        let params = PseKncParams::default();
+        assert!(pseknc::pseknc("ACGU", &table, &params).is_err());
+    }
+
+    #[test]
+    fn test_homopolymer_has_no_correlation() {
+        let table = PropertyTable::rna();
+        let params = PseKncParams::default();
+        let res = pseknc::pseknc("AAAAAAAAAA", &table, &params).unwrap();
+
+        assert_eq!(res[0], 1.0);
+        assert!(res[16..].iter().all(|x| *x == 0.0));
+    }
+}
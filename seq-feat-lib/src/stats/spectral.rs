@@ -0,0 +1,229 @@
+//! Power spectral density and fixed width spectral fingerprints
+//!
+//! Sequences vary in length, so their raw spectra have different numbers of
+//! frequency bins and can't be stacked into a feature matrix. This module
+//! computes a windowed power spectral density (PSD) for each sequence and
+//! then averages it into a fixed number of equal width frequency bands, so
+//! every sequence gets a fingerprint of the same size covering `0..=0.5`
+//! cycles per base.
+use crate::stats::fourier::fft_sequence;
+use crate::stats::mapping::NumericMapping;
+use anyhow::{anyhow, Result};
+use std::f64::consts::PI;
+use std::str::FromStr;
+
+/// Window functions to taper a signal with before transforming it
+///
+/// Windows are periodic (the `fftbins=True` form in scipy), which is the
+/// appropriate choice for spectral estimation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Window {
+    Rectangular,
+    #[default]
+    Hann,
+    Hamming,
+    Blackman,
+}
+
+impl Window {
+    /// The name used on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            Window::Rectangular => "rectangular",
+            Window::Hann => "hann",
+            Window::Hamming => "hamming",
+            Window::Blackman => "blackman",
+        }
+    }
+
+    /// Get the window coefficients for a signal of length `n`
+    pub fn coefficients(&self, n: usize) -> Vec<f64> {
+        (0..n)
+            .map(|idx| {
+                let x = 2.0 * PI * idx as f64 / n as f64;
+                match self {
+                    Window::Rectangular => 1.0,
+                    Window::Hann => 0.5 - 0.5 * x.cos(),
+                    Window::Hamming => 0.54 - 0.46 * x.cos(),
+                    Window::Blackman => 0.42 - 0.5 * x.cos() + 0.08 * (2.0 * x).cos(),
+                }
+            })
+            .collect()
+    }
+}
+
+impl FromStr for Window {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        [
+            Window::Rectangular,
+            Window::Hann,
+            Window::Hamming,
+            Window::Blackman,
+        ]
+        .into_iter()
+        .find(|x| x.name() == s.to_lowercase())
+        .ok_or_else(|| anyhow!("Unknown window function {}", s))
+    }
+}
+
+/// Calculate the one-sided power spectral density of a signal
+///
+/// The signal has its mean removed, so the DC bin doesn't swamp everything
+/// else, and is then tapered by the window. The periodogram is normalised by
+/// the window power and doubled for every bin except DC and Nyquist, so with
+/// a rectangular window the PSD sums to the signal's power about its mean.
+/// Bin `k` is at frequency `k / n` cycles per base, for `k` in `0..=n/2`.
+pub fn psd(signal: &[f64], window: Window) -> Result<Vec<f64>> {
+    let n = signal.len();
+    if n == 0 {
+        return Ok(Vec::new());
+    }
+
+    let mean = signal.iter().sum::<f64>() / n as f64;
+    let coefficients = window.coefficients(n);
+    let window_power: f64 = coefficients.iter().map(|x| x * x).sum();
+    let tapered: Vec<f64> = signal
+        .iter()
+        .zip(&coefficients)
+        .map(|(x, w)| (x - mean) * w)
+        .collect();
+
+    let spectrum = fft_sequence(&tapered).map_err(|e| anyhow!("{}", e))?;
+    Ok(spectrum
+        .iter()
+        .enumerate()
+        .map(|(k, x)| {
+            let one_sided = if k == 0 || 2 * k == n { 1.0 } else { 2.0 };
+            one_sided * x.norm_sqr() / window_power
+        })
+        .collect())
+}
+
+/// Calculate the PSD of a sequence, summed over the channels of a mapping
+///
+/// Every channel is treated as a separate real signal, including the real
+/// and imaginary parts of complex mappings.
+pub fn sequence_psd(
+    clean_seq: &str,
+    mapping: &dyn NumericMapping,
+    window: Window,
+) -> Result<Vec<f64>> {
+    let mut total = vec![0.0; clean_seq.len() / 2 + 1];
+    for channel in mapping.map(clean_seq) {
+        for (x, y) in total.iter_mut().zip(psd(&channel, window)?) {
+            *x += y;
+        }
+    }
+    Ok(total)
+}
+
+/// Average a one-sided PSD into a fixed number of frequency bands
+///
+/// The range `0..=0.5` cycles per base is split into `bins` equal bands, and
+/// each band is the mean of the PSD bins whose frequency falls in it. Short
+/// sequences can have bands with no PSD bins in, which are filled by linear
+/// interpolation of the PSD at the centre of the band instead.
+pub fn bin_psd(psd: &[f64], bins: usize) -> Vec<f64> {
+    if psd.is_empty() || bins == 0 {
+        return vec![0.0; bins];
+    }
+    // Number of samples in the signal the PSD came from, give or take one
+    // for odd lengths, which only changes where the last bin sits
+    let n = 2 * (psd.len() - 1);
+    let frequency = |k: usize| if n == 0 { 0.0 } else { k as f64 / n as f64 };
+
+    let mut sums = vec![0.0; bins];
+    let mut counts = vec![0usize; bins];
+    for (k, x) in psd.iter().enumerate() {
+        let band = ((frequency(k) / 0.5 * bins as f64) as usize).min(bins - 1);
+        sums[band] += x;
+        counts[band] += 1;
+    }
+
+    (0..bins)
+        .map(|band| {
+            if counts[band] > 0 {
+                return sums[band] / counts[band] as f64;
+            }
+            // Interpolate between the PSD bins either side of the band centre
+            let centre = (band as f64 + 0.5) * 0.5 / bins as f64;
+            let position = (centre * n as f64).min((psd.len() - 1) as f64);
+            let lower = position.floor() as usize;
+            let upper = (lower + 1).min(psd.len() - 1);
+            psd[lower] + (psd[upper] - psd[lower]) * (position - lower as f64)
+        })
+        .collect()
+}
+
+/// Calculate a fixed width spectral fingerprint for a sequence
+///
+/// This is the windowed PSD of the mapped sequence, averaged into `bins`
+/// frequency bands by `bin_psd`.
+pub fn spectral_fingerprint(
+    clean_seq: &str,
+    mapping: &dyn NumericMapping,
+    window: Window,
+    bins: usize,
+) -> Result<Vec<f64>> {
+    Ok(bin_psd(&sequence_psd(clean_seq, mapping, window)?, bins))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::stats::mapping::Mapping;
+    use crate::stats::spectral::{self, Window};
+
+    #[test]
+    fn test_windows() {
+        let hann = Window::Hann.coefficients(4);
+        let expected = [0.0, 0.5, 1.0, 0.5];
+        for (x, y) in hann.iter().zip(expected) {
+            assert!((x - y).abs() < 1e-12);
+        }
+        assert!((Window::Hamming.coefficients(4)[0] - 0.08).abs() < 1e-12);
+        assert!(Window::Blackman.coefficients(8)[0].abs() < 1e-12);
+        assert_eq!("Hamming".parse::<Window>().unwrap(), Window::Hamming);
+    }
+
+    #[test]
+    fn test_psd_parseval() {
+        // With a rectangular window the PSD sums to n times the variance
+        let signal = [1.0, 3.0, -2.0, 0.5, 4.0, -1.0, 2.0, 0.0];
+        let psd = spectral::psd(&signal, Window::Rectangular).unwrap();
+        let mean = signal.iter().sum::<f64>() / 8.0;
+        let variance = signal.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / 8.0;
+
+        assert_eq!(psd.len(), 5);
+        assert!((psd.iter().sum::<f64>() / 8.0 - variance).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_period3_peak() {
+        // A codon repeat puts its power at 1/3 cycles per base, which is in
+        // the band 0.3..0.4 with 5 bands
+        let seq = "AUG".repeat(40);
+        let fingerprint =
+            spectral::spectral_fingerprint(&seq, &Mapping::Binary, Window::Hann, 5).unwrap();
+
+        assert_eq!(fingerprint.len(), 5);
+        let peak = fingerprint
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .unwrap()
+            .0;
+        assert_eq!(peak, 3);
+    }
+
+    #[test]
+    fn test_bin_short_psd() {
+        // Fewer PSD bins than bands means interpolating
+        let binned = spectral::bin_psd(&[0.0, 2.0, 4.0], 8);
+        assert_eq!(binned.len(), 8);
+        assert!(binned.iter().all(|x| x.is_finite()));
+        assert_eq!(binned[0], 0.0);
+        assert!(binned.windows(2).all(|x| x[0] <= x[1]));
+    }
+}
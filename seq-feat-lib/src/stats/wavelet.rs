@@ -0,0 +1,330 @@
+//! Discrete wavelet transform features
+//!
+//! Fourier features describe which periodicities are present but not where
+//! they are. A multi-level discrete wavelet transform (DWT) splits a numeric
+//! signal into detail coefficients at successively coarser scales, each of
+//! which is still localised along the sequence, plus a final approximation.
+//! The features here summarise the detail coefficients at each level.
+//!
+//! The transform is the standard orthogonal filter bank with periodic
+//! boundary handling, so the total energy of the coefficients matches the
+//! energy of the signal as long as every level has an even length. Odd
+//! lengths are padded (see `dwt`), and the repeated sample adds its energy
+//! to the coefficients.
+use crate::stats::mapping::NumericMapping;
+use anyhow::{anyhow, Result};
+use std::str::FromStr;
+
+/// Stats reported for the detail coefficients at each level
+pub const LEVEL_STAT_NAMES: [&str; 7] = [
+    "energy",
+    "relative_energy",
+    "entropy",
+    "mean",
+    "sd",
+    "min",
+    "max",
+];
+
+/// The wavelets available, named as in PyWavelets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Wavelet {
+    #[default]
+    Haar,
+    /// Daubechies with two vanishing moments, the 4 tap "D4" wavelet
+    Db2,
+    /// Daubechies with four vanishing moments, 8 taps
+    Db4,
+}
+
+impl Wavelet {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Wavelet::Haar => "haar",
+            Wavelet::Db2 => "db2",
+            Wavelet::Db4 => "db4",
+        }
+    }
+
+    /// The low pass (scaling) filter
+    pub fn scaling_filter(&self) -> &'static [f64] {
+        match self {
+            Wavelet::Haar => &[
+                std::f64::consts::FRAC_1_SQRT_2,
+                std::f64::consts::FRAC_1_SQRT_2,
+            ],
+            Wavelet::Db2 => &[
+                0.48296291314469025,
+                0.836516303737469,
+                0.22414386804185735,
+                -0.12940952255092145,
+            ],
+            Wavelet::Db4 => &[
+                0.2303778133088965,
+                0.7148465705529157,
+                0.6308807679298589,
+                -0.027983769416859854,
+                -0.18703481171909309,
+                0.030841381835560764,
+                0.0328830116668852,
+                -0.010597401785069032,
+            ],
+        }
+    }
+
+    /// The high pass (wavelet) filter, the quadrature mirror of the scaling
+    /// filter
+    pub fn wavelet_filter(&self) -> Vec<f64> {
+        let scaling = self.scaling_filter();
+        scaling
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(k, x)| if k % 2 == 0 { *x } else { -x })
+            .collect()
+    }
+}
+
+impl FromStr for Wavelet {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        [Wavelet::Haar, Wavelet::Db2, Wavelet::Db4]
+            .into_iter()
+            .find(|x| x.name() == s.to_lowercase())
+            .ok_or_else(|| anyhow!("Unknown wavelet {}", s))
+    }
+}
+
+/// The result of a multi-level DWT
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decomposition {
+    /// Detail coefficients, finest level first
+    pub details: Vec<Vec<f64>>,
+    /// Approximation coefficients after the last level
+    pub approximation: Vec<f64>,
+}
+
+/// Run a single level of the DWT
+///
+/// Returns the approximation and detail coefficients, each half the length
+/// of the signal rounded up. Odd length signals have their last sample
+/// repeated on the end first, so the coefficients of an odd length signal
+/// carry slightly more energy than the signal itself.
+pub fn dwt(signal: &[f64], wavelet: Wavelet) -> (Vec<f64>, Vec<f64>) {
+    let mut padded = signal.to_vec();
+    if padded.len() % 2 == 1 {
+        padded.push(*signal.last().unwrap());
+    }
+    let n = padded.len();
+    let low = wavelet.scaling_filter();
+    let high = wavelet.wavelet_filter();
+
+    let convolve = |filter: &[f64]| -> Vec<f64> {
+        (0..n / 2)
+            .map(|idx| {
+                filter
+                    .iter()
+                    .enumerate()
+                    .map(|(k, h)| h * padded[(2 * idx + k) % n])
+                    .sum()
+            })
+            .collect()
+    };
+
+    (convolve(low), convolve(&high))
+}
+
+/// Run a multi-level DWT
+///
+/// Stops early if the approximation gets shorter than the filter, so the
+/// decomposition can have fewer than `levels` levels for short signals.
+pub fn wavedec(signal: &[f64], wavelet: Wavelet, levels: usize) -> Decomposition {
+    let mut approximation = signal.to_vec();
+    let mut details = Vec::with_capacity(levels);
+
+    while details.len() < levels && approximation.len() >= wavelet.scaling_filter().len() {
+        let (next_approximation, detail) = dwt(&approximation, wavelet);
+        details.push(detail);
+        approximation = next_approximation;
+    }
+
+    Decomposition {
+        details,
+        approximation,
+    }
+}
+
+/// Summarise one level of detail coefficients, see `LEVEL_STAT_NAMES`
+fn level_stats(coefficients: &[f64], total_energy: f64) -> Vec<f64> {
+    let n = coefficients.len() as f64;
+    let energy: f64 = coefficients.iter().map(|x| x * x).sum();
+    let entropy = -coefficients
+        .iter()
+        .map(|x| x * x / energy)
+        .filter(|p| *p > 0.0)
+        .map(|p| p * p.log2())
+        .sum::<f64>();
+    let mean = coefficients.iter().sum::<f64>() / n;
+    let sd = (coefficients.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / n).sqrt();
+
+    vec![
+        energy,
+        if total_energy > 0.0 {
+            energy / total_energy
+        } else {
+            0.0
+        },
+        entropy,
+        mean,
+        sd,
+        coefficients.iter().cloned().fold(f64::INFINITY, f64::min),
+        coefficients
+            .iter()
+            .cloned()
+            .fold(f64::NEG_INFINITY, f64::max),
+    ]
+}
+
+/// Calculate wavelet features of a sequence under a numeric mapping
+///
+/// Each channel of the mapping is decomposed separately, and the detail
+/// coefficients of all channels are pooled at each level. For each level the
+/// stats in `LEVEL_STAT_NAMES` are reported, where relative energy is the
+/// fraction of the total coefficient energy (details and approximation) at
+/// that level, and entropy is the Shannon entropy of the normalised squared
+/// coefficients. The energy of the final approximation comes last.
+///
+/// Levels that the sequence is too short for are NaN, so the output always
+/// has `7 * levels + 1` values, matching `wavelet_feature_names`.
+pub fn wavelet_features(
+    clean_seq: &str,
+    mapping: &dyn NumericMapping,
+    wavelet: Wavelet,
+    levels: usize,
+) -> Vec<f64> {
+    let decompositions: Vec<Decomposition> = mapping
+        .map(clean_seq)
+        .iter()
+        .map(|channel| wavedec(channel, wavelet, levels))
+        .collect();
+    let num_levels = decompositions.first().map_or(0, |x| x.details.len());
+
+    let approximation: Vec<f64> = decompositions
+        .iter()
+        .flat_map(|x| x.approximation.iter().cloned())
+        .collect();
+    let approximation_energy: f64 = approximation.iter().map(|x| x * x).sum();
+    let total_energy = approximation_energy
+        + decompositions
+            .iter()
+            .flat_map(|x| x.details.iter().flatten())
+            .map(|x| x * x)
+            .sum::<f64>();
+
+    let mut features = Vec::with_capacity(LEVEL_STAT_NAMES.len() * levels + 1);
+    for level in 0..levels {
+        if level < num_levels {
+            let pooled: Vec<f64> = decompositions
+                .iter()
+                .flat_map(|x| x.details[level].iter().cloned())
+                .collect();
+            features.extend(level_stats(&pooled, total_energy));
+        } else {
+            features.extend([f64::NAN; LEVEL_STAT_NAMES.len()]);
+        }
+    }
+    features.push(approximation_energy);
+
+    features
+}
+
+/// Get the column names for `wavelet_features`, in output order
+///
+/// Names look like `wavelet_db4_eiip_l1_energy`, with level 1 the finest.
+pub fn wavelet_feature_names(
+    mapping: &dyn NumericMapping,
+    wavelet: Wavelet,
+    levels: usize,
+) -> Vec<String> {
+    let prefix = format!("wavelet_{}_{}", wavelet.name(), mapping.name());
+    let mut names = Vec::with_capacity(LEVEL_STAT_NAMES.len() * levels + 1);
+    for level in 1..=levels {
+        for stat in LEVEL_STAT_NAMES {
+            names.push(format!("{}_l{}_{}", prefix, level, stat));
+        }
+    }
+    names.push(format!("{}_approximation_energy", prefix));
+    names
+}
+
+#[cfg(test)]
+mod test {
+    use crate::stats::mapping::Mapping;
+    use crate::stats::wavelet::{self, Wavelet};
+
+    #[test]
+    fn test_haar_step() {
+        let (approximation, detail) = wavelet::dwt(&[4.0, 2.0, 5.0, 5.0], Wavelet::Haar);
+        let root2 = std::f64::consts::SQRT_2;
+
+        assert!((approximation[0] - 6.0 / root2).abs() < 1e-12);
+        assert!((approximation[1] - 10.0 / root2).abs() < 1e-12);
+        assert!((detail[0] - 2.0 / root2).abs() < 1e-12);
+        assert!(detail[1].abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_filters_are_orthonormal() {
+        for wavelet in [Wavelet::Haar, Wavelet::Db2, Wavelet::Db4] {
+            let low = wavelet.scaling_filter();
+            let high = wavelet.wavelet_filter();
+            let norm: f64 = low.iter().map(|x| x * x).sum();
+            let dot: f64 = low.iter().zip(&high).map(|(x, y)| x * y).sum();
+
+            assert!((norm - 1.0).abs() < 1e-9, "{}", wavelet.name());
+            assert!(dot.abs() < 1e-9, "{}", wavelet.name());
+            assert!((low.iter().sum::<f64>() - std::f64::consts::SQRT_2).abs() < 1e-9);
+            assert!(high.iter().sum::<f64>().abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_energy_preserved() {
+        let signal: Vec<f64> = (0..64).map(|x| ((x * 7) % 11) as f64 - 5.0).collect();
+        let energy: f64 = signal.iter().map(|x| x * x).sum();
+
+        for wavelet in [Wavelet::Haar, Wavelet::Db2, Wavelet::Db4] {
+            let decomposition = wavelet::wavedec(&signal, wavelet, 3);
+            let coefficient_energy: f64 = decomposition
+                .details
+                .iter()
+                .flatten()
+                .chain(&decomposition.approximation)
+                .map(|x| x * x)
+                .sum();
+            assert_eq!(decomposition.details.len(), 3);
+            assert!(
+                (energy - coefficient_energy).abs() < 1e-9,
+                "{}",
+                wavelet.name()
+            );
+        }
+    }
+
+    #[test]
+    fn test_wavelet_features() {
+        let seq = "AUGGCCAUUGUAAGCUAGCUUAGGCAUGCAUGCC";
+        let features = wavelet::wavelet_features(seq, &Mapping::Eiip, Wavelet::Db2, 5);
+        let names = wavelet::wavelet_feature_names(&Mapping::Eiip, Wavelet::Db2, 5);
+
+        assert_eq!(features.len(), names.len());
+        assert_eq!(names[0], "wavelet_db2_eiip_l1_energy");
+        // 34 -> 17 -> 9 -> 5 -> 3 leaves no room for a fifth level
+        assert!(features[..28].iter().all(|x| x.is_finite()));
+        assert!(features[28..35].iter().all(|x| x.is_nan()));
+
+        let relative_energy: f64 = (0..4).map(|level| features[7 * level + 1]).sum();
+        assert!(relative_energy > 0.0 && relative_energy < 1.0);
+    }
+}
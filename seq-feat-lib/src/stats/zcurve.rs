@@ -0,0 +1,227 @@
+//! Z-curve trajectory and parameters
+//!
+//! The Z-curve represents a sequence as a walk in three dimensions, one
+//! coordinate for each of the ways of splitting the four bases into two
+//! pairs: purine/pyrimidine (x), amino/keto (y) and weak/strong hydrogen
+//! bonding (z). See `mapping::z_curve` for the per-position walk.
+//!
+//! The Z-curve parameters used for gene finding, and by iLearnPlus, apply
+//! the same three splits to the base following each prefix, counting the
+//! bases after every mononucleotide, dinucleotide and so on, optionally
+//! separately for each codon position:
+//!
+//! - 9 parameters, phase specific mononucleotide
+//! - 12 parameters, non phase specific dinucleotide
+//! - 36 parameters, phase specific dinucleotide
+//! - 48 parameters, non phase specific trinucleotide
+use crate::stats::composition::{kmer_counts, kmer_names, phased_kmer_counts, A, C, G, U};
+use crate::stats::mapping::z_curve;
+use anyhow::{anyhow, Result};
+use std::str::FromStr;
+use wasm_bindgen::prelude::*;
+
+/// Names of the three Z-curve coordinates
+pub const AXES: [&str; 3] = ["x", "y", "z"];
+
+/// Get the Z-curve trajectory as a flat array
+///
+/// Values are interleaved as `x, y, z` for each position in turn, so the
+/// output is three times the length of the sequence. This is the layout
+/// wanted for plotting, where it can be viewed as a typed array of points.
+#[wasm_bindgen(js_name=z_curve)]
+pub fn trajectory(clean_seq: &str) -> Vec<f64> {
+    let axes = z_curve(clean_seq);
+    (0..clean_seq.len())
+        .flat_map(|idx| axes.iter().map(move |axis| axis[idx]))
+        .collect()
+}
+
+/// The Z-curve parameter sets available
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZCurveParams {
+    /// Phase specific mononucleotide, 9 parameters
+    Mono9,
+    /// Non phase specific dinucleotide, 12 parameters
+    Di12,
+    /// Phase specific dinucleotide, 36 parameters
+    Di36,
+    /// Non phase specific trinucleotide, 48 parameters
+    Tri48,
+}
+
+impl ZCurveParams {
+    /// The number of parameters, which is also the name on the command line
+    pub fn dimension(&self) -> usize {
+        match self {
+            ZCurveParams::Mono9 => 9,
+            ZCurveParams::Di12 => 12,
+            ZCurveParams::Di36 => 36,
+            ZCurveParams::Tri48 => 48,
+        }
+    }
+
+    /// Length of the kmers counted
+    fn k(&self) -> usize {
+        match self {
+            ZCurveParams::Mono9 => 1,
+            ZCurveParams::Di12 | ZCurveParams::Di36 => 2,
+            ZCurveParams::Tri48 => 3,
+        }
+    }
+
+    fn phased(&self) -> bool {
+        matches!(self, ZCurveParams::Mono9 | ZCurveParams::Di36)
+    }
+}
+
+impl FromStr for ZCurveParams {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        [
+            ZCurveParams::Mono9,
+            ZCurveParams::Di12,
+            ZCurveParams::Di36,
+            ZCurveParams::Tri48,
+        ]
+        .into_iter()
+        .find(|x| x.dimension().to_string() == s)
+        .ok_or_else(|| anyhow!("Unknown Z-curve parameter set {}, use 9, 12, 36 or 48", s))
+    }
+}
+
+/// Apply the three Z-curve splits to each group of four kmer counts
+///
+/// Kmers sharing a prefix are adjacent in `kmer_counts` order, so each chunk
+/// of four is the counts of `prefix + A, C, G, U`.
+fn axes(counts: &[u64], total: f64) -> Vec<f64> {
+    counts
+        .chunks_exact(4)
+        .flat_map(|next| {
+            let next = next.iter().map(|x| *x as f64).collect::<Vec<f64>>();
+            [
+                (next[A] + next[G]) - (next[C] + next[U]),
+                (next[A] + next[C]) - (next[G] + next[U]),
+                (next[A] + next[U]) - (next[G] + next[C]),
+            ]
+        })
+        .map(|x| if total > 0.0 { x / total } else { 0.0 })
+        .collect()
+}
+
+/// Calculate a set of Z-curve parameters
+///
+/// Counts are normalised by the total number of kmers in the sequence (over
+/// all phases for the phase specific sets), which for a sequence of length
+/// `L` with no ambiguous bases is `L - k + 1`. Values are grouped by phase,
+/// then prefix, then axis, matching `z_curve_param_names`.
+pub fn z_curve_params(clean_seq: &str, kind: ZCurveParams) -> Vec<f64> {
+    if kind.phased() {
+        let phased = phased_kmer_counts(clean_seq, kind.k());
+        let total = phased.iter().flatten().sum::<u64>() as f64;
+        phased
+            .iter()
+            .flat_map(|counts| axes(counts, total))
+            .collect()
+    } else {
+        let counts = kmer_counts(clean_seq, kind.k());
+        let total = counts.iter().sum::<u64>() as f64;
+        axes(&counts, total)
+    }
+}
+
+/// Get the column names for `z_curve_params`, in output order
+///
+/// Names look like `zcurve36_p1_AC_x` for phase specific sets, where phases
+/// are numbered from 1, and `zcurve48_AC_x` otherwise. The prefix is left out
+/// for the 9 parameter set, giving `zcurve9_p1_x`.
+pub fn z_curve_param_names(kind: ZCurveParams) -> Vec<String> {
+    let prefix = format!("zcurve{}", kind.dimension());
+    let prefixes: Vec<String> = kmer_names(kind.k() - 1)
+        .into_iter()
+        .map(|x| if x.is_empty() { x } else { format!("_{}", x) })
+        .collect();
+    let phases: Vec<String> = if kind.phased() {
+        (1..=3).map(|x| format!("_p{}", x)).collect()
+    } else {
+        vec![String::new()]
+    };
+
+    let mut names = Vec::with_capacity(kind.dimension());
+    for phase in &phases {
+        for kmer in &prefixes {
+            for axis in AXES {
+                names.push(format!("{}{}{}_{}", prefix, phase, kmer, axis));
+            }
+        }
+    }
+    names
+}
+
+#[cfg(test)]
+mod test {
+    use crate::stats::zcurve::{self, ZCurveParams};
+
+    #[test]
+    fn test_trajectory() {
+        assert_eq!(
+            zcurve::trajectory("AGC"),
+            [1.0, 1.0, 1.0, 2.0, 0.0, 0.0, 1.0, 1.0, -1.0]
+        );
+    }
+
+    #[test]
+    fn test_names_match_values() {
+        let seq = "AUGGCCAUUGUAAGCUAGCUUAGGCAUGCAUGCC";
+        for dimension in ["9", "12", "36", "48"] {
+            let kind: ZCurveParams = dimension.parse().unwrap();
+            let values = zcurve::z_curve_params(seq, kind);
+            let names = zcurve::z_curve_param_names(kind);
+            assert_eq!(values.len(), kind.dimension());
+            assert_eq!(names.len(), kind.dimension());
+        }
+        assert!("10".parse::<ZCurveParams>().is_err());
+        assert_eq!(
+            zcurve::z_curve_param_names(ZCurveParams::Mono9)[0],
+            "zcurve9_p1_x"
+        );
+        assert_eq!(
+            zcurve::z_curve_param_names(ZCurveParams::Di36)[35],
+            "zcurve36_p3_U_z"
+        );
+        assert_eq!(
+            zcurve::z_curve_param_names(ZCurveParams::Tri48)[4],
+            "zcurve48_AC_y"
+        );
+    }
+
+    #[test]
+    fn test_mono9() {
+        // Phase 1 is A, A, G; phase 2 C, U, G; phase 3 G, C
+        let values = zcurve::z_curve_params("ACGAUCGG", ZCurveParams::Mono9);
+        let expected = [
+            3.0, 1.0, 1.0, //
+            -1.0, -1.0, -1.0, //
+            0.0, 0.0, -2.0,
+        ];
+        for (x, y) in values.iter().zip(expected) {
+            assert!((x - y / 8.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_di12_matches_trajectory_steps() {
+        // Summed over prefixes the dinucleotide parameters count every base
+        // but the first, so they add up to the trajectory's last step less
+        // its first
+        let seq = "GGCAUGGAGUCCUGUGGUAUCCACGAGAUCACC";
+        let values = zcurve::z_curve_params(seq, ZCurveParams::Di12);
+        let walk = zcurve::trajectory(seq);
+        let end = &walk[walk.len() - 3..];
+        for axis in 0..3 {
+            let summed: f64 = values.iter().skip(axis).step_by(3).sum();
+            let expected = (end[axis] - walk[axis]) / (seq.len() - 1) as f64;
+            assert!((summed - expected).abs() < 1e-12);
+        }
+    }
+}
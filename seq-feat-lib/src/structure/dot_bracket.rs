@@ -0,0 +1,141 @@
+//! Converting between dot-bracket strings and pair tables
+//!
+//! A pair table has one entry per base, holding the index of the base it
+//! pairs with, or `None` if it's unpaired. Only nested structures are
+//! supported, pseudoknots aren't.
+//!
+//! Structures can also be written in the WUSS notation used by Rfam and
+//! Infernal, where `<>`, `[]` and `{}` are base pairs just like `()`, and
+//! `,:_-~` are unpaired bases just like `.`. The different symbols only
+//! annotate the kind of helix or loop, so they read the same here. WUSS
+//! pseudoknots, written with matching upper and lower case letters, aren't
+//! supported.
+use anyhow::{anyhow, Result};
+
+/// Whether a character is part of a dot-bracket or WUSS structure
+pub fn is_structure_symbol(symbol: u8) -> bool {
+    matches!(
+        symbol,
+        b'(' | b')'
+            | b'<'
+            | b'>'
+            | b'['
+            | b']'
+            | b'{'
+            | b'}'
+            | b'.'
+            | b','
+            | b':'
+            | b'_'
+            | b'-'
+            | b'~'
+    )
+}
+
+/// Which kind of bracket a symbol is, for matching it with its partner
+fn bracket_kind(symbol: u8) -> usize {
+    match symbol {
+        b'(' | b')' => 0,
+        b'<' | b'>' => 1,
+        b'[' | b']' => 2,
+        _ => 3,
+    }
+}
+
+/// Parse a dot-bracket or WUSS string into a pair table
+///
+/// Each bracket closes the last open bracket of the same kind. Errors on
+/// unbalanced brackets, on pairs that cross (a pseudoknot written with
+/// different brackets, as in ViennaRNA's extended notation) and on any
+/// other character.
+pub fn pair_table(structure: &str) -> Result<Vec<Option<usize>>> {
+    let mut pairs = vec![None; structure.len()];
+    let mut open: [Vec<usize>; 4] = Default::default();
+
+    for (idx, symbol) in structure.bytes().enumerate() {
+        match symbol {
+            b'(' | b'<' | b'[' | b'{' => open[bracket_kind(symbol)].push(idx),
+            b')' | b'>' | b']' | b'}' => {
+                let partner = open[bracket_kind(symbol)].pop().ok_or_else(|| {
+                    anyhow!("Unmatched {} at position {}", symbol as char, idx + 1)
+                })?;
+                pairs[partner] = Some(idx);
+                pairs[idx] = Some(partner);
+            }
+            _ if is_structure_symbol(symbol) => {}
+            _ => return Err(anyhow!("Invalid character {} in structure", symbol as char)),
+        }
+    }
+
+    if let Some(idx) = open.iter().flatten().min() {
+        return Err(anyhow!(
+            "Unmatched {} at position {}",
+            structure.as_bytes()[*idx] as char,
+            idx + 1
+        ));
+    }
+
+    // Nested pairs close in the reverse order they open
+    let mut closing = Vec::new();
+    for (idx, partner) in pairs.iter().enumerate() {
+        match partner {
+            Some(j) if *j > idx => closing.push(*j),
+            Some(_) if closing.pop() != Some(idx) => {
+                return Err(anyhow!(
+                    "Crossing base pairs at position {}, pseudoknots aren't supported",
+                    idx + 1
+                ))
+            }
+            _ => {}
+        }
+    }
+    Ok(pairs)
+}
+
+/// Write a list of base pairs `(i, j)` with `i < j` as dot-bracket
+pub fn from_pairs(length: usize, pairs: &[(usize, usize)]) -> String {
+    let mut structure = vec![b'.'; length];
+    for (i, j) in pairs {
+        structure[*i] = b'(';
+        structure[*j] = b')';
+    }
+    String::from_utf8(structure).unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::structure::dot_bracket;
+
+    #[test]
+    fn test_round_trip() {
+        let structure = "((..((...))..)).";
+        let pairs = dot_bracket::pair_table(structure).unwrap();
+        assert_eq!(pairs[0], Some(14));
+        assert_eq!(pairs[4], Some(10));
+        assert_eq!(pairs[15], None);
+
+        let list: Vec<(usize, usize)> = pairs
+            .iter()
+            .enumerate()
+            .filter_map(|(i, j)| j.filter(|j| *j > i).map(|j| (i, j)))
+            .collect();
+        assert_eq!(dot_bracket::from_pairs(structure.len(), &list), structure);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(dot_bracket::pair_table("(()").is_err());
+        assert!(dot_bracket::pair_table("())").is_err());
+        assert!(dot_bracket::pair_table("(.x)").is_err());
+        assert!(dot_bracket::pair_table("((.[[.)).]]").is_err());
+        assert!(dot_bracket::pair_table("(.>").is_err());
+    }
+
+    #[test]
+    fn test_wuss() {
+        assert_eq!(
+            dot_bracket::pair_table("::<<<_..>->>,,[[{..}]]~").unwrap(),
+            dot_bracket::pair_table("..(((...).))..(((..))).").unwrap()
+        );
+    }
+}
@@ -0,0 +1,282 @@
+//! Features of a known secondary structure
+//!
+//! These describe a structure given in dot-bracket, for example from Rfam or
+//! R2DT, rather than one predicted here. The structure is decomposed into
+//! loops the same way as for the energy model: each base pair closes a loop,
+//! which is a hairpin if it has no branches, a stack, bulge or interior loop
+//! if it has one, and a multiloop if it has more. Runs of stacked pairs make
+//! up the stems.
+//!
+//! The triplet composition is the 32 dimensional encoding of the triplet-SVM
+//! microRNA classifier (Xue et al. 2005): the base in the middle of every
+//! window of three, with whether each of the three bases is paired.
+use crate::stats::composition::{base_index, BASES, OTHER};
+use crate::structure::dot_bracket::{is_structure_symbol, pair_table};
+use anyhow::{anyhow, Result};
+
+/// Structure features, in output order
+///
+/// Loop lengths count unpaired bases, and stem lengths count base pairs.
+pub const STRUCTURE_FEATURE_NAMES: [&str; 12] = [
+    "structure_paired_fraction",
+    "structure_stems",
+    "structure_stem_mean_length",
+    "structure_max_helix",
+    "structure_hairpins",
+    "structure_hairpin_mean_length",
+    "structure_interior_loops",
+    "structure_interior_mean_length",
+    "structure_bulges",
+    "structure_bulge_mean_length",
+    "structure_multiloops",
+    "structure_multiloop_mean_length",
+];
+
+/// Drop a trailing free energy like ` (-1.20)` or ` ( -1.20)`, as RNAfold
+/// writes after the structure
+fn strip_energy(record: &str) -> &str {
+    let record = record.trim_end();
+    if let Some(start) = record.strip_suffix(')').and_then(|x| x.rfind('(')) {
+        if record[start + 1..record.len() - 1]
+            .trim()
+            .parse::<f64>()
+            .is_ok()
+        {
+            return record[..start].trim_end();
+        }
+    }
+    record
+}
+
+/// Split a record holding a sequence followed by its structure
+///
+/// FASTA readers join the second and third lines of the 3-line format used
+/// by RNAfold and friends, so the sequence is the first half and the
+/// structure the second. The structure can be dot-bracket or WUSS (see
+/// `dot_bracket`), and can be followed by its energy as RNAfold writes it.
+///
+/// Returns `None` for a record without any brackets, which is just a
+/// sequence. Errors if a record with brackets can't be split into a
+/// sequence and a structure of the same length, rather than letting the
+/// whole thing through as a sequence.
+pub fn split_structure(record: &str) -> Result<Option<(&str, &str)>> {
+    if !record.bytes().any(|x| b"()<>[]{}".contains(&x)) {
+        return Ok(None);
+    }
+    let record = strip_energy(record);
+    let (seq, structure) = record.split_at(record.len() / 2);
+    if record.len().is_multiple_of(2)
+        && structure.bytes().all(is_structure_symbol)
+        && !seq.bytes().any(is_structure_symbol)
+    {
+        Ok(Some((seq, structure)))
+    } else {
+        Err(anyhow!(
+            "Record has brackets but doesn't split into a sequence and a structure of the same length"
+        ))
+    }
+}
+
+/// Mean of some lengths, or NaN if there are none
+fn mean(lengths: &[usize]) -> f64 {
+    lengths.iter().sum::<usize>() as f64 / lengths.len() as f64
+}
+
+/// Calculate the loop and stem features of a dot-bracket structure
+///
+/// Returns the features in `STRUCTURE_FEATURE_NAMES` order, with NaN for the
+/// mean length of any element the structure doesn't have. Errors if the
+/// structure doesn't parse.
+pub fn structure_features(structure: &str) -> Result<Vec<f64>> {
+    let pairs = pair_table(structure)?;
+    let mut stems = Vec::new();
+    let mut hairpins = Vec::new();
+    let mut interior = Vec::new();
+    let mut bulges = Vec::new();
+    let mut multiloops = Vec::new();
+
+    for (i, partner) in pairs.iter().enumerate() {
+        let j = match partner {
+            Some(j) if *j > i => *j,
+            _ => continue,
+        };
+
+        // A stem starts at any pair not stacked inside another
+        let stacked = i > 0 && pairs[i - 1] == Some(j + 1);
+        if !stacked {
+            let mut length = 1;
+            while pairs[i + length] == Some(j - length) {
+                length += 1;
+            }
+            stems.push(length);
+        }
+
+        let mut branches = Vec::new();
+        let mut unpaired = 0;
+        let mut k = i + 1;
+        while k < j {
+            match pairs[k] {
+                Some(l) => {
+                    branches.push((k, l));
+                    k = l + 1;
+                }
+                None => {
+                    unpaired += 1;
+                    k += 1;
+                }
+            }
+        }
+        match branches[..] {
+            [] => hairpins.push(unpaired),
+            [(k, l)] => match (k - i - 1, j - l - 1) {
+                (0, 0) => {}
+                (0, length) | (length, 0) => bulges.push(length),
+                (left, right) => interior.push(left + right),
+            },
+            _ => multiloops.push(unpaired),
+        }
+    }
+
+    let paired = pairs.iter().filter(|x| x.is_some()).count();
+    Ok(vec![
+        paired as f64 / pairs.len() as f64,
+        stems.len() as f64,
+        mean(&stems),
+        stems.iter().max().map_or(0.0, |x| *x as f64),
+        hairpins.len() as f64,
+        mean(&hairpins),
+        interior.len() as f64,
+        mean(&interior),
+        bulges.len() as f64,
+        mean(&bulges),
+        multiloops.len() as f64,
+        mean(&multiloops),
+    ])
+}
+
+/// Frequencies of the 32 sequence-structure triplets
+///
+/// For every window of three bases, the middle base and whether each base in
+/// the window is paired pick one of the 32 triplets, indexed as
+/// `8 * base + 4 * first + 2 * middle + last` with paired as 0. Windows
+/// centred on a non nucleotide are skipped, and the counts are divided by
+/// the number of windows used. Errors if the structure doesn't parse or is a
+/// different length to the sequence.
+pub fn triplet_composition(clean_seq: &str, structure: &str) -> Result<Vec<f64>> {
+    if clean_seq.len() != structure.len() {
+        return Err(anyhow!(
+            "Structure length {} doesn't match sequence length {}",
+            structure.len(),
+            clean_seq.len()
+        ));
+    }
+    let unpaired: Vec<usize> = pair_table(structure)?
+        .iter()
+        .map(|x| x.is_none() as usize)
+        .collect();
+
+    let mut counts = vec![0u64; 32];
+    for (idx, window) in unpaired.windows(3).enumerate() {
+        let base = base_index(clean_seq.as_bytes()[idx + 1]);
+        if base != OTHER {
+            counts[8 * base + 4 * window[0] + 2 * window[1] + window[2]] += 1;
+        }
+    }
+
+    let total = counts.iter().sum::<u64>() as f64;
+    Ok(counts.into_iter().map(|x| x as f64 / total).collect())
+}
+
+/// Get the column names for `triplet_composition`, like `triplet_A_ppu`,
+/// with `p` for paired and `u` for unpaired
+pub fn triplet_names() -> Vec<String> {
+    let mut names = Vec::with_capacity(32);
+    for base in BASES {
+        for pattern in 0..8 {
+            let state: String = [4, 2, 1]
+                .iter()
+                .map(|bit| if pattern & bit == 0 { 'p' } else { 'u' })
+                .collect();
+            names.push(format!("triplet_{}_{}", base, state));
+        }
+    }
+    names
+}
+
+#[cfg(test)]
+mod test {
+    use crate::structure::features;
+
+    #[test]
+    fn test_split() {
+        let split = |x| features::split_structure(x).unwrap();
+        assert_eq!(
+            split("GGGAAACCC(((...)))"),
+            Some(("GGGAAACCC", "(((...)))"))
+        );
+        assert_eq!(split("GGGAAACCC"), None);
+        assert_eq!(split("GGGAAACCCA"), None);
+        assert_eq!(split(""), None);
+
+        // RNAfold output, with the energy after the structure
+        assert_eq!(
+            split("GGGAAACCC(((...))) ( -1.20)"),
+            Some(("GGGAAACCC", "(((...)))"))
+        );
+        assert_eq!(
+            split("GGGGAAACCCC((((...)))) (-12.30)"),
+            Some(("GGGGAAACCCC", "((((...))))"))
+        );
+        assert_eq!(
+            split("GGGAAACCC<<<_._>>>"),
+            Some(("GGGAAACCC", "<<<_._>>>"))
+        );
+
+        // Anything else with brackets is an error, not a sequence
+        assert!(features::split_structure("GGGAAACCC(((...))").is_err());
+        assert!(features::split_structure("GGGAAACCC(((...))) [-1.20]").is_err());
+        assert!(features::split_structure("GGGAAACC(((...))).").is_err());
+    }
+
+    #[test]
+    fn test_loops() {
+        // A multiloop closing a hairpin, a stem with a one base bulge, and a
+        // stem with a 2x1 interior loop, plus an exterior hairpin
+        let structure = "((.((...)).(((.((...))))).((..((...)).)).)).((...))";
+        let values = features::structure_features(structure).unwrap();
+        assert_eq!(values.len(), features::STRUCTURE_FEATURE_NAMES.len());
+        assert_eq!(values[1], 7.0);
+        assert_eq!(values[3], 3.0);
+        assert_eq!(values[4], 4.0);
+        assert_eq!(values[5], 3.0);
+        assert_eq!(values[6..8], [1.0, 3.0]);
+        assert_eq!(values[8..10], [1.0, 1.0]);
+        assert_eq!(values[10..12], [1.0, 4.0]);
+        assert_eq!(values[0], 30.0 / 51.0);
+    }
+
+    #[test]
+    fn test_empty_structure() {
+        let values = features::structure_features("....").unwrap();
+        assert_eq!(values[..2], [0.0, 0.0]);
+        assert!(values[2].is_nan());
+        assert!(features::structure_features("((.)").is_err());
+    }
+
+    #[test]
+    fn test_triplets() {
+        let names = features::triplet_names();
+        assert_eq!(names.len(), 32);
+        assert_eq!(names[0], "triplet_A_ppp");
+        assert_eq!(names[13], "triplet_C_upu");
+
+        let values = features::triplet_composition("GGGAAACCC", "(((...)))").unwrap();
+        assert_eq!(values.len(), 32);
+        assert!((values.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+        // G in ((( and ((., A in (.., ... and ..), C in .)) and )))
+        for idx in [16, 17, 3, 7, 6, 12, 8] {
+            assert_eq!(values[idx], 1.0 / 7.0);
+        }
+        assert!(features::triplet_composition("GGGAAACCC", "((...))").is_err());
+    }
+}
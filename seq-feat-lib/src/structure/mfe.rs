@@ -0,0 +1,541 @@
+//! Minimum free energy secondary structure prediction
+//!
+//! This is the Zuker algorithm with the loop decomposition used by
+//! ViennaRNA: every structure is broken into hairpins, stacks, bulges,
+//! interior loops, multiloops and the exterior loop, and the structure with
+//! the lowest total energy under `EnergyParams` is found by dynamic
+//! programming. Interior loops are limited to `MAXLOOP` unpaired bases, and
+//! multiloops have a linear energy, so folding takes time cubic in the
+//! sequence length and quadratic memory.
+use crate::stats::composition::base_index;
+use crate::stats::counts::gc_content;
+use crate::stats::null::{null_score, NullScore, Tail};
+use crate::structure::dot_bracket::{from_pairs, pair_table};
+use crate::structure::params::{pair_type, EnergyParams, INF, MAXLOOP, MIN_HAIRPIN};
+use anyhow::{anyhow, Result};
+use wasm_bindgen::prelude::*;
+
+/// Features reported for a folded sequence, in output order
+pub const MFE_FEATURE_NAMES: [&str; 3] = ["mfe", "mfe_per_nt", "mfe_gc"];
+
+/// A predicted structure and its free energy in kcal/mol
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fold {
+    pub structure: String,
+    pub energy: f64,
+}
+
+/// Encode a sequence as composition counter indices, for the energy model
+pub fn encode(clean_seq: &str) -> Vec<usize> {
+    clean_seq.bytes().map(base_index).collect()
+}
+
+/// Convert an energy from the model's dcal/mol to kcal/mol
+pub fn to_kcal(energy: i32) -> f64 {
+    energy as f64 / 100.0
+}
+
+/// The filled dynamic programming matrices, indexed `i * n + j`
+struct Matrices {
+    n: usize,
+    /// Best energy of the segment `i..=j` given `i` pairs with `j`
+    paired: Vec<i32>,
+    /// Best energy of `i..=j` as part of a multiloop, with at least one
+    /// branch
+    multi: Vec<i32>,
+    /// Best energy of `i..=j` as part of a multiloop, with exactly one
+    /// branch which starts at `i`
+    multi_one: Vec<i32>,
+    /// Best energy of the first `j` bases in the exterior loop
+    exterior: Vec<i32>,
+}
+
+impl Matrices {
+    #[inline]
+    fn idx(&self, i: usize, j: usize) -> usize {
+        i * self.n + j
+    }
+}
+
+/// Best energy of a multiloop closed by `(i, j)`, and where to split it
+fn multiloop(
+    seq: &[usize],
+    params: &EnergyParams,
+    m: &Matrices,
+    i: usize,
+    j: usize,
+) -> (i32, usize) {
+    let closing = params.ml_closing_stem(seq, i, j);
+    let mut best = (INF, 0);
+    for u in i + 2..j {
+        let energy = m.multi[m.idx(i + 1, u - 1)] + m.multi_one[m.idx(u, j - 1)] + closing;
+        if energy < best.0 {
+            best = (energy, u);
+        }
+    }
+    best
+}
+
+fn fill(seq: &[usize], params: &EnergyParams) -> Matrices {
+    let n = seq.len();
+    let mut m = Matrices {
+        n,
+        paired: vec![INF; n * n],
+        multi: vec![INF; n * n],
+        multi_one: vec![INF; n * n],
+        exterior: vec![0; n + 1],
+    };
+
+    for span in MIN_HAIRPIN + 1..n {
+        for i in 0..n - span {
+            let j = i + span;
+            let ij = m.idx(i, j);
+
+            if pair_type(seq[i], seq[j]).is_some() {
+                let mut best = params.hairpin(seq, i, j);
+                for k in i + 1..(i + MAXLOOP + 2).min(j - MIN_HAIRPIN - 1) {
+                    let left = k - i - 1;
+                    let min_l = (k + MIN_HAIRPIN + 1).max((j - 1).saturating_sub(MAXLOOP - left));
+                    for l in min_l..j {
+                        let inner = m.paired[m.idx(k, l)];
+                        if inner < INF {
+                            best = best.min(params.interior(seq, i, j, k, l) + inner);
+                        }
+                    }
+                }
+                best = best.min(multiloop(seq, params, &m, i, j).0);
+                m.paired[ij] = best.min(INF);
+
+                if m.paired[ij] < INF {
+                    m.multi_one[ij] = m.paired[ij] + params.ml_stem(seq, i, j);
+                }
+            }
+            m.multi_one[ij] = m.multi_one[ij]
+                .min(m.multi_one[m.idx(i, j - 1)] + params.ml_base)
+                .min(INF);
+
+            let mut best = INF;
+            for u in i..j {
+                let branch = m.multi_one[m.idx(u, j)];
+                best = best.min((u - i) as i32 * params.ml_base + branch);
+                if u > i {
+                    best = best.min(m.multi[m.idx(i, u - 1)] + branch);
+                }
+            }
+            m.multi[ij] = best.min(INF);
+        }
+    }
+
+    for j in 0..n {
+        let mut best = m.exterior[j];
+        for i in 0..j {
+            let paired = m.paired[m.idx(i, j)];
+            if paired < INF {
+                best = best.min(m.exterior[i] + paired + params.exterior_stem(seq, i, j));
+            }
+        }
+        m.exterior[j + 1] = best;
+    }
+
+    m
+}
+
+/// Parts of the sequence still to trace back through
+enum Segment {
+    /// The first `j` bases, in the exterior loop
+    Exterior(usize),
+    /// `i..=j` with `i` paired to `j`
+    Paired(usize, usize),
+    /// `i..=j` in a multiloop, with at least one branch
+    Multi(usize, usize),
+    /// `i..=j` in a multiloop, with one branch starting at `i`
+    MultiOne(usize, usize),
+}
+
+fn traceback(seq: &[usize], params: &EnergyParams, m: &Matrices) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    let mut segments = vec![Segment::Exterior(m.n)];
+
+    while let Some(segment) = segments.pop() {
+        match segment {
+            Segment::Exterior(j) => {
+                if j == 0 {
+                    continue;
+                }
+                if m.exterior[j] == m.exterior[j - 1] {
+                    segments.push(Segment::Exterior(j - 1));
+                    continue;
+                }
+                let end = j - 1;
+                let i = (0..end)
+                    .find(|i| {
+                        let paired = m.paired[m.idx(*i, end)];
+                        paired < INF
+                            && m.exterior[*i] + paired + params.exterior_stem(seq, *i, end)
+                                == m.exterior[j]
+                    })
+                    .expect("exterior loop traceback");
+                segments.push(Segment::Exterior(i));
+                segments.push(Segment::Paired(i, end));
+            }
+            Segment::Paired(i, j) => {
+                pairs.push((i, j));
+                let target = m.paired[m.idx(i, j)];
+                if params.hairpin(seq, i, j) == target {
+                    continue;
+                }
+
+                let mut inner = None;
+                'interior: for k in i + 1..(i + MAXLOOP + 2).min(j - MIN_HAIRPIN - 1) {
+                    let left = k - i - 1;
+                    let min_l = (k + MIN_HAIRPIN + 1).max((j - 1).saturating_sub(MAXLOOP - left));
+                    for l in min_l..j {
+                        let paired = m.paired[m.idx(k, l)];
+                        if paired < INF && params.interior(seq, i, j, k, l) + paired == target {
+                            inner = Some((k, l));
+                            break 'interior;
+                        }
+                    }
+                }
+                if let Some((k, l)) = inner {
+                    segments.push(Segment::Paired(k, l));
+                    continue;
+                }
+
+                let (energy, u) = multiloop(seq, params, m, i, j);
+                assert_eq!(energy, target, "multiloop traceback");
+                segments.push(Segment::Multi(i + 1, u - 1));
+                segments.push(Segment::MultiOne(u, j - 1));
+            }
+            Segment::MultiOne(i, j) => {
+                let paired = m.paired[m.idx(i, j)];
+                if paired < INF && paired + params.ml_stem(seq, i, j) == m.multi_one[m.idx(i, j)] {
+                    segments.push(Segment::Paired(i, j));
+                } else {
+                    segments.push(Segment::MultiOne(i, j - 1));
+                }
+            }
+            Segment::Multi(i, j) => {
+                let target = m.multi[m.idx(i, j)];
+                for u in i..j {
+                    let branch = m.multi_one[m.idx(u, j)];
+                    if (u - i) as i32 * params.ml_base + branch == target {
+                        segments.push(Segment::MultiOne(u, j));
+                        break;
+                    }
+                    if u > i && m.multi[m.idx(i, u - 1)] + branch == target {
+                        segments.push(Segment::Multi(i, u - 1));
+                        segments.push(Segment::MultiOne(u, j));
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    pairs.sort_unstable();
+    pairs
+}
+
+/// Predict the minimum free energy structure of a sequence
+///
+/// Where several structures share the lowest energy, hairpins are preferred
+/// over interior loops, and those over multiloops.
+pub fn fold(clean_seq: &str, params: &EnergyParams) -> Fold {
+    let seq = encode(clean_seq);
+    let matrices = fill(&seq, params);
+    let pairs = traceback(&seq, params, &matrices);
+
+    Fold {
+        structure: from_pairs(seq.len(), &pairs),
+        energy: to_kcal(matrices.exterior[seq.len()]),
+    }
+}
+
+/// Minimum free energy of a sequence in kcal/mol, with the embedded
+/// parameters
+#[wasm_bindgen(js_name=mfe)]
+pub fn mfe(clean_seq: &str) -> f64 {
+    fold(clean_seq, &EnergyParams::turner2004()).energy
+}
+
+/// Minimum free energy structure of a sequence in dot-bracket, with the
+/// embedded parameters
+#[wasm_bindgen(js_name=mfe_structure)]
+pub fn mfe_structure(clean_seq: &str) -> String {
+    fold(clean_seq, &EnergyParams::turner2004()).structure
+}
+
+/// Score the MFE against dinucleotide shuffles of the sequence
+///
+/// A negative z-score means the sequence is more stable than expected from
+/// its dinucleotide composition. Each shuffle is folded, so this takes
+/// `shuffles + 1` times as long as `fold`.
+pub fn mfe_null_score(
+    clean_seq: &str,
+    params: &EnergyParams,
+    shuffles: usize,
+    seed: u64,
+) -> NullScore {
+    let energy = |seq: &str| fold(seq, params).energy;
+    null_score(clean_seq, energy, Tail::Lower, shuffles, 2, seed)
+}
+
+/// MFE z-score against dinucleotide shuffles, with the embedded parameters
+#[wasm_bindgen(js_name=mfe_z_score)]
+pub fn mfe_z_score(clean_seq: &str, shuffles: usize, seed: u64) -> f64 {
+    mfe_null_score(clean_seq, &EnergyParams::turner2004(), shuffles, seed).z_score
+}
+
+/// Evaluate the free energy of a given structure in kcal/mol
+///
+/// Errors if the structure doesn't match the sequence length, pairs bases
+/// that can't pair or has a hairpin loop that's too small.
+pub fn eval_structure(clean_seq: &str, structure: &str, params: &EnergyParams) -> Result<f64> {
+    let seq = encode(clean_seq);
+    if structure.len() != seq.len() {
+        return Err(anyhow!(
+            "Structure length {} doesn't match sequence length {}",
+            structure.len(),
+            seq.len()
+        ));
+    }
+    let pairs = pair_table(structure)?;
+    let pair_of = |i: usize, j: usize| {
+        pair_type(seq[i], seq[j]).ok_or_else(|| anyhow!("Bases {} and {} can't pair", i + 1, j + 1))
+    };
+
+    let mut energy = 0;
+    let mut i = 0;
+    while i < seq.len() {
+        match pairs[i] {
+            Some(j) => {
+                pair_of(i, j)?;
+                energy += params.exterior_stem(&seq, i, j);
+                i = j + 1;
+            }
+            None => i += 1,
+        }
+    }
+
+    for (i, j) in pairs.iter().enumerate() {
+        let j = match j {
+            Some(j) if *j > i => *j,
+            _ => continue,
+        };
+        pair_of(i, j)?;
+        if j - i - 1 < MIN_HAIRPIN {
+            return Err(anyhow!("Hairpin closed at {} is too small", i + 1));
+        }
+
+        let mut branches = Vec::new();
+        let mut unpaired = 0;
+        let mut k = i + 1;
+        while k < j {
+            match pairs[k] {
+                Some(l) => {
+                    branches.push((k, l));
+                    k = l + 1;
+                }
+                None => {
+                    unpaired += 1;
+                    k += 1;
+                }
+            }
+        }
+
+        energy += match branches[..] {
+            [] => params.hairpin(&seq, i, j),
+            [(k, l)] => params.interior(&seq, i, j, k, l),
+            _ => {
+                let mut loop_energy =
+                    params.ml_closing_stem(&seq, i, j) + unpaired * params.ml_base;
+                for (k, l) in branches {
+                    pair_of(k, l)?;
+                    loop_energy += params.ml_stem(&seq, k, l);
+                }
+                loop_energy
+            }
+        };
+    }
+
+    Ok(to_kcal(energy))
+}
+
+/// Calculate the features in `MFE_FEATURE_NAMES` from a fold
+///
+/// These are the MFE, the MFE per nucleotide and the MFE per nucleotide
+/// divided by the GC content (the minimum free energy index), which is NaN
+/// for sequences with no G or C.
+pub fn mfe_features(clean_seq: &str, fold: &Fold) -> Vec<f64> {
+    let per_nt = fold.energy / clean_seq.len() as f64;
+    let gc = gc_content(clean_seq);
+    vec![
+        fold.energy,
+        per_nt,
+        if gc > 0.0 { per_nt / gc } else { f64::NAN },
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use crate::structure::dot_bracket;
+    use crate::structure::mfe::{self, encode};
+    use crate::structure::params::test::full_params;
+    use crate::structure::params::{pair_type, EnergyParams, MIN_HAIRPIN};
+    use crate::utils;
+    use std::fs;
+    use std::io::BufReader;
+
+    /// Every secondary structure of `i..j`, as lists of pairs
+    fn all_structures(seq: &[usize], i: usize, j: usize) -> Vec<Vec<(usize, usize)>> {
+        if j <= i {
+            return vec![Vec::new()];
+        }
+        let mut structures = all_structures(seq, i + 1, j);
+        for k in i + MIN_HAIRPIN + 1..j {
+            if pair_type(seq[i], seq[k]).is_none() {
+                continue;
+            }
+            for inside in all_structures(seq, i + 1, k) {
+                for outside in all_structures(seq, k + 1, j) {
+                    let mut structure = vec![(i, k)];
+                    structure.extend(&inside);
+                    structure.extend(&outside);
+                    structures.push(structure);
+                }
+            }
+        }
+        structures
+    }
+
+    #[test]
+    fn test_simple_hairpin() {
+        // Three G-C on G-C stacks at -3.3 and a triloop at 5.4
+        let fold = mfe::fold("GGGGAAACCCC", &EnergyParams::turner2004());
+        assert_eq!(fold.structure, "((((...))))");
+        assert!((fold.energy + 4.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_unstructured() {
+        let fold = mfe::fold("AAAAAAAA", &EnergyParams::turner2004());
+        assert_eq!(fold.structure, "........");
+        assert_eq!(fold.energy, 0.0);
+        assert_eq!(mfe::mfe(""), 0.0);
+    }
+
+    #[test]
+    fn test_matches_exhaustive_search() {
+        for (params, seq) in [
+            "GGGAAAUCCCAGCU",
+            "GCGCUUCGGCGCAA",
+            "AUGGCUACGUAGCC",
+            "GGACUUCGGUCCGAAAGG",
+            "GGCAAAGCCGCAAAGCGG",
+            "GGCGGAAACCGCCAAAG",
+        ]
+        .into_iter()
+        .flat_map(|seq| [(EnergyParams::turner2004(), seq), (full_params(), seq)])
+        {
+            let encoded = encode(seq);
+            let best = all_structures(&encoded, 0, seq.len())
+                .iter()
+                .map(|pairs| {
+                    let structure = dot_bracket::from_pairs(seq.len(), pairs);
+                    mfe::eval_structure(seq, &structure, &params).unwrap()
+                })
+                .fold(f64::INFINITY, f64::min);
+
+            let fold = mfe::fold(seq, &params);
+            assert!((fold.energy - best).abs() < 1e-9, "{}", seq);
+            let evaluated = mfe::eval_structure(seq, &fold.structure, &params).unwrap();
+            assert!((fold.energy - evaluated).abs() < 1e-9, "{}", seq);
+        }
+    }
+
+    #[test]
+    fn test_multiloop() {
+        // Two GAAA hairpins inside a six pair closing helix. By hand that's
+        // -16.6 of closing helix stacks, 8.4 to close the multiloop and -4.0
+        // and -3.6 for the two branches
+        let seq = "GGGCCCAGACCGAAAGGUCACUGGGAAACCAGAGGGCCC";
+        let params = EnergyParams::turner2004();
+        let fold = mfe::fold(seq, &params);
+        assert_eq!(fold.structure, "((((((.((((....)))).((((....)))).))))))");
+        assert!((fold.energy + 15.8).abs() < 1e-9);
+        let evaluated = mfe::eval_structure(seq, &fold.structure, &params).unwrap();
+        assert!((fold.energy - evaluated).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_z_score() {
+        // A designed structure is more stable than its shuffles
+        let seq = "GGGCCCAGACCGAAAGGUCACUGGGAAACCAGAGGGCCC";
+        let score = mfe::mfe_null_score(seq, &EnergyParams::turner2004(), 30, 1);
+        assert!(score.value < score.mean);
+        assert!(score.z_score < -1.0);
+        assert!(score.p_value < 0.1);
+        assert_eq!(mfe::mfe_z_score(seq, 30, 1), score.z_score);
+    }
+
+    #[test]
+    fn test_eval_errors() {
+        let params = EnergyParams::turner2004();
+        assert!(mfe::eval_structure("GGGAAACCC", "((....))", &params).is_err());
+        assert!(mfe::eval_structure("GGGAAAAAA", "(((...)))", &params).is_err());
+        assert!(mfe::eval_structure("GGGAACCC", "(((..)))", &params).is_err());
+    }
+
+    #[test]
+    fn test_features() {
+        let seq = "GGGGAAACCCC";
+        let fold = mfe::fold(seq, &EnergyParams::turner2004());
+        let features = mfe::mfe_features(seq, &fold);
+        assert_eq!(features.len(), mfe::MFE_FEATURE_NAMES.len());
+        assert!((features[1] + 4.5 / 11.0).abs() < 1e-12);
+        assert!((features[2] + 4.5 / 8.0).abs() < 1e-12);
+    }
+
+    /// Compare with RNAfold 2.x on `testdata/rnafold.fasta`, using the
+    /// structures, energies and parameter file saved by
+    /// `testdata/rnafold.py`
+    ///
+    /// RNAfold is run with its default `-d2` and the saved parameter file.
+    /// The fold and RNAfold's own structure must both give its energy, with
+    /// that file and with the embedded parameters, which only agree where
+    /// the embedded subset of Turner 2004 covers every loop.
+    #[test]
+    #[ignore = "needs testdata/rnafold.tsv and testdata/rna_turner2004.par from testdata/rnafold.py"]
+    fn test_rnafold() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata");
+        let file = fs::File::open(format!("{}/rna_turner2004.par", dir)).unwrap();
+        let par = EnergyParams::from_vienna_par(BufReader::new(file)).unwrap();
+
+        let reference = fs::read_to_string(format!("{}/rnafold.tsv", dir)).unwrap();
+        for params in [&par, &EnergyParams::turner2004()] {
+            for line in reference.lines().skip(1) {
+                let fields: Vec<&str> = line.split('\t').collect();
+                let seq = utils::seq::clean(fields[1]).unwrap();
+                let energy: f64 = fields[3].parse().unwrap();
+
+                let evaluated = mfe::eval_structure(&seq, fields[2], params).unwrap();
+                assert!(
+                    (evaluated - energy).abs() < 1e-6,
+                    "{} {}",
+                    fields[0],
+                    evaluated
+                );
+
+                let fold = mfe::fold(&seq, params);
+                assert!(
+                    (fold.energy - energy).abs() < 1e-6,
+                    "{} {} {}",
+                    fields[0],
+                    fold.structure,
+                    fold.energy
+                );
+            }
+        }
+    }
+}
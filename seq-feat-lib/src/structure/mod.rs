@@ -0,0 +1,6 @@
+pub mod dot_bracket;
+pub mod features;
+pub mod mfe;
+pub mod nussinov;
+pub mod params;
+pub mod partition;
@@ -0,0 +1,274 @@
+//! Base pair maximisation (Nussinov) as a fast structure proxy
+//!
+//! Rather than minimising free energy, the Nussinov algorithm finds the
+//! nested structure with the most base pairs. It has no energy model, so it
+//! is a much rougher guide to the real structure than `mfe::fold`, but its
+//! inner loop is trivial, which makes it practical in the browser.
+//!
+//! With a maximum base pair span the table only has to cover pairs up to
+//! that far apart, so memory is linear in the sequence length and time is
+//! linear times the span squared, as in local folding with RNALfold.
+use crate::stats::composition::base_index;
+use crate::structure::dot_bracket::from_pairs;
+use crate::structure::params::pair_type;
+use wasm_bindgen::prelude::*;
+
+/// Options for base pair maximisation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NussinovParams {
+    /// Fewest unpaired bases allowed in a hairpin loop
+    pub min_loop: usize,
+    /// Allow G-U wobble pairs as well as Watson-Crick pairs
+    pub wobble: bool,
+    /// Longest distance between paired bases, `j - i`, or no limit
+    pub max_span: Option<usize>,
+}
+
+impl Default for NussinovParams {
+    fn default() -> Self {
+        NussinovParams {
+            min_loop: 3,
+            wobble: true,
+            max_span: None,
+        }
+    }
+}
+
+/// Pair counts for every segment up to the maximum span
+struct Band {
+    width: usize,
+    counts: Vec<u32>,
+}
+
+impl Band {
+    /// Best pair count of `i..=j`, or zero for an empty segment
+    #[inline]
+    fn get(&self, i: usize, j: usize) -> u32 {
+        if j < i || j - i >= self.width {
+            0
+        } else {
+            self.counts[i * self.width + j - i]
+        }
+    }
+}
+
+/// Find a structure with the most base pairs, as a list of pairs `(i, j)`
+pub fn max_pairs(clean_seq: &str, params: &NussinovParams) -> Vec<(usize, usize)> {
+    let seq: Vec<usize> = clean_seq.bytes().map(base_index).collect();
+    let n = seq.len();
+    let span = params.max_span.unwrap_or(n).min(n.saturating_sub(1));
+    let can_pair = |i: usize, j: usize| {
+        j - i > params.min_loop
+            && j - i <= span
+            && pair_type(seq[i], seq[j]).is_some_and(|x| params.wobble || !(2..=3).contains(&x))
+    };
+
+    // The band holds segments i..=j with j - i < width, which covers any
+    // segment strictly inside a pair of the maximum span
+    let mut band = Band {
+        width: span.max(1),
+        counts: vec![0; n * span.max(1)],
+    };
+    for length in 1..band.width {
+        for i in 0..n.saturating_sub(length) {
+            let j = i + length;
+            let mut best = band.get(i, j - 1);
+            for k in i..j {
+                if can_pair(k, j) {
+                    let inside = band.get(k + 1, j - 1);
+                    let before = if k > i { band.get(i, k - 1) } else { 0 };
+                    best = best.max(before + inside + 1);
+                }
+            }
+            band.counts[i * band.width + length] = best;
+        }
+    }
+
+    // Exterior loop over the whole sequence, best[j] for the first j bases
+    let mut best = vec![0u32; n + 1];
+    for j in 0..n {
+        best[j + 1] = best[j];
+        for k in j.saturating_sub(span)..j {
+            if can_pair(k, j) {
+                best[j + 1] = best[j + 1].max(best[k] + band.get(k + 1, j - 1) + 1);
+            }
+        }
+    }
+
+    // Trace back, with segments still inside a pair as (i, j) and the
+    // exterior prefix handled by the outer loop
+    let mut pairs = Vec::with_capacity(best[n] as usize);
+    let mut segments = Vec::new();
+    let mut j = n;
+    while j > 0 {
+        let end = j - 1;
+        if best[j] == best[end] {
+            j = end;
+            continue;
+        }
+        let k = (end.saturating_sub(span)..end)
+            .find(|k| can_pair(*k, end) && best[*k] + band.get(k + 1, end - 1) + 1 == best[j])
+            .expect("exterior traceback");
+        pairs.push((k, end));
+        segments.push((k + 1, end - 1));
+        j = k;
+    }
+    while let Some((i, j)) = segments.pop() {
+        if j <= i || band.get(i, j) == 0 {
+            continue;
+        }
+        if band.get(i, j) == band.get(i, j - 1) {
+            segments.push((i, j - 1));
+            continue;
+        }
+        let k = (i..j)
+            .find(|k| {
+                can_pair(*k, j) && {
+                    let before = if *k > i { band.get(i, k - 1) } else { 0 };
+                    before + band.get(k + 1, j - 1) + 1 == band.get(i, j)
+                }
+            })
+            .expect("segment traceback");
+        pairs.push((k, j));
+        segments.push((k + 1, j - 1));
+        if k > i {
+            segments.push((i, k - 1));
+        }
+    }
+
+    pairs.sort_unstable();
+    pairs
+}
+
+/// Find a structure with the most base pairs, in dot-bracket
+pub fn nussinov(clean_seq: &str, params: &NussinovParams) -> String {
+    from_pairs(clean_seq.len(), &max_pairs(clean_seq, params))
+}
+
+/// Fraction of bases paired in a structure with the most base pairs
+#[wasm_bindgen(js_name=paired_fraction)]
+pub fn paired_fraction(
+    clean_seq: &str,
+    min_loop: usize,
+    wobble: bool,
+    max_span: Option<usize>,
+) -> f64 {
+    let params = NussinovParams {
+        min_loop,
+        wobble,
+        max_span,
+    };
+    2.0 * max_pairs(clean_seq, &params).len() as f64 / clean_seq.len() as f64
+}
+
+#[cfg(test)]
+mod test {
+    use crate::structure::nussinov::{self, NussinovParams};
+
+    #[test]
+    fn test_hairpin() {
+        let params = NussinovParams::default();
+        assert_eq!(nussinov::nussinov("GGGAAACCC", &params), "(((...)))");
+        // Only two unpaired bases left for the loop with min_loop 3
+        assert_eq!(nussinov::max_pairs("GGGAACCC", &params).len(), 2);
+        assert_eq!(nussinov::nussinov("", &params), "");
+    }
+
+    #[test]
+    fn test_wobble() {
+        let with = NussinovParams::default();
+        let without = NussinovParams {
+            wobble: false,
+            ..Default::default()
+        };
+        assert_eq!(nussinov::max_pairs("GGGGGGUUUUU", &with).len(), 4);
+        assert_eq!(nussinov::nussinov("GGGGGGUUUUU", &without), "...........");
+    }
+
+    #[test]
+    fn test_max_span() {
+        let seq = "GGGAAAAAAAAACCCAGCUUUAGCUUUU";
+        let full = nussinov::max_pairs(seq, &NussinovParams::default());
+        let banded = nussinov::max_pairs(
+            seq,
+            &NussinovParams {
+                max_span: Some(10),
+                ..Default::default()
+            },
+        );
+        assert!(banded.iter().all(|(i, j)| j - i <= 10));
+        assert!(banded.len() <= full.len());
+
+        // A span at least the length of the sequence changes nothing
+        let wide = nussinov::max_pairs(
+            seq,
+            &NussinovParams {
+                max_span: Some(100),
+                ..Default::default()
+            },
+        );
+        assert_eq!(wide, full);
+
+        assert_eq!(
+            nussinov::paired_fraction("GGGAAACCC", 3, true, Some(4)),
+            2.0 / 9.0
+        );
+        assert!((nussinov::paired_fraction("GGGAAACCC", 3, true, None) - 6.0 / 9.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_maximal() {
+        // Every base can pair in a perfect stem loop
+        let seq = "GCAUGCAUAAAAUGCAUGC";
+        assert_eq!(
+            nussinov::max_pairs(seq, &NussinovParams::default()).len(),
+            8
+        );
+        assert_eq!(nussinov::paired_fraction(seq, 3, true, None), 16.0 / 19.0);
+    }
+
+    /// Most pairs in `i..j` by the textbook recursion, without any table
+    fn brute_force(seq: &[u8], i: usize, j: usize) -> usize {
+        if j <= i + 4 {
+            return 0;
+        }
+        let last = j - 1;
+        let mut best = brute_force(seq, i, last);
+        for k in i..last.saturating_sub(3) {
+            let pair = matches!(
+                (seq[k], seq[last]),
+                (b'A', b'U')
+                    | (b'U', b'A')
+                    | (b'G', b'C')
+                    | (b'C', b'G')
+                    | (b'G', b'U')
+                    | (b'U', b'G')
+            );
+            if pair {
+                best = best.max(brute_force(seq, i, k) + brute_force(seq, k + 1, last) + 1);
+            }
+        }
+        best
+    }
+
+    #[test]
+    fn test_brute_force() {
+        let mut state = 12345u64;
+        for _ in 0..20 {
+            let seq: String = (0..14)
+                .map(|_| {
+                    state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+                    b"ACGU"[(state >> 62) as usize] as char
+                })
+                .collect();
+            let pairs = nussinov::max_pairs(&seq, &NussinovParams::default());
+            assert_eq!(
+                pairs.len(),
+                brute_force(seq.as_bytes(), 0, seq.len()),
+                "{}",
+                seq
+            );
+            assert!(pairs.iter().all(|(i, j)| j - i > 3));
+        }
+    }
+}
@@ -0,0 +1,818 @@
+//! Nearest neighbour energy parameters
+//!
+//! Energies are integers in dcal/mol (so -330 is -3.3 kcal/mol) at 37°C, as
+//! in ViennaRNA, so the dynamic programming is exact and never has to compare
+//! floats. Loops are scored as in ViennaRNA 2.x, and
+//! `EnergyParams::from_vienna_par` reads every free energy section of its
+//! parameter files, so loading `rna_turner2004.par` gives RNAfold's model.
+//!
+//! The embedded parameters are a subset of Turner 2004 transcribed from
+//! ViennaRNA's `rna_turner2004.par`:
+//!
+//! - Stacking of all canonical and G-U pairs
+//! - Hairpin, bulge and interior loop initiation, extrapolated beyond 30
+//!   unpaired bases
+//! - Interior loop asymmetry (Ninio), terminal A-U/G-U penalties and linear
+//!   multiloop costs
+//!
+//! The embedded set leaves out the sequence dependent tables: terminal
+//! mismatches, dangling ends, the special hairpin loops and the 1x1, 1x2 and
+//! 2x2 interior loops. Hairpins get Turner 2004's first mismatch bonuses
+//! instead of the mismatch table, and interior loops only the A-U/G-U
+//! closure penalty. Turner 2004 has no generic energy for 1x1 and 1x2 loops,
+//! so without their tables those loops can't form.
+//!
+//! Dangling ends are on by default, as RNAfold's default `-d2`, where every
+//! stem in the exterior loop or a multiloop gets the mismatch energy of the
+//! bases either side of it, whether or not they pair. Clearing
+//! `EnergyParams::dangles` gives `-d0`. The embedded set has no dangle or
+//! mismatch energies, so this only makes a difference with a parameter file.
+use crate::stats::composition::{base_index, A, C, G, OTHER, U};
+use anyhow::{anyhow, Result};
+use std::io::BufRead;
+
+/// Energy standing in for impossible structures
+///
+/// Small enough that adding a few together can't overflow.
+pub const INF: i32 = 10_000_000;
+
+/// Longest interior loop or bulge considered, in unpaired bases
+pub const MAXLOOP: usize = 30;
+
+/// Fewest unpaired bases allowed in a hairpin loop
+pub const MIN_HAIRPIN: usize = 3;
+
+/// Number of pair types, in ViennaRNA's order CG, GC, GU, UG, AU, UA
+pub const NUM_PAIRS: usize = 6;
+
+/// Get the pair type of two bases, by their composition counter index
+///
+/// Types are numbered in ViennaRNA's order from 0, and bases that can't pair
+/// give `None`.
+#[inline]
+pub fn pair_type(first: usize, second: usize) -> Option<usize> {
+    match (first, second) {
+        (C, G) => Some(0),
+        (G, C) => Some(1),
+        (G, U) => Some(2),
+        (U, G) => Some(3),
+        (A, U) => Some(4),
+        (U, A) => Some(5),
+        _ => None,
+    }
+}
+
+/// Terminal mismatch energies, by pair type then the bases on the 5' and 3'
+/// side of the pair
+pub type MismatchTable = [[[i32; 4]; 4]; NUM_PAIRS];
+
+/// Dangling end energies, by pair type then the dangling base
+pub type DangleTable = [[i32; 4]; NUM_PAIRS];
+
+/// Energies of small interior loops that are tabulated as a whole
+///
+/// Looked up by the outer pair type, the reversed inner pair type, then the
+/// unpaired bases in ViennaRNA's order for the table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoopTable {
+    unpaired: usize,
+    energies: Vec<i32>,
+}
+
+impl LoopTable {
+    /// Read a ViennaRNA table with the given number of unpaired bases
+    ///
+    /// Files list `base_width` bases per position, with N first when that's
+    /// 5, and either the 7 pair types including the non standard one or just
+    /// the 6 canonical ones.
+    fn from_values(
+        name: &str,
+        values: &[i32],
+        unpaired: usize,
+        base_width: usize,
+    ) -> Result<LoopTable> {
+        let per_pairs = base_width.pow(unpaired as u32);
+        let pair_width = if values.len() >= 49 * per_pairs { 7 } else { 6 };
+        need(name, values, pair_width * pair_width * per_pairs)?;
+
+        let energies = (0..NUM_PAIRS * NUM_PAIRS * 4usize.pow(unpaired as u32))
+            .map(|index| {
+                let (mut rest, mut file, mut scale) = (index, 0, 1);
+                for _ in 0..unpaired {
+                    file += (rest % 4 + base_width - 4) * scale;
+                    scale *= base_width;
+                    rest /= 4;
+                }
+                let (outer, inner) = (rest / NUM_PAIRS, rest % NUM_PAIRS);
+                values[file + (outer * pair_width + inner) * scale]
+            })
+            .collect();
+        Ok(LoopTable { unpaired, energies })
+    }
+
+    /// Energy of a loop, or `None` if any of its bases isn't a nucleotide
+    #[inline]
+    fn get(&self, outer: usize, inner: usize, bases: &[usize]) -> Option<i32> {
+        debug_assert_eq!(bases.len(), self.unpaired);
+        let mut index = outer * NUM_PAIRS + inner;
+        for base in bases {
+            if *base == OTHER {
+                return None;
+            }
+            index = index * 4 + base;
+        }
+        Some(self.energies[index])
+    }
+}
+
+/// A full set of energy parameters, see the module docs
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnergyParams {
+    /// Stacking of the pair `(i, j)` on `(k, l)`, indexed by the types of
+    /// `(i, j)` and the reversed inner pair `(l, k)`
+    pub stack: [[i32; NUM_PAIRS]; NUM_PAIRS],
+    /// Hairpin loop initiation by number of unpaired bases
+    pub hairpin: [i32; MAXLOOP + 1],
+    /// Bulge loop initiation by number of unpaired bases
+    pub bulge: [i32; MAXLOOP + 1],
+    /// Interior loop initiation by total number of unpaired bases
+    pub interior: [i32; MAXLOOP + 1],
+    /// Terminal mismatch in hairpins, by closing pair type then the bases
+    /// on the 5' and 3' side inside the pair
+    pub mismatch_hairpin: MismatchTable,
+    /// Terminal mismatch in generic interior loops, indexed as
+    /// `mismatch_hairpin`
+    pub mismatch_interior: MismatchTable,
+    /// Terminal mismatch in 1xn interior loops
+    pub mismatch_interior_1n: MismatchTable,
+    /// Terminal mismatch in 2x3 interior loops
+    pub mismatch_interior_23: MismatchTable,
+    /// Mismatch of a multiloop stem with both neighbours, with dangles
+    pub mismatch_multi: MismatchTable,
+    /// Mismatch of an exterior loop stem with both neighbours, with dangles
+    pub mismatch_exterior: MismatchTable,
+    /// A base dangling on the 5' side of a pair, by pair type then the base
+    pub dangle5: DangleTable,
+    /// A base dangling on the 3' side of a pair, by pair type then the base
+    pub dangle3: DangleTable,
+    /// 1x1 interior loops, by the bases on the 5' then the 3' side
+    pub int11: Option<LoopTable>,
+    /// 1x2 interior loops, by the single base then the two opposite it, with
+    /// 2x1 loops looked up from the inner pair
+    pub int21: Option<LoopTable>,
+    /// 2x2 interior loops, by the two bases on the 5' side then the two on
+    /// the 3' side
+    pub int22: Option<LoopTable>,
+    /// Hairpin loops of 3, 4 and 6 bases with tabulated total energies, as
+    /// the loop sequence including the closing pair
+    pub special_hairpins: Vec<(String, i32)>,
+    /// Multiloop penalty for closing the loop
+    pub ml_closing: i32,
+    /// Multiloop penalty for each branch, including the closing pair
+    pub ml_intern: i32,
+    /// Multiloop penalty for each unpaired base
+    pub ml_base: i32,
+    /// Interior loop asymmetry penalty per base of difference
+    pub ninio: i32,
+    /// Largest interior loop asymmetry penalty
+    pub max_ninio: i32,
+    /// Penalty for a helix ending in an A-U or G-U pair
+    pub terminal_au: i32,
+    /// Scale of the logarithmic extrapolation of loops over `MAXLOOP`
+    pub lxc: f64,
+    /// Whether stems in the exterior loop and multiloops get dangling end
+    /// energies, as RNAfold's `-d2`
+    pub dangles: bool,
+}
+
+impl Default for EnergyParams {
+    fn default() -> Self {
+        EnergyParams::turner2004()
+    }
+}
+
+impl EnergyParams {
+    /// The embedded Turner 2004 subset
+    pub fn turner2004() -> EnergyParams {
+        let mut mismatch_hairpin = [[[0; 4]; 4]; NUM_PAIRS];
+        for table in mismatch_hairpin.iter_mut() {
+            // First mismatch bonuses, indexed 5' side then 3' side
+            table[U][U] = -90;
+            table[G][A] = -80;
+            table[G][G] = -80;
+        }
+        // Interior loops closed by A-U or G-U pay a per closure penalty
+        let mut mismatch_interior = [[[0; 4]; 4]; NUM_PAIRS];
+        for table in mismatch_interior.iter_mut().skip(2) {
+            *table = [[70; 4]; 4];
+        }
+
+        EnergyParams {
+            stack: [
+                [-240, -330, -210, -140, -210, -210],
+                [-330, -340, -250, -150, -220, -240],
+                [-210, -250, 130, -50, -140, -130],
+                [-140, -150, -50, 30, -60, -100],
+                [-210, -220, -140, -60, -110, -90],
+                [-210, -240, -130, -100, -90, -130],
+            ],
+            hairpin: [
+                INF, INF, INF, 540, 560, 570, 540, 600, 550, 640, 650, 660, 670, 678, 686, 694,
+                701, 707, 713, 719, 725, 730, 735, 740, 744, 749, 753, 757, 761, 765, 769,
+            ],
+            bulge: [
+                INF, 380, 280, 320, 360, 400, 440, 459, 470, 480, 490, 500, 510, 519, 527, 534,
+                541, 548, 554, 560, 565, 571, 576, 580, 585, 589, 594, 598, 602, 605, 609,
+            ],
+            // Sizes 2 and 3 are 1x1 and 1x2 loops, which only the tables
+            // give energies for
+            interior: [
+                INF, INF, INF, INF, 110, 200, 200, 210, 230, 240, 250, 260, 270, 280, 290, 290,
+                300, 310, 310, 320, 330, 330, 340, 340, 350, 350, 350, 360, 360, 370, 370,
+            ],
+            mismatch_hairpin,
+            mismatch_interior,
+            mismatch_interior_1n: mismatch_interior,
+            mismatch_interior_23: mismatch_interior,
+            mismatch_multi: [[[0; 4]; 4]; NUM_PAIRS],
+            mismatch_exterior: [[[0; 4]; 4]; NUM_PAIRS],
+            dangle5: [[0; 4]; NUM_PAIRS],
+            dangle3: [[0; 4]; NUM_PAIRS],
+            int11: None,
+            int21: None,
+            int22: None,
+            special_hairpins: Vec::new(),
+            ml_closing: 930,
+            ml_intern: -90,
+            ml_base: 0,
+            ninio: 60,
+            max_ninio: 300,
+            terminal_au: 50,
+            lxc: 107.856,
+            dangles: true,
+        }
+    }
+
+    /// Load parameters from a ViennaRNA 2.x parameter file
+    ///
+    /// Starts from the embedded parameters and replaces each free energy
+    /// section found in the file: `stack`, the `hairpin`, `bulge` and
+    /// `interior` initiations, every `mismatch_*` table, `dangle5` and
+    /// `dangle3`, `int11`, `int21` and `int22`, the `Triloops`, `Tetraloops`
+    /// and `Hexaloops`, `ML_params`, `NINIO` and `Misc`. The enthalpies are
+    /// ignored, and `dangles` is left on.
+    pub fn from_vienna_par<R: BufRead>(reader: R) -> Result<EnergyParams> {
+        let mut params = EnergyParams::turner2004();
+        let mut sections: Vec<(String, Vec<String>)> = Vec::new();
+
+        for line in reader.lines() {
+            let line = line?;
+            if line.starts_with("##") {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix("# ") {
+                sections.push((name.trim().to_owned(), Vec::new()));
+            } else if let Some((_, lines)) = sections.last_mut() {
+                lines.push(strip_comments(&line));
+            }
+        }
+
+        for (name, lines) in &sections {
+            if let Some(length) = match name.as_str() {
+                "Triloops" => Some(5),
+                "Tetraloops" => Some(6),
+                "Hexaloops" => Some(8),
+                _ => None,
+            } {
+                params.read_special_hairpins(name, lines, length)?;
+                continue;
+            }
+
+            let mut floats = Vec::new();
+            for token in lines.iter().flat_map(|x| x.split_whitespace()) {
+                floats.push(match token {
+                    "INF" => INF as f64,
+                    "DEF" => -50.0,
+                    _ => token
+                        .parse::<f64>()
+                        .map_err(|_| anyhow!("Invalid value {} in parameter file", token))?,
+                });
+            }
+            let values: Vec<i32> = floats.iter().map(|x| x.round() as i32).collect();
+            let values = &values[..];
+
+            match name.as_str() {
+                "stack" => {
+                    // With or without the trailing non standard pair column
+                    let width = if values.len() >= 49 { 7 } else { 6 };
+                    need(name, values, width * width)?;
+                    for (row, stack) in params.stack.iter_mut().enumerate() {
+                        for (column, value) in stack.iter_mut().enumerate() {
+                            *value = values[row * width + column];
+                        }
+                    }
+                }
+                "hairpin" | "bulge" | "interior" => {
+                    need(name, values, MAXLOOP + 1)?;
+                    let table = match name.as_str() {
+                        "hairpin" => &mut params.hairpin,
+                        "bulge" => &mut params.bulge,
+                        _ => &mut params.interior,
+                    };
+                    table.copy_from_slice(&values[..MAXLOOP + 1]);
+                }
+                "mismatch_hairpin" => read_mismatch(name, values, &mut params.mismatch_hairpin)?,
+                "mismatch_interior" => read_mismatch(name, values, &mut params.mismatch_interior)?,
+                "mismatch_interior_1n" => {
+                    read_mismatch(name, values, &mut params.mismatch_interior_1n)?
+                }
+                "mismatch_interior_23" => {
+                    read_mismatch(name, values, &mut params.mismatch_interior_23)?
+                }
+                "mismatch_multi" => read_mismatch(name, values, &mut params.mismatch_multi)?,
+                "mismatch_exterior" => read_mismatch(name, values, &mut params.mismatch_exterior)?,
+                "dangle5" | "dangle3" => {
+                    // Rows of 5 per pair type, with N before A, C, G, U
+                    need(name, values, 5 * NUM_PAIRS)?;
+                    let table = if name == "dangle5" {
+                        &mut params.dangle5
+                    } else {
+                        &mut params.dangle3
+                    };
+                    for (pair, row) in table.iter_mut().enumerate() {
+                        for (base, value) in row.iter_mut().enumerate() {
+                            *value = values[5 * pair + base + 1];
+                        }
+                    }
+                }
+                "int11" => params.int11 = Some(LoopTable::from_values(name, values, 2, 5)?),
+                "int21" => params.int21 = Some(LoopTable::from_values(name, values, 3, 5)?),
+                "int22" => params.int22 = Some(LoopTable::from_values(name, values, 4, 4)?),
+                "ML_params" => {
+                    need(name, values, 6)?;
+                    params.ml_base = values[0];
+                    params.ml_closing = values[2];
+                    params.ml_intern = values[4];
+                }
+                "NINIO" => {
+                    need(name, values, 3)?;
+                    params.ninio = values[0];
+                    params.max_ninio = values[2];
+                }
+                "Misc" => {
+                    need(name, values, 5)?;
+                    params.terminal_au = values[2];
+                    params.lxc = floats[4];
+                }
+                _ => {}
+            }
+        }
+
+        Ok(params)
+    }
+
+    /// Replace the special hairpins of one length with a section's lines of
+    /// loop sequence, energy and enthalpy
+    fn read_special_hairpins(&mut self, name: &str, lines: &[String], length: usize) -> Result<()> {
+        self.special_hairpins.retain(|(x, _)| x.len() != length);
+        for line in lines {
+            let mut tokens = line.split_whitespace();
+            let Some(loop_seq) = tokens.next() else {
+                continue;
+            };
+            let energy = tokens
+                .next()
+                .and_then(|x| x.parse::<i32>().ok())
+                .ok_or_else(|| anyhow!("Section {} has no energy for {}", name, loop_seq))?;
+            if loop_seq.len() != length {
+                return Err(anyhow!(
+                    "Section {} has loop {}, expected {} bases",
+                    name,
+                    loop_seq,
+                    length
+                ));
+            }
+            self.special_hairpins.push((loop_seq.to_owned(), energy));
+        }
+        Ok(())
+    }
+
+    /// Penalty for a helix ending in the given pair type
+    #[inline]
+    pub fn terminal(&self, pair: usize) -> i32 {
+        if pair > 1 {
+            self.terminal_au
+        } else {
+            0
+        }
+    }
+
+    /// Look up a loop table, extrapolating beyond `MAXLOOP`
+    fn loop_initiation(&self, table: &[i32; MAXLOOP + 1], size: usize) -> i32 {
+        if size <= MAXLOOP {
+            table[size]
+        } else {
+            table[MAXLOOP] + (self.lxc * (size as f64 / MAXLOOP as f64).ln()) as i32
+        }
+    }
+
+    /// Tabulated energy of a hairpin loop, given its bases including the
+    /// closing pair
+    fn special_hairpin(&self, bases: &[usize]) -> Option<i32> {
+        self.special_hairpins
+            .iter()
+            .find(|(loop_seq, _)| {
+                loop_seq.len() == bases.len()
+                    && loop_seq.bytes().map(base_index).eq(bases.iter().copied())
+            })
+            .map(|(_, energy)| *energy)
+    }
+
+    /// Energy of the hairpin loop closed by `(i, j)`
+    ///
+    /// `seq` is the sequence as composition counter indices, and `(i, j)`
+    /// must be able to pair.
+    pub fn hairpin(&self, seq: &[usize], i: usize, j: usize) -> i32 {
+        let size = j - i - 1;
+        if size < MIN_HAIRPIN {
+            return INF;
+        }
+        if let Some(energy) = self.special_hairpin(&seq[i..=j]) {
+            return energy;
+        }
+        let pair = pair_type(seq[i], seq[j]).unwrap();
+        let energy = self.loop_initiation(&self.hairpin, size);
+        if size == MIN_HAIRPIN {
+            energy + self.terminal(pair)
+        } else {
+            energy + mismatch(&self.mismatch_hairpin, pair, seq[i + 1], seq[j - 1])
+        }
+    }
+
+    /// Energy of the interior loop, bulge or stack closed by `(i, j)` on the
+    /// outside and `(k, l)` on the inside, with `i < k < l < j`
+    ///
+    /// 1x1, 1x2 and 2x2 loops come from their tables where those are loaded,
+    /// unless a base in the loop isn't a nucleotide.
+    pub fn interior(&self, seq: &[usize], i: usize, j: usize, k: usize, l: usize) -> i32 {
+        let outer = pair_type(seq[i], seq[j]).unwrap();
+        let inner = pair_type(seq[l], seq[k]).unwrap();
+        let (left, right) = (k - i - 1, j - l - 1);
+        let size = left + right;
+
+        if size == 0 {
+            return self.stack[outer][inner];
+        }
+        if left == 0 || right == 0 {
+            let energy = self.loop_initiation(&self.bulge, size);
+            return if size == 1 {
+                energy + self.stack[outer][inner]
+            } else {
+                energy + self.terminal(outer) + self.terminal(inner)
+            };
+        }
+
+        let tabulated = match (left, right) {
+            (1, 1) => self
+                .int11
+                .as_ref()
+                .and_then(|x| x.get(outer, inner, &[seq[i + 1], seq[j - 1]])),
+            (1, 2) => self
+                .int21
+                .as_ref()
+                .and_then(|x| x.get(outer, inner, &[seq[i + 1], seq[l + 1], seq[j - 1]])),
+            (2, 1) => self
+                .int21
+                .as_ref()
+                .and_then(|x| x.get(inner, outer, &[seq[l + 1], seq[i + 1], seq[k - 1]])),
+            (2, 2) => self.int22.as_ref().and_then(|x| {
+                x.get(
+                    outer,
+                    inner,
+                    &[seq[i + 1], seq[k - 1], seq[l + 1], seq[j - 1]],
+                )
+            }),
+            _ => None,
+        };
+        if let Some(energy) = tabulated {
+            return energy;
+        }
+
+        let mismatches = if left == 1 || right == 1 {
+            &self.mismatch_interior_1n
+        } else if left.min(right) == 2 && size == 5 {
+            &self.mismatch_interior_23
+        } else {
+            &self.mismatch_interior
+        };
+        self.loop_initiation(&self.interior, size)
+            + (self.ninio * left.abs_diff(right) as i32).min(self.max_ninio)
+            + mismatch(mismatches, outer, seq[i + 1], seq[j - 1])
+            + mismatch(mismatches, inner, seq[l + 1], seq[k - 1])
+    }
+
+    /// Dangling end energy of a stem of the given pair type, from the bases
+    /// on its 5' and 3' side where there are any
+    fn stem_dangles(
+        &self,
+        table: &MismatchTable,
+        pair: usize,
+        five: Option<usize>,
+        three: Option<usize>,
+    ) -> i32 {
+        if !self.dangles {
+            return 0;
+        }
+        match (five.filter(|x| *x != OTHER), three.filter(|x| *x != OTHER)) {
+            (Some(five), Some(three)) => table[pair][five][three],
+            (Some(five), None) => self.dangle5[pair][five],
+            (None, Some(three)) => self.dangle3[pair][three],
+            (None, None) => 0,
+        }
+    }
+
+    /// Energy of the pair `(i, j)` as a stem in the exterior loop
+    pub fn exterior_stem(&self, seq: &[usize], i: usize, j: usize) -> i32 {
+        let pair = pair_type(seq[i], seq[j]).unwrap();
+        let five = i.checked_sub(1).map(|x| seq[x]);
+        self.terminal(pair)
+            + self.stem_dangles(&self.mismatch_exterior, pair, five, seq.get(j + 1).copied())
+    }
+
+    /// Multiloop penalty for the pair `(i, j)` as a branch
+    pub fn ml_stem(&self, seq: &[usize], i: usize, j: usize) -> i32 {
+        let pair = pair_type(seq[i], seq[j]).unwrap();
+        let five = i.checked_sub(1).map(|x| seq[x]);
+        self.ml_intern
+            + self.terminal(pair)
+            + self.stem_dangles(&self.mismatch_multi, pair, five, seq.get(j + 1).copied())
+    }
+
+    /// Multiloop penalty for closing the loop with the pair `(i, j)`, seen
+    /// from inside the loop
+    pub fn ml_closing_stem(&self, seq: &[usize], i: usize, j: usize) -> i32 {
+        let pair = pair_type(seq[j], seq[i]).unwrap();
+        self.ml_closing
+            + self.ml_intern
+            + self.terminal(pair)
+            + self.stem_dangles(
+                &self.mismatch_multi,
+                pair,
+                Some(seq[j - 1]),
+                Some(seq[i + 1]),
+            )
+    }
+}
+
+/// Look up a mismatch table, with nothing for non nucleotides
+#[inline]
+fn mismatch(table: &MismatchTable, pair: usize, five: usize, three: usize) -> i32 {
+    if five == OTHER || three == OTHER {
+        0
+    } else {
+        table[pair][five][three]
+    }
+}
+
+/// Error if a section has fewer values than needed
+fn need(name: &str, values: &[i32], count: usize) -> Result<()> {
+    if values.len() < count {
+        Err(anyhow!(
+            "Section {} has {} values, expected at least {}",
+            name,
+            values.len(),
+            count
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Read a mismatch section, of 5x5 blocks per pair type with N before A, C,
+/// G, U
+fn read_mismatch(name: &str, values: &[i32], table: &mut MismatchTable) -> Result<()> {
+    need(name, values, 25 * NUM_PAIRS)?;
+    for (pair, mismatches) in table.iter_mut().enumerate() {
+        for (five, row) in mismatches.iter_mut().enumerate() {
+            for (three, value) in row.iter_mut().enumerate() {
+                *value = values[25 * pair + 5 * (five + 1) + three + 1];
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Remove `/* ... */` comments from a line
+fn strip_comments(line: &str) -> String {
+    let mut stripped = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(start) = rest.find("/*") {
+        stripped.push_str(&rest[..start]);
+        rest = match rest[start..].find("*/") {
+            Some(end) => &rest[start + end + 2..],
+            None => "",
+        };
+    }
+    stripped.push_str(rest);
+    stripped
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use crate::stats::composition::{A, U};
+    use crate::structure::mfe::encode;
+    use crate::structure::params::{self, EnergyParams, INF, NUM_PAIRS};
+    use std::io::Cursor;
+
+    /// A parameter file section of `count` values, each its own index
+    fn indexed_section(name: &str, count: usize) -> String {
+        let values: Vec<String> = (0..count).map(|x| x.to_string()).collect();
+        format!("# {}\n{}\n", name, values.join(" "))
+    }
+
+    /// The embedded parameters with dangles, special hairpins and small loop
+    /// tables of arbitrary energies, to exercise every energy term
+    pub(crate) fn full_params() -> EnergyParams {
+        let section = |name: &str, count: usize| {
+            let values: Vec<String> = (0..count)
+                .map(|x| ((x * 37) % 300) as i32 - 150)
+                .map(|x| x.to_string())
+                .collect();
+            format!("# {}\n{}\n", name, values.join(" "))
+        };
+        let par = [
+            section("int11", 7 * 7 * 25),
+            section("int21", 7 * 7 * 125),
+            section("int22", 6 * 6 * 256),
+            section("mismatch_multi", 7 * 25),
+            section("mismatch_exterior", 7 * 25),
+            section("dangle5", 7 * 5),
+            section("dangle3", 7 * 5),
+            "# Triloops\nCAAAG 250 0\n# Tetraloops\nGGAAAC -100 0\nCGAAAG 150 0\n".to_owned(),
+        ]
+        .concat();
+        let mut params = EnergyParams::from_vienna_par(Cursor::new(par)).unwrap();
+        params.dangles = true;
+        params
+    }
+
+    #[test]
+    fn test_stack_symmetry() {
+        // Stacking 5'XY/3'X'Y' is the same as reading it from the other
+        // strand, which swaps the roles of the two pairs
+        let params = EnergyParams::turner2004();
+        for outer in 0..6 {
+            for inner in 0..6 {
+                assert_eq!(params.stack[outer][inner], params.stack[inner][outer]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_pair_types() {
+        assert_eq!(params::pair_type(1, 2), Some(0));
+        assert_eq!(params::pair_type(3, 0), Some(5));
+        assert_eq!(params::pair_type(0, 0), None);
+    }
+
+    #[test]
+    fn test_parse_par() {
+        let par = "## RNAfold parameter file v2.0
+
+# stack
+/*  CG     GC     GU     UG     AU     UA     @  */
+  -240   -330   -210   -140   -210   -210   -140
+  -330   -340   -250   -150   -220   -240   -150
+  -210   -250    130    -50   -140   -130    130
+  -140   -150    -50     30    -60   -100     30
+  -210   -220   -140    -60   -110    -90    -60
+  -210   -240   -130   -100    -90   -100    -90
+  -140   -150    130     30    -60    -90    130
+
+# stack_enthalpies
+/*  CG     GC     GU     UG     AU     UA     @  */
+ -1060  -1340  -1210   -560  -1050  -1040   -560
+
+# hairpin
+   INF   INF   INF   500   560   570   540   600   550   640
+   650   660   670   678   686   694   701   707   713   719
+   725   730   735   740   744   749   753   757   761   765
+   769
+
+# interior
+   INF   INF   INF   INF   110   200   200   210   230   240
+   250   260   270   280   290   290   300   310   310   320
+   330   330   340   340   350   350   350   360   360   370
+   370
+
+# ML_params
+/* F = cu*n_unpaired + cc + ci*loop_degree (branches) */
+/*\t    cu\t    cu_dH\t    cc\t    cc_dH\t    ci\t    ci_dH  */
+	     0	     0	   900	  3000	   -80	  -220
+
+# NINIO
+/* Ninio = MIN(max, m*|n1-n2| */
+/*\t    m\t  m_dH     max  */
+	    60	   320	   300
+
+# Misc
+/* all parameters are pairs of 'energy enthalpy' */
+/*    DuplexInit     TerminalAU      LXC */
+   410   360    40   370  107.856000     0
+
+# END
+";
+        let params = EnergyParams::from_vienna_par(Cursor::new(par)).unwrap();
+        let default = EnergyParams::turner2004();
+
+        assert_eq!(params.stack[5][5], -100);
+        assert_eq!(params.stack[0], default.stack[0]);
+        assert_eq!(params.hairpin[3], 500);
+        assert_eq!(params.hairpin[2], INF);
+        assert_eq!(params.interior[4], 110);
+        assert_eq!(params.ml_closing, 900);
+        assert_eq!(params.ml_intern, -80);
+        assert_eq!(params.terminal_au, 40);
+        assert!((params.lxc - 107.856).abs() < 1e-9);
+        assert_eq!(params.bulge, default.bulge);
+
+        assert!(EnergyParams::from_vienna_par(Cursor::new("# stack\n1 2 3\n")).is_err());
+    }
+
+    #[test]
+    fn test_small_loop_tables() {
+        // Each value is its index in the file, which lists 7 pair types
+        // (then 6 for int22) and 5 bases (then 4) with N first
+        let par = [
+            indexed_section("int11", 7 * 7 * 25),
+            indexed_section("int21", 7 * 7 * 125),
+            indexed_section("int22", 6 * 6 * 256),
+        ]
+        .concat();
+        let params = EnergyParams::from_vienna_par(Cursor::new(par)).unwrap();
+
+        // 1x1 with A and U, between G-C and the inner pair read as C-G
+        let seq = encode("GAGAAAACUC");
+        assert_eq!(params.interior(&seq, 0, 9, 2, 7), 7 * 25 + 5 + 4);
+        // 1x2 with A opposite C and U
+        let seq = encode("CAGAAAACCUG");
+        assert_eq!(params.interior(&seq, 0, 10, 2, 7), 25 + 2 * 5 + 4);
+        // 2x1 is looked up from the inner pair, with C opposite A and U
+        let seq = encode("CAUGAAAACCG");
+        assert_eq!(params.interior(&seq, 0, 10, 3, 8), 2 * 25 + 5 + 4);
+        // 2x2 with A and U opposite C and U
+        let seq = encode("CAUGAAAACCUG");
+        assert_eq!(params.interior(&seq, 0, 11, 3, 8), 3 * 16 + 4 + 3);
+        // Non nucleotides fall back to the generic loop
+        let seq = encode("GNGAAAACUC");
+        assert!(params.interior(&seq, 0, 9, 2, 7) >= INF);
+        // Without the tables 1x1 loops can't form
+        let seq = encode("GAGAAAACUC");
+        assert!(EnergyParams::turner2004().interior(&seq, 0, 9, 2, 7) >= INF);
+    }
+
+    #[test]
+    fn test_special_hairpins() {
+        let par = "# Tetraloops\nGGAAAC -300 -1000\n\n# Triloops\nCAAAG 250 0\n";
+        let params = EnergyParams::from_vienna_par(Cursor::new(par)).unwrap();
+        let default = EnergyParams::turner2004();
+
+        let seq = encode("GGAAAC");
+        assert_eq!(params.hairpin(&seq, 0, 5), -300);
+        let seq = encode("CAAAG");
+        assert_eq!(params.hairpin(&seq, 0, 4), 250);
+        let seq = encode("GGAAGC");
+        assert_eq!(params.hairpin(&seq, 0, 5), default.hairpin(&seq, 0, 5));
+
+        assert!(EnergyParams::from_vienna_par(Cursor::new("# Tetraloops\nGAAAC 1 2\n")).is_err());
+        assert!(EnergyParams::from_vienna_par(Cursor::new("# Triloops\nCAAAG\n")).is_err());
+    }
+
+    #[test]
+    fn test_dangles() {
+        let mut params = EnergyParams::turner2004();
+        params.mismatch_exterior = [[[-100; 4]; 4]; NUM_PAIRS];
+        params.mismatch_multi = [[[-200; 4]; 4]; NUM_PAIRS];
+        params.dangle5[0][A] = -10;
+        params.dangle3[0][U] = -30;
+        let seq = encode("ACAAAAGU");
+
+        // -d0 ignores the neighbours
+        params.dangles = false;
+        assert_eq!(params.exterior_stem(&seq, 1, 6), 0);
+        params.dangles = true;
+        assert_eq!(params.exterior_stem(&seq, 1, 6), -100);
+        assert_eq!(params.exterior_stem(&seq[1..], 0, 5), -30);
+        assert_eq!(params.exterior_stem(&seq[..7], 1, 6), -10);
+        assert_eq!(params.exterior_stem(&seq[1..7], 0, 5), 0);
+        assert_eq!(params.ml_stem(&seq, 1, 6), params.ml_intern - 200);
+        // The closing pair of a multiloop is G-C from inside, with the A to
+        // its 5' side and C to its 3' side
+        let seq = encode("CAAAAAG");
+        params.mismatch_multi[1][A][A] = -50;
+        assert_eq!(
+            params.ml_closing_stem(&seq, 0, 6),
+            params.ml_closing + params.ml_intern - 50
+        );
+    }
+}
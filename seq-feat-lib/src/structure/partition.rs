@@ -0,0 +1,491 @@
+//! Partition function folding and base pair probabilities
+//!
+//! McCaskill's algorithm sums the Boltzmann weights of every structure,
+//! using the same loop decomposition and `EnergyParams` energies as
+//! `mfe::fold`, so the two always agree on what a structure costs. An outside
+//! pass then gives the probability of every base pair in the ensemble.
+//!
+//! The weights of long sequences overflow a float, so as in ViennaRNA each
+//! segment is scaled down per base by a factor estimated from the MFE.
+//! Memory is quadratic in the sequence length and time is cubic, a few times
+//! slower than MFE folding.
+use crate::structure::mfe::{encode, fold, Fold};
+use crate::structure::params::{pair_type, EnergyParams, INF, MAXLOOP, MIN_HAIRPIN};
+use wasm_bindgen::prelude::*;
+
+/// Features of the structure ensemble, in output order
+pub const ENSEMBLE_FEATURE_NAMES: [&str; 3] = [
+    "ensemble_free_energy",
+    "mfe_frequency",
+    "ensemble_diversity",
+];
+
+/// Gas constant times 37°C, in kcal/mol
+pub const KT: f64 = 0.00198717 * 310.15;
+
+/// Boltzmann weight of an energy in dcal/mol
+#[inline]
+fn boltzmann(energy: i32) -> f64 {
+    if energy >= INF {
+        0.0
+    } else {
+        (-(energy as f64) / 100.0 / KT).exp()
+    }
+}
+
+/// The structure ensemble of a sequence
+#[derive(Debug, Clone)]
+pub struct Ensemble {
+    /// Ensemble free energy in kcal/mol
+    pub free_energy: f64,
+    /// The minimum free energy structure
+    pub mfe: Fold,
+    n: usize,
+    probabilities: Vec<f64>,
+}
+
+impl Ensemble {
+    /// Probability that bases `i` and `j` pair
+    pub fn probability(&self, i: usize, j: usize) -> f64 {
+        self.probabilities[i.min(j) * self.n + i.max(j)]
+    }
+
+    /// The base pair probability matrix, indexed `i * n + j` with `i < j`
+    /// and zero below the diagonal
+    pub fn pair_probabilities(&self) -> &[f64] {
+        &self.probabilities
+    }
+
+    /// Probability that each base is unpaired
+    pub fn unpaired(&self) -> Vec<f64> {
+        (0..self.n)
+            .map(|i| 1.0 - (0..self.n).map(|j| self.probability(i, j)).sum::<f64>())
+            .collect()
+    }
+
+    /// Probability of the MFE structure in the ensemble
+    pub fn mfe_frequency(&self) -> f64 {
+        ((self.free_energy - self.mfe.energy) / KT).exp()
+    }
+
+    /// Ensemble diversity, the mean base pair distance between structures
+    /// in the ensemble, as ViennaRNA's `vrna_mean_bp_distance`
+    pub fn diversity(&self) -> f64 {
+        2.0 * self
+            .probabilities
+            .iter()
+            .map(|p| p * (1.0 - p))
+            .sum::<f64>()
+    }
+}
+
+/// Scaled weights of every segment, indexed `i * n + j`
+struct Tables {
+    n: usize,
+    /// Weight of the segment `i..=j` given `i` pairs with `j`
+    paired: Vec<f64>,
+    /// Weight of `i..=j` as part of a multiloop, with at least one branch
+    multi: Vec<f64>,
+    /// Weight of `i..=j` as part of a multiloop, with exactly one branch
+    /// which starts at `i`
+    multi_one: Vec<f64>,
+}
+
+impl Tables {
+    fn new(n: usize) -> Tables {
+        Tables {
+            n,
+            paired: vec![0.0; n * n],
+            multi: vec![0.0; n * n],
+            multi_one: vec![0.0; n * n],
+        }
+    }
+
+    #[inline]
+    fn idx(&self, i: usize, j: usize) -> usize {
+        i * self.n + j
+    }
+}
+
+/// Pairs `(k, l)` that can close an interior loop, bulge or stack inside
+/// `(i, j)`, with the same limits as MFE folding
+fn inner_pairs(i: usize, j: usize) -> impl Iterator<Item = (usize, usize)> {
+    (i + 1..(i + MAXLOOP + 2).min(j - MIN_HAIRPIN - 1)).flat_map(move |k| {
+        let left = k - i - 1;
+        let min_l = (k + MIN_HAIRPIN + 1).max((j - 1).saturating_sub(MAXLOOP - left));
+        (min_l..j).map(move |l| (k, l))
+    })
+}
+
+/// Weights that depend only on the sequence, shared by the inside and
+/// outside passes
+struct Weights<'a> {
+    seq: &'a [usize],
+    params: &'a EnergyParams,
+    /// Scale for a segment of each length
+    scale: Vec<f64>,
+    /// Weight of each number of unpaired bases in a multiloop, scaled
+    ml_unpaired: Vec<f64>,
+}
+
+impl Weights<'_> {
+    /// Weight of the multiloop closing pair `(i, j)`, scaled for both bases
+    fn ml_closing(&self, i: usize, j: usize) -> f64 {
+        boltzmann(self.params.ml_closing_stem(self.seq, i, j)) * self.scale[2]
+    }
+
+    /// Weight of the pair `(i, j)` as a multiloop branch
+    fn ml_stem(&self, i: usize, j: usize) -> f64 {
+        boltzmann(self.params.ml_stem(self.seq, i, j))
+    }
+
+    /// Weight of the pair `(i, j)` in the exterior loop
+    fn exterior_stem(&self, i: usize, j: usize) -> f64 {
+        boltzmann(self.params.exterior_stem(self.seq, i, j))
+    }
+
+    /// Weight of the loop between `(i, j)` and `(k, l)`, scaled for the
+    /// bases in `i..=j` but not `k..=l`
+    fn interior(&self, i: usize, j: usize, k: usize, l: usize) -> f64 {
+        boltzmann(self.params.interior(self.seq, i, j, k, l)) * self.scale[j - i + k - l]
+    }
+}
+
+/// Sum the weights of every structure of every segment
+fn inside(w: &Weights) -> (Tables, Vec<f64>) {
+    let seq = w.seq;
+    let n = seq.len();
+    let mut t = Tables::new(n);
+
+    for span in MIN_HAIRPIN + 1..n {
+        for i in 0..n - span {
+            let j = i + span;
+            let ij = t.idx(i, j);
+
+            if pair_type(seq[i], seq[j]).is_some() {
+                let mut q = boltzmann(w.params.hairpin(seq, i, j)) * w.scale[span + 1];
+                for (k, l) in inner_pairs(i, j) {
+                    let inner = t.paired[t.idx(k, l)];
+                    if inner > 0.0 {
+                        q += inner * w.interior(i, j, k, l);
+                    }
+                }
+                let closing = w.ml_closing(i, j);
+                for u in i + 2..j {
+                    q += t.multi[t.idx(i + 1, u - 1)] * t.multi_one[t.idx(u, j - 1)] * closing;
+                }
+                t.paired[ij] = q;
+                t.multi_one[ij] = q * w.ml_stem(i, j);
+            }
+            t.multi_one[ij] += t.multi_one[t.idx(i, j - 1)] * w.ml_unpaired[1];
+
+            let mut q = 0.0;
+            for u in i..j {
+                let branch = t.multi_one[t.idx(u, j)];
+                q += w.ml_unpaired[u - i] * branch;
+                if u > i {
+                    q += t.multi[t.idx(i, u - 1)] * branch;
+                }
+            }
+            t.multi[ij] = q;
+        }
+    }
+
+    let mut exterior = vec![1.0; n + 1];
+    for j in 0..n {
+        let mut q = exterior[j] * w.scale[1];
+        for (i, before) in exterior[..j].iter().enumerate() {
+            let paired = t.paired[t.idx(i, j)];
+            if paired > 0.0 {
+                q += before * paired * w.exterior_stem(i, j);
+            }
+        }
+        exterior[j + 1] = q;
+    }
+
+    (t, exterior)
+}
+
+/// Sum the weights of everything outside each segment, and so get the base
+/// pair probabilities
+///
+/// Each inside rule is run backwards, from the largest segments down, so
+/// every outside weight is complete before it's passed on.
+fn outside(w: &Weights, t: &Tables, exterior: &[f64]) -> Vec<f64> {
+    let seq = w.seq;
+    let n = seq.len();
+    let mut o = Tables::new(n);
+
+    let mut exterior_out = vec![0.0; n + 1];
+    exterior_out[n] = 1.0;
+    for j in (0..n).rev() {
+        let q = exterior_out[j + 1];
+        exterior_out[j] += q * w.scale[1];
+        for i in 0..j {
+            let paired = t.paired[t.idx(i, j)];
+            if paired > 0.0 {
+                let stem = w.exterior_stem(i, j);
+                exterior_out[i] += q * paired * stem;
+                o.paired[t.idx(i, j)] += q * exterior[i] * stem;
+            }
+        }
+    }
+
+    for span in (MIN_HAIRPIN + 1..n).rev() {
+        for i in 0..n - span {
+            let j = i + span;
+            let ij = t.idx(i, j);
+
+            let q = o.multi[ij];
+            if q > 0.0 {
+                for u in i..j {
+                    let uj = t.idx(u, j);
+                    let mut before = w.ml_unpaired[u - i];
+                    if u > i {
+                        before += t.multi[t.idx(i, u - 1)];
+                        o.multi[t.idx(i, u - 1)] += q * t.multi_one[uj];
+                    }
+                    o.multi_one[uj] += q * before;
+                }
+            }
+
+            let q = o.multi_one[ij];
+            if q > 0.0 {
+                o.multi_one[t.idx(i, j - 1)] += q * w.ml_unpaired[1];
+                if t.paired[ij] > 0.0 {
+                    o.paired[ij] += q * w.ml_stem(i, j);
+                }
+            }
+
+            let q = o.paired[ij];
+            if q > 0.0 && t.paired[ij] > 0.0 {
+                for (k, l) in inner_pairs(i, j) {
+                    if t.paired[t.idx(k, l)] > 0.0 {
+                        o.paired[t.idx(k, l)] += q * w.interior(i, j, k, l);
+                    }
+                }
+                let closing = q * w.ml_closing(i, j);
+                for u in i + 2..j {
+                    let multi = t.idx(i + 1, u - 1);
+                    let multi_one = t.idx(u, j - 1);
+                    o.multi[multi] += closing * t.multi_one[multi_one];
+                    o.multi_one[multi_one] += closing * t.multi[multi];
+                }
+            }
+        }
+    }
+
+    t.paired
+        .iter()
+        .zip(&o.paired)
+        .map(|(inside, outside)| inside * outside / exterior[n])
+        .collect()
+}
+
+/// Calculate the structure ensemble of a sequence
+pub fn ensemble(clean_seq: &str, params: &EnergyParams) -> Ensemble {
+    let seq = encode(clean_seq);
+    let n = seq.len();
+    let mfe = fold(clean_seq, params);
+
+    // Scale segments so the MFE structure of the whole sequence has a weight
+    // around one, with a little slack as the ensemble is always lower
+    let per_base = if n > 0 {
+        1.07 * mfe.energy / n as f64 / KT
+    } else {
+        0.0
+    };
+    let scale: Vec<f64> = (0..=n).map(|d| (per_base * d as f64).exp()).collect();
+    let ml_unpaired = (0..=n)
+        .map(|d| boltzmann(params.ml_base * d as i32) * scale[d])
+        .collect();
+    let weights = Weights {
+        seq: &seq,
+        params,
+        scale,
+        ml_unpaired,
+    };
+
+    let (tables, exterior) = inside(&weights);
+    let probabilities = outside(&weights, &tables, &exterior);
+
+    Ensemble {
+        free_energy: -KT * exterior[n].ln() + 1.07 * mfe.energy,
+        mfe,
+        n,
+        probabilities,
+    }
+}
+
+/// Ensemble free energy of a sequence in kcal/mol, with the embedded
+/// parameters
+#[wasm_bindgen(js_name=ensemble_free_energy)]
+pub fn ensemble_free_energy(clean_seq: &str) -> f64 {
+    ensemble(clean_seq, &EnergyParams::turner2004()).free_energy
+}
+
+/// Probability that each base is unpaired, with the embedded parameters
+#[wasm_bindgen(js_name=unpaired_probabilities)]
+pub fn unpaired_probabilities(clean_seq: &str) -> Vec<f64> {
+    ensemble(clean_seq, &EnergyParams::turner2004()).unpaired()
+}
+
+/// Calculate the features in `ENSEMBLE_FEATURE_NAMES` from an ensemble
+pub fn ensemble_features(ensemble: &Ensemble) -> Vec<f64> {
+    vec![
+        ensemble.free_energy,
+        ensemble.mfe_frequency(),
+        ensemble.diversity(),
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use crate::structure::dot_bracket;
+    use crate::structure::mfe::{self, encode};
+    use crate::structure::params::test::full_params;
+    use crate::structure::params::{pair_type, EnergyParams, MIN_HAIRPIN};
+    use crate::structure::partition::{self, KT};
+    use crate::utils;
+    use std::fs;
+    use std::io::BufReader;
+
+    /// Every secondary structure of `i..j`, as lists of pairs
+    fn all_structures(seq: &[usize], i: usize, j: usize) -> Vec<Vec<(usize, usize)>> {
+        if j <= i {
+            return vec![Vec::new()];
+        }
+        let mut structures = all_structures(seq, i + 1, j);
+        for k in i + MIN_HAIRPIN + 1..j {
+            if pair_type(seq[i], seq[k]).is_none() {
+                continue;
+            }
+            for inside in all_structures(seq, i + 1, k) {
+                for outside in all_structures(seq, k + 1, j) {
+                    let mut structure = vec![(i, k)];
+                    structure.extend(&inside);
+                    structure.extend(&outside);
+                    structures.push(structure);
+                }
+            }
+        }
+        structures
+    }
+
+    #[test]
+    fn test_matches_exhaustive_sum() {
+        for (params, seq) in [
+            "GGGAAAUCCCAGCU",
+            "GCGCUUCGGCGCAA",
+            "GGACUUCGGUCCGAAAGG",
+            "GGCAAAGCCGCAAAGCGG",
+            "GGCGGAAACCGCCAAAG",
+        ]
+        .into_iter()
+        .flat_map(|seq| [(EnergyParams::turner2004(), seq), (full_params(), seq)])
+        {
+            let n = seq.len();
+            let mut total = 0.0;
+            let mut pair_weights = vec![0.0; n * n];
+            for pairs in all_structures(&encode(seq), 0, n) {
+                let structure = dot_bracket::from_pairs(n, &pairs);
+                let energy = mfe::eval_structure(seq, &structure, &params).unwrap();
+                let weight = (-energy / KT).exp();
+                total += weight;
+                for (i, j) in pairs {
+                    pair_weights[i * n + j] += weight;
+                }
+            }
+
+            let ensemble = partition::ensemble(seq, &params);
+            assert!(
+                (ensemble.free_energy + KT * total.ln()).abs() < 1e-9,
+                "{}",
+                seq
+            );
+            for (i, j) in (0..n).flat_map(|i| (i + 1..n).map(move |j| (i, j))) {
+                let expected = pair_weights[i * n + j] / total;
+                assert!(
+                    (ensemble.probability(i, j) - expected).abs() < 1e-9,
+                    "{}",
+                    seq
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_features() {
+        let params = EnergyParams::turner2004();
+        let ensemble = partition::ensemble("AAAAAAAA", &params);
+        assert_eq!(ensemble.free_energy, 0.0);
+        assert_eq!(partition::ensemble_features(&ensemble), [0.0, 1.0, 0.0]);
+        assert_eq!(ensemble.unpaired(), [1.0; 8]);
+
+        // A strong hairpin dominates its ensemble
+        let ensemble = partition::ensemble("GGGGCGAAAGCCCC", &params);
+        assert!(ensemble.free_energy <= ensemble.mfe.energy);
+        assert!(ensemble.mfe_frequency() > 0.5 && ensemble.mfe_frequency() <= 1.0);
+        assert!(ensemble.probability(13, 0) > 0.5);
+        let unpaired = ensemble.unpaired();
+        assert!(unpaired[6] > 0.5 && unpaired[0] < 0.5);
+    }
+
+    #[test]
+    fn test_long_sequence() {
+        let seq = "GGGCCCAGACCGAAAGGUCACUGGGAAACCAGAGGGCCC".repeat(8);
+        let ensemble = partition::ensemble(&seq, &EnergyParams::turner2004());
+        assert!(ensemble.free_energy.is_finite());
+        assert!(ensemble.free_energy <= ensemble.mfe.energy);
+        assert!(ensemble.mfe_frequency() <= 1.0);
+        assert!(ensemble
+            .unpaired()
+            .iter()
+            .all(|p| (-1e-9..=1.0 + 1e-9).contains(p)));
+    }
+
+    /// Compare with RNAfold 2.x `-p` on `testdata/rnafold.fasta`, using the
+    /// ensemble free energies, MFE frequencies and diversities saved by
+    /// `testdata/rnafold.py`, with the saved parameter file and with the
+    /// embedded parameters
+    ///
+    /// RNAfold prints ensemble free energies to two decimals.
+    #[test]
+    #[ignore = "needs testdata/rnafold.tsv and testdata/rna_turner2004.par from testdata/rnafold.py"]
+    fn test_rnafold() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata");
+        let file = fs::File::open(format!("{}/rna_turner2004.par", dir)).unwrap();
+        let par = EnergyParams::from_vienna_par(BufReader::new(file)).unwrap();
+
+        let reference = fs::read_to_string(format!("{}/rnafold.tsv", dir)).unwrap();
+        for params in [&par, &EnergyParams::turner2004()] {
+            for line in reference.lines().skip(1) {
+                let fields: Vec<&str> = line.split('\t').collect();
+                let seq = utils::seq::clean(fields[1]).unwrap();
+                let [free_energy, mfe_frequency, diversity]: [f64; 3] =
+                    [fields[4], fields[5], fields[6]].map(|x| x.parse().unwrap());
+
+                let ensemble = partition::ensemble(&seq, params);
+                assert!(
+                    (ensemble.free_energy - free_energy).abs() < 0.006,
+                    "{} {}",
+                    fields[0],
+                    ensemble.free_energy
+                );
+                assert!(
+                    (ensemble.mfe_frequency() - mfe_frequency).abs()
+                        < 1e-3 * mfe_frequency.max(1e-3),
+                    "{} {}",
+                    fields[0],
+                    ensemble.mfe_frequency()
+                );
+                assert!(
+                    (ensemble.diversity() - diversity).abs() < 1e-2,
+                    "{} {}",
+                    fields[0],
+                    ensemble.diversity()
+                );
+            }
+        }
+    }
+}
@@ -1,7 +1,9 @@
 use anyhow::{anyhow, Result};
 use regex::{Match, Regex};
+use std::collections::HashMap;
 lazy_static! {
     pub static ref SEQ_CHECK: Regex = Regex::new(r"[[:alpha:]&&[^ACTGU]]").unwrap();
+    pub static ref PROTEIN_CHECK: Regex = Regex::new(r"[^ACDEFGHIKLMNPQRSTVWY]").unwrap();
 }
 
 /// Checks a sequence contains only uppercase AUCG characters
@@ -69,13 +71,146 @@ pub fn clean(seq: &str) -> Result<String> {
     }
 }
 
+/// Cleans up a protein sequence, ready for feature extraction
+///
+/// Converts to uppercase and drops a trailing stop `*`, as left by many
+/// translation tools. Errors if anything other than the 20 standard amino
+/// acids remains, including ambiguity codes like X and B.
+///
+/// # Examples
+/// ```
+/// use seq_feat::utils::seq::clean_protein;
+/// let peptide = clean_protein("mkvl*").unwrap();
+/// assert_eq!(peptide, "MKVL");
+/// ```
+pub fn clean_protein(seq: &str) -> Result<String> {
+    let uc_seq = seq.to_uppercase();
+    let uc_seq = uc_seq.strip_suffix('*').unwrap_or(&uc_seq);
+    if PROTEIN_CHECK.is_match(uc_seq) {
+        Err(anyhow!("Invalid characters found in protein sequence"))
+    } else {
+        Ok(uc_seq.to_string())
+    }
+}
+
+/// SplitMix64, a small seeded random number generator
+///
+/// Only used for shuffling, where it's fast and plenty random enough, and
+/// keeps results the same across platforms for a given seed.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// A random number in `0..n`
+    fn below(&mut self, n: usize) -> usize {
+        ((self.next_u64() as u128 * n as u128) >> 64) as usize
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i + 1));
+        }
+    }
+}
+
+/// Shuffle a sequence, keeping the counts of every kmer of length `k`
+///
+/// This is the Altschul-Erickson shuffle as generalised by Kandel et al.
+/// (1996) and uShuffle: the sequence is a walk through the graph of its
+/// (k-1)-mers, and a random Eulerian walk through the same graph gives a
+/// uniformly random sequence with the same kmers, starting and ending with
+/// the same (k-1)-mers. With `k` of 1 this is a plain shuffle, and with 2 it
+/// keeps the dinucleotide counts. The same seed always gives the same
+/// shuffle, and sequences shorter than `k` are returned as they are.
+pub fn kmer_shuffle(seq: &str, k: usize, seed: u64) -> String {
+    let bytes = seq.as_bytes();
+    let n = bytes.len();
+    if k == 0 || n < k {
+        return seq.to_owned();
+    }
+    let mut rng = SplitMix64(seed);
+
+    // Vertices are the (k-1)-mers, and each kmer is an edge to the vertex
+    // it ends with, labelled by its last base
+    let mut ids: HashMap<&[u8], usize> = HashMap::new();
+    let mut vertices = Vec::new();
+    for start in 0..=n - (k - 1) {
+        ids.entry(&bytes[start..start + k - 1]).or_insert_with(|| {
+            vertices.push(start);
+            vertices.len() - 1
+        });
+    }
+    let vertex = |start: usize| ids[&bytes[start..start + k - 1]];
+    let mut edges: Vec<Vec<(usize, u8)>> = vec![Vec::new(); vertices.len()];
+    for start in 0..=n - k {
+        edges[vertex(start)].push((vertex(start + 1), bytes[start + k - 1]));
+    }
+
+    // Pick the last edge out of every vertex so they form a random spanning
+    // tree into the final vertex, with Wilson's loop erased random walks.
+    // This guarantees the walk below can use every edge.
+    let last = vertex(n - (k - 1));
+    let mut in_tree = vec![false; vertices.len()];
+    let mut next = vec![0; vertices.len()];
+    in_tree[last] = true;
+    for start in 0..vertices.len() {
+        let mut u = start;
+        while !in_tree[u] {
+            next[u] = rng.below(edges[u].len());
+            u = edges[u][next[u]].0;
+        }
+        let mut u = start;
+        while !in_tree[u] {
+            in_tree[u] = true;
+            u = edges[u][next[u]].0;
+        }
+    }
+
+    // Every other edge can be taken in any order
+    for (u, out) in edges.iter_mut().enumerate() {
+        if u == last {
+            rng.shuffle(out);
+        } else {
+            let end = out.len() - 1;
+            out.swap(next[u], end);
+            rng.shuffle(&mut out[..end]);
+        }
+    }
+
+    let mut shuffled = bytes[..k - 1].to_vec();
+    let mut taken = vec![0; vertices.len()];
+    let mut u = vertex(0);
+    for _ in 0..=n - k {
+        let (v, base) = edges[u][taken[u]];
+        taken[u] += 1;
+        shuffled.push(base);
+        u = v;
+    }
+    String::from_utf8(shuffled).unwrap()
+}
+
+/// Shuffle a sequence, keeping its dinucleotide counts
+///
+/// See `kmer_shuffle`, this is the same with `k` of 2.
+pub fn dinucleotide_shuffle(seq: &str, seed: u64) -> String {
+    kmer_shuffle(seq, 2, seed)
+}
+
 #[cfg(test)]
 mod test {
     use crate::utils::seq;
+    use std::collections::HashMap;
     #[test]
     fn test_seq_ok() {
-        assert_eq!(seq::is_ok("ACTGUAAA"), true);
-        assert_eq!(seq::is_ok("WTFLOL"), false);
+        assert!(seq::is_ok("ACTGUAAA"));
+        assert!(!seq::is_ok("WTFLOL"));
     }
 
     #[test]
@@ -91,4 +226,54 @@ mod test {
             "Invalid characters found in sequence"
         );
     }
+
+    fn kmer_counts(seq: &str, k: usize) -> HashMap<&str, usize> {
+        let mut counts = HashMap::new();
+        for start in 0..=seq.len() - k {
+            *counts.entry(&seq[start..start + k]).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn test_clean_protein() {
+        assert_eq!(seq::clean_protein("mkWv*").unwrap(), "MKWV");
+        assert!(seq::clean_protein("MKXV").is_err());
+        assert!(seq::clean_protein("MK*V").is_err());
+    }
+
+    #[test]
+    fn test_kmer_shuffle() {
+        let sequence = "AUGGCUACGUAGCCAUUAGCGGAUCCAUGCAAAUGCGCGUAUAUCCGAU";
+        for k in 1..=4 {
+            let shuffled = seq::kmer_shuffle(sequence, k, 7);
+            assert_eq!(shuffled.len(), sequence.len());
+            assert_eq!(kmer_counts(&shuffled, k), kmer_counts(sequence, k));
+            assert_eq!(shuffled[..k - 1], sequence[..k - 1]);
+            assert_eq!(shuffled, seq::kmer_shuffle(sequence, k, 7));
+        }
+        assert_ne!(
+            seq::dinucleotide_shuffle(sequence, 1),
+            seq::dinucleotide_shuffle(sequence, 2)
+        );
+        assert_eq!(seq::dinucleotide_shuffle("A", 1), "A");
+        assert_eq!(seq::dinucleotide_shuffle("", 1), "");
+    }
+
+    #[test]
+    fn test_shuffle_uniform() {
+        // The only sequences with the dinucleotides of AAUAAU, which start
+        // with A and end with U, are these three, and each should come up
+        // about equally often
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for seed in 0..3000 {
+            *counts
+                .entry(seq::dinucleotide_shuffle("AAUAAU", seed))
+                .or_insert(0) += 1;
+        }
+        assert_eq!(counts.len(), 3);
+        for sequence in ["AAUAAU", "AUAAAU", "AAAUAU"] {
+            assert!((850..1150).contains(&counts[sequence]), "{:?}", counts);
+        }
+    }
 }
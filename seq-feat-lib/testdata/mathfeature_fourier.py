@@ -0,0 +1,113 @@
+"""Generate MathFeature reference values for the Fourier spectrum statistics
+
+Runs MathFeature's methods/Mappings.py on fourier.fasta with each numeric
+mapping, and writes the statistics to mathfeature_fourier.csv under
+seq_feat's column names, for fourier's test_spectrum_stats_mathfeature:
+
+    python3 mathfeature_fourier.py /path/to/MathFeature
+
+Column names are matched ignoring case and punctuation, and the script
+stops if any MathFeature column can't be matched, rather than guessing.
+"""
+import csv
+import re
+import subprocess
+import sys
+import tempfile
+from pathlib import Path
+
+HERE = Path(__file__).resolve().parent
+
+# Mappings.py --representation codes, from its --help
+REPRESENTATIONS = {
+    "binary": "1",
+    "z_curve": "2",
+    "real": "3",
+    "integer": "4",
+    "eiip": "5",
+    "complex": "6",
+    "atomic_number": "7",
+}
+
+# fourier::SPECTRUM_STAT_NAMES
+NAMES = [
+    "average",
+    "median",
+    "maximum",
+    "minimum",
+    "peak",
+    "none_levated_peak",
+    "sd_population",
+    "sd_sample",
+    "percentile_15",
+    "percentile_25",
+    "percentile_50",
+    "percentile_75",
+    "amplitude",
+    "variance",
+    "interquartile_range",
+    "semi_interquartile_range",
+    "coefficient_of_variation",
+    "skewness",
+    "kurtosis",
+]
+
+ALIASES = {
+    "populationstandarddeviation": "sd_population",
+    "standarddeviationpopulation": "sd_population",
+    "samplestandarddeviation": "sd_sample",
+    "standarddeviationsample": "sd_sample",
+}
+
+
+def normalise(name):
+    return re.sub("[^a-z0-9]", "", name.lower())
+
+
+def column_name(name):
+    key = normalise(name)
+    if key in ALIASES:
+        return ALIASES[key]
+    for ours in NAMES:
+        if normalise(ours) == key:
+            return ours
+    raise SystemExit(f"Can't match MathFeature column {name!r}")
+
+
+def main():
+    mathfeature = Path(sys.argv[1])
+    rows = []
+    with tempfile.TemporaryDirectory() as tmp:
+        for mapping, representation in REPRESENTATIONS.items():
+            output = Path(tmp) / f"{mapping}.csv"
+            subprocess.run(
+                [
+                    sys.executable,
+                    str(mathfeature / "methods" / "Mappings.py"),
+                    "-i",
+                    str(HERE / "fourier.fasta"),
+                    "-o",
+                    str(output),
+                    "-l",
+                    "reference",
+                    "-r",
+                    representation,
+                ],
+                check=True,
+            )
+            with open(output) as handle:
+                for record in csv.DictReader(handle):
+                    row = {"name": record.pop("nameseq").strip(), "mapping": mapping}
+                    record.pop("label", None)
+                    for name, value in record.items():
+                        row[column_name(name)] = value.strip()
+                    rows.append(row)
+
+    with open(HERE / "mathfeature_fourier.csv", "w", newline="") as handle:
+        writer = csv.DictWriter(handle, fieldnames=["name", "mapping"] + NAMES)
+        writer.writeheader()
+        writer.writerows(rows)
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,77 @@
+"""Generate RNAfold reference folds for the MFE and partition function models
+
+Folds rnafold.fasta with RNAfold 2.x from ViennaRNA, with its default double
+dangles and the partition function on, and writes the MFE structures and
+energies, ensemble free energies, MFE frequencies and ensemble diversities to
+rnafold.tsv along with a copy of the parameter file, for mfe's test_rnafold
+and partition's test_rnafold:
+
+    python3 rnafold.py /path/to/ViennaRNA/misc/rna_turner2004.par
+
+The parameter file is passed to RNAfold too, so the reference and the tests
+always use the same energies.
+"""
+import csv
+import re
+import shutil
+import subprocess
+import sys
+from pathlib import Path
+
+HERE = Path(__file__).resolve().parent
+
+SEQUENCE = re.compile(r"^[ACGUTN]+$", re.IGNORECASE)
+# The MFE structure, with its energy in brackets
+STRUCTURE = re.compile(r"^([().]+)\s+\(\s*(-?\d+\.\d+)\)$")
+# The pair probability string, with the ensemble free energy
+ENSEMBLE = re.compile(r"^\S+\s+\[\s*(-?\d+\.\d+)\]$")
+FREQUENCY = re.compile(
+    r"frequency of mfe structure in ensemble\s+(\S+);\s+ensemble diversity\s+(\S+)"
+)
+
+
+def main():
+    par = Path(sys.argv[1])
+    output = subprocess.run(
+        ["RNAfold", "-d2", "-p", "--noPS", "--noDP", "-P", str(par)],
+        stdin=open(HERE / "rnafold.fasta"),
+        capture_output=True,
+        text=True,
+        check=True,
+        cwd=HERE,
+    ).stdout
+
+    rows = []
+    record = {}
+    for line in output.splitlines():
+        line = line.strip()
+        if line.startswith(">"):
+            record = {"name": line[1:].split()[0]}
+        elif SEQUENCE.match(line):
+            record["sequence"] = line.upper()
+        elif match := STRUCTURE.match(line):
+            record["structure"], record["energy"] = match.groups()
+        elif match := ENSEMBLE.match(line):
+            record["ensemble_energy"] = match.group(1)
+        elif match := FREQUENCY.search(line):
+            record["mfe_frequency"], record["diversity"] = match.groups()
+            rows.append(record)
+
+    columns = [
+        "name",
+        "sequence",
+        "structure",
+        "energy",
+        "ensemble_energy",
+        "mfe_frequency",
+        "diversity",
+    ]
+    with open(HERE / "rnafold.tsv", "w", newline="") as handle:
+        writer = csv.writer(handle, delimiter="\t", lineterminator="\n")
+        writer.writerow(columns)
+        writer.writerows([record[column] for column in columns] for record in rows)
+    shutil.copyfile(par, HERE / "rna_turner2004.par")
+
+
+if __name__ == "__main__":
+    main()